@@ -3,19 +3,93 @@
 extern crate proc_macro;
 
 use crate::proc_macro::TokenStream;
-use quote::ToTokens;
-use syn::{self, parse_macro_input, parse_quote, AttributeArgs, FnArg, ItemFn, Stmt};
+use quote::{quote, ToTokens};
+use syn::ext::IdentExt;
+use syn::parse::{Parse, ParseStream};
+use syn::{
+    self, parse_macro_input, parse_quote, FnArg, Ident, ItemFn, LitStr, Pat, ReturnType, Stmt,
+    Token,
+};
 
+/// Custom attribute to trace the parser.
+///
+/// The options are given like `#[tracable_parser(show_output, name = "number")]`.
+///
+/// * `show_output` / `show_args` : show the output or the other arguments by `Debug`
+/// * `name = "..."` : the parser name in the trace instead of the function name
+/// * `group = "..."` : the group to be enabled or disabled by `TracableInfo::enable_group`
+/// * `skip` : expand to the plain function
+/// * `if = flag` : trace the parser only while `flag()` returns `true`
+/// * `memoize` : cache the successful results by the parser name and the offset until the next root parse
+///
+/// `memoize` stores the output in the thread-local cache,
+/// so the output type must be `Clone + Send + 'static`, and borrowed outputs like `&str` can't be memoized.
+/// It can't be used with async functions or functions taking other arguments than the input,
+/// because the cache key doesn't include them.
 #[proc_macro_attribute]
 pub fn tracable_parser(attr: TokenStream, item: TokenStream) -> TokenStream {
-    let attr = parse_macro_input!(attr as AttributeArgs);
+    let options = parse_macro_input!(attr as Options);
     let item = parse_macro_input!(item as ItemFn);
-    impl_tracable_parser(&attr, &item)
+    impl_tracable_parser(&options, &item)
 }
 
-fn impl_tracable_parser(_attr: &AttributeArgs, item: &ItemFn) -> TokenStream {
-    let default = impl_tracable_parser_default(&item);
-    let trace = impl_tracable_parser_trace(&item);
+/// Options given by `#[tracable_parser(...)]`.
+#[derive(Default)]
+struct Options {
+    show_output: bool,
+    show_args: bool,
+    name: Option<LitStr>,
+    group: Option<LitStr>,
+    skip: bool,
+    memoize: bool,
+    condition: Option<syn::Path>,
+}
+
+impl Parse for Options {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut options = Options::default();
+        while !input.is_empty() {
+            // `if` is a keyword, so the option names are parsed as any identifier
+            let key = Ident::parse_any(input)?;
+            match key.to_string().as_str() {
+                "show_output" => options.show_output = true,
+                "show_args" => options.show_args = true,
+                "skip" => options.skip = true,
+                "memoize" => options.memoize = true,
+                "name" => {
+                    input.parse::<Token![=]>()?;
+                    options.name = Some(input.parse()?);
+                }
+                "group" => {
+                    input.parse::<Token![=]>()?;
+                    options.group = Some(input.parse()?);
+                }
+                "if" => {
+                    input.parse::<Token![=]>()?;
+                    options.condition = Some(input.parse()?);
+                }
+                _ => {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        format!("unknown option of #[tracable_parser]: {}", key),
+                    ))
+                }
+            }
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        Ok(options)
+    }
+}
+
+fn impl_tracable_parser(options: &Options, item: &ItemFn) -> TokenStream {
+    if options.skip {
+        return item.into_token_stream().into();
+    }
+
+    let default = impl_tracable_parser_default(item);
+    let trace = impl_tracable_parser_trace(options, item);
 
     let mut item = item.clone();
 
@@ -30,37 +104,177 @@ fn impl_tracable_parser_default(item: &ItemFn) -> Stmt {
     let body = item.block.as_ref();
     parse_quote! {
         #[cfg(not(feature = "trace"))]
-        {
-            #body
-        }
+        #body
     }
 }
 
-fn impl_tracable_parser_trace(item: &ItemFn) -> Stmt {
+fn impl_tracable_parser_trace(options: &Options, item: &ItemFn) -> Stmt {
     let ident = &item.sig.ident;
+    let name = match &options.name {
+        Some(x) => quote!(#x),
+        None => quote!(stringify!(#ident)),
+    };
+
+    // The receiver of methods is skipped, and the first typed argument is the input
+    let mut args = item.sig.inputs.iter().filter_map(|x| match x {
+        FnArg::Typed(arg) => Some(&arg.pat),
+        FnArg::Receiver(_) => None,
+    });
+    let input = match args.next() {
+        Some(x) => x,
+        None => panic!("function with #[tracable_parser] must have an argument"),
+    };
+    let (input_expr, input_mut) = match input.as_ref() {
+        Pat::Ident(x) => (&x.ident, x.mutability.is_some()),
+        _ => panic!("the first argument of #[tracable_parser] must be an identifier"),
+    };
+
+    // The memoized result is keyed by the name and the offset, so the other arguments would be ignored
+    if options.memoize && args.clone().next().is_some() {
+        panic!(
+            "function with other arguments than the input can't be memoized by #[tracable_parser]"
+        );
+    }
+
+    // The other arguments are passed through, and shown by `show_args`
+    let forward_trace = if options.show_args {
+        let args = args.filter_map(|x| match x.as_ref() {
+            Pat::Ident(x) => Some(&x.ident),
+            _ => None,
+        });
+        quote! {
+            nom_tracable::forward_trace_args(
+                #input_expr,
+                #name,
+                &[#((stringify!(#args), &#args as &dyn core::fmt::Debug)),*],
+            )
+        }
+    } else {
+        quote!(nom_tracable::forward_trace(#input_expr, #name))
+    };
+
+    let group = options
+        .group
+        .as_ref()
+        .map(|group| quote!(nom_tracable::register_group(#name, #group);));
 
-    let input = if let Some(x) = &item.sig.inputs.first() {
-        match x {
-            FnArg::Typed(arg) => &arg.pat,
-            _ => panic!("function with #[tracable_parser] must have an argument"),
+    // The mutable input is assigned to keep `mut` of the argument used
+    let forward_trace: Stmt = if input_mut {
+        parse_quote! {
+            let depth = {
+                let (depth, traced_input) = #forward_trace;
+                #input_expr = traced_input;
+                depth
+            };
         }
     } else {
-        panic!("function with #[tracable_parser] must have an argument");
+        parse_quote!(let (depth, #input) = #forward_trace;)
     };
 
     let body = item.block.as_ref();
 
+    let output = match &item.sig.output {
+        ReturnType::Type(_, ty) => ty,
+        _ => panic!("function with #[tracable_parser] must return IResult"),
+    };
+
+    // The error type is concrete here, so the details are shown if it implements `TraceError`
+    let detail: Stmt = parse_quote! {
+        let detail = {
+            #[allow(unused_imports)]
+            use nom_tracable::{ErrorProbeDetail as _, ErrorProbeFallback as _};
+            (&nom_tracable::ErrorProbe::of(&body_ret)).detail()
+        };
+    };
+
+    let backward_trace: syn::Path = if options.show_output {
+        parse_quote!(nom_tracable::backward_trace_output)
+    } else {
+        parse_quote!(nom_tracable::backward_trace_detail)
+    };
+
+    // The body is a closure of the input, which is called without the trace if the condition is false
+    let await_body = item.sig.asyncness.map(|_| quote!(.await));
+    let condition: Option<Stmt> = options.condition.as_ref().map(|condition| {
+        parse_quote! {
+            if !#condition() {
+                return body(#input_expr)#await_body;
+            }
+        }
+    });
+
+    // The input is cloned before the body takes it, to rebuild the rest of a memoized result
+    let (memo_input, body_call): (Option<Stmt>, _) = if options.memoize {
+        (
+            Some(parse_quote!(let memo_input = core::clone::Clone::clone(&#input_expr);)),
+            quote!(nom_tracable::memoize(memo_input, #name, || body(#input_expr))),
+        )
+    } else {
+        (None, quote!(body(#input_expr)))
+    };
+
+    // The error is made before the body takes the input
+    let limited: Stmt = parse_quote! {
+        let limited = {
+            let limited: Option<#output> = None;
+            if nom_tracable::depth_limit_exceeded(&#input_expr) {
+                #[allow(unused_imports)]
+                use nom_tracable::{LimitProbeError as _, LimitProbeFallback as _};
+                (&nom_tracable::LimitProbe::of(&limited)).limit_error(&#input_expr, #name)
+            } else {
+                None
+            }
+        };
+    };
+
+    if item.sig.asyncness.is_some() {
+        if options.memoize {
+            panic!("async function with #[tracable_parser] can't be memoized");
+        }
+
+        // The trace context is carried by the future because the task may move between threads
+        return parse_quote! {
+            #[cfg(feature = "trace")]
+            {
+                let body = nom_tracable::parser_body(&#input_expr, |#input| async move { #body });
+                #condition
+                nom_tracable::task_context(async move {
+                    #group
+                    let _unwind = nom_tracable::unwind_guard();
+                    #forward_trace
+                    #limited
+
+                    let body_ret: #output = match limited {
+                        Some(x) => Err(x),
+                        None => body(#input_expr).await,
+                    };
+                    #detail
+
+                    #backward_trace(body_ret, #name, depth, detail)
+                })
+                .await
+            }
+        };
+    }
+
     parse_quote! {
         #[cfg(feature = "trace")]
         {
-            let (depth, #input) = nom_tracable::forward_trace(#input, stringify!(#ident));
+            let body = nom_tracable::parser_body(&#input_expr, |#input| #body);
+            #condition
+            #group
+            let _unwind = nom_tracable::unwind_guard();
+            #forward_trace
+            #memo_input
+            #limited
 
-            let body_ret = {
-                let body = || { #body };
-                body()
+            let body_ret: #output = match limited {
+                Some(x) => Err(x),
+                None => #body_call,
             };
+            #detail
 
-            nom_tracable::backward_trace(body_ret, stringify!(#ident), depth)
+            #backward_trace(body_ret, #name, depth, detail)
         }
     }
 }
@@ -38,7 +38,7 @@ impl<'a> Tracable for Span<'a> {
         let fragment: String = String::from_utf8_lossy(self.0.fragment())
             .lines()
             .next()
-            .unwrap_or_else(|| "")
+            .unwrap_or("")
             .chars()
             .take(info.fragment_width)
             .collect();
@@ -48,6 +48,14 @@ impl<'a> Tracable for Span<'a> {
     fn header(&self) -> String {
         self.0.header()
     }
+
+    fn offset(&self) -> usize {
+        self.0.offset()
+    }
+
+    fn fragment_bytes(&self) -> &[u8] {
+        self.0.fragment_bytes()
+    }
 }
 impl<'a> InputIter for Span<'a> {
     type Item = u8;
@@ -0,0 +1,154 @@
+use crate::{writer, Trace, TraceEventKind};
+use std::collections::BTreeMap;
+use std::io::Write;
+
+/// The minimum number of successful calls of a parser to judge its distribution.
+const MIN_SAMPLES: usize = 4;
+
+/// Kind of `Anomaly`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnomalyKind {
+    /// The parser consumed nothing though it usually consumes input.
+    Zero,
+    /// The parser consumed far more input than usual.
+    Large,
+}
+
+/// Struct to have a successful call whose consumed length is unusual for the parser.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Anomaly {
+    pub kind: AnomalyKind,
+    pub parser: String,
+    /// Offset of the input at the entry.
+    pub offset: usize,
+    /// Consumed length of the call.
+    pub consumed: usize,
+    /// Median of the consumed length of the parser in the same trace.
+    pub median: usize,
+}
+
+impl Trace {
+    /// Get the consumed length of each successful call as `(parser, entry offset, consumed)`.
+    fn consumed(&self) -> Vec<(&str, usize, usize)> {
+        let mut ret = Vec::new();
+        let mut stack = Vec::new();
+        for event in &self.events {
+            match event.kind {
+                TraceEventKind::Forward => stack.push(event.offset),
+                TraceEventKind::Ok => {
+                    if let Some(entry) = stack.pop() {
+                        let consumed = event.offset.saturating_sub(entry);
+                        ret.push((event.parser.as_str(), entry, consumed));
+                    }
+                }
+                TraceEventKind::Err => {
+                    stack.pop();
+                }
+                TraceEventKind::Custom | TraceEventKind::Bookmark => (),
+            }
+        }
+        ret
+    }
+
+    /// Find successful calls whose consumed length is zero or larger than `factor` times of
+    /// the median of the parser in the same trace.
+    ///
+    /// Parsers called successfully less than 4 times are not judged.
+    pub fn anomalies(&self, factor: f64) -> Vec<Anomaly> {
+        let consumed = self.consumed();
+
+        let mut lengths: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+        for (parser, _, x) in &consumed {
+            lengths.entry(parser).or_default().push(*x);
+        }
+        let medians: BTreeMap<&str, usize> = lengths
+            .into_iter()
+            .filter(|(_, x)| x.len() >= MIN_SAMPLES)
+            .map(|(parser, mut x)| {
+                x.sort_unstable();
+                (parser, x[x.len() / 2])
+            })
+            .collect();
+
+        let mut ret = Vec::new();
+        for (parser, offset, consumed) in consumed {
+            let median = match medians.get(parser) {
+                Some(x) if *x > 0 => *x,
+                _ => continue,
+            };
+            let kind = if consumed == 0 {
+                AnomalyKind::Zero
+            } else if consumed as f64 > median as f64 * factor {
+                AnomalyKind::Large
+            } else {
+                continue;
+            };
+            ret.push(Anomaly {
+                kind,
+                parser: String::from(parser),
+                offset,
+                consumed,
+                median,
+            });
+        }
+        ret
+    }
+
+    /// Find anomalies by `anomalies`, and show the report.
+    pub fn show_anomalies(&self, factor: f64) -> Vec<Anomaly> {
+        let anomalies = self.anomalies(factor);
+
+        let max_parser_len = anomalies
+            .iter()
+            .map(|x| x.parser.len())
+            .max()
+            .unwrap_or(0)
+            .max("parser".len());
+
+        let mut lock = writer();
+
+        writeln!(
+            lock,
+            "\n{:<parser$} | {:<5} | {:>10} | {:>10} | {:>10}",
+            "parser",
+            "kind",
+            "offset",
+            "consumed",
+            "median",
+            parser = max_parser_len,
+        )
+        .unwrap();
+
+        writeln!(
+            lock,
+            "{} | {} | {} | {} | {}",
+            "-".repeat(max_parser_len),
+            "-".repeat(5),
+            "-".repeat(10),
+            "-".repeat(10),
+            "-".repeat(10),
+        )
+        .unwrap();
+
+        for x in &anomalies {
+            let kind = match x.kind {
+                AnomalyKind::Zero => "zero",
+                AnomalyKind::Large => "large",
+            };
+            writeln!(
+                lock,
+                "{:<parser$} | {:<5} | {:>10} | {:>10} | {:>10}",
+                x.parser,
+                kind,
+                x.offset,
+                x.consumed,
+                x.median,
+                parser = max_parser_len,
+            )
+            .unwrap();
+        }
+        writeln!(lock).unwrap();
+
+        anomalies
+    }
+}
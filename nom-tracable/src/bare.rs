@@ -0,0 +1,355 @@
+use crate::{is_enabled, ErrorDetail, HasTracableInfo, Tracable, TracableInfo};
+use core::cell::Cell;
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicPtr, Ordering};
+use critical_section::Mutex;
+use nom::IResult;
+
+/// The maximum length of a trace line. Longer lines are truncated.
+const LINE_LEN: usize = 128;
+
+/// The width of the count columns.
+const COUNT_WIDTH: usize = 10;
+
+#[derive(Clone, Copy)]
+struct Counters {
+    depth: usize,
+    forward: usize,
+    backward: usize,
+    /// The parsers entered in the root parse which was not traced by `set_enabled`.
+    untraced: usize,
+}
+
+// The counters are protected by a critical section because the tracer may be used from interrupt handlers,
+// and targets like thumbv6m have no atomic read-modify-write operations.
+// They are shared by all parses, so concurrent parses are counted together.
+static COUNTERS: Mutex<Cell<Counters>> = Mutex::new(Cell::new(Counters {
+    depth: 0,
+    forward: 0,
+    backward: 0,
+    untraced: 0,
+}));
+
+/// Update the counters in a critical section.
+fn update<R>(f: impl FnOnce(&mut Counters) -> R) -> R {
+    critical_section::with(|cs| {
+        let counters = COUNTERS.borrow(cs);
+        let mut x = counters.get();
+        let ret = f(&mut x);
+        counters.set(x);
+        ret
+    })
+}
+
+// `fn(&str)` is stored as a pointer because atomics of function pointers are not available.
+static SINK: AtomicPtr<()> = AtomicPtr::new(core::ptr::null_mut());
+
+pub(crate) fn set_sink(sink: Option<fn(&str)>) {
+    let sink = sink.map_or(core::ptr::null_mut(), |x| x as *mut ());
+    SINK.store(sink, Ordering::Release);
+}
+
+fn sink() -> Option<fn(&str)> {
+    let sink = SINK.load(Ordering::Acquire);
+    if sink.is_null() {
+        None
+    } else {
+        // SAFETY: the pointer is stored from `fn(&str)` by `set_sink`
+        Some(unsafe { core::mem::transmute::<*mut (), fn(&str)>(sink) })
+    }
+}
+
+/// Buffer of a trace line on the stack.
+struct Line {
+    buf: [u8; LINE_LEN],
+    len: usize,
+}
+
+impl Line {
+    fn new() -> Self {
+        Line {
+            buf: [0; LINE_LEN],
+            len: 0,
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        // The buffer is written by `write_str` only, so it is valid UTF-8
+        core::str::from_utf8(&self.buf[..self.len]).unwrap_or("")
+    }
+}
+
+impl Write for Line {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            let len = c.len_utf8();
+            if self.len + len > LINE_LEN {
+                return Ok(());
+            }
+            c.encode_utf8(&mut self.buf[self.len..]);
+            self.len += len;
+        }
+        Ok(())
+    }
+}
+
+fn emit_line(args: fmt::Arguments) {
+    if let Some(sink) = sink() {
+        let mut line = Line::new();
+        let _ = line.write_fmt(args);
+        sink(line.as_str());
+    }
+}
+
+/// Groups are not supported without `std`, so all parsers are displayed.
+pub fn register_group(_name: &str, _group: &'static str) {}
+
+/// The depth is not limited without `std`.
+pub fn depth_limit_exceeded<T>(_input: &T) -> bool {
+    false
+}
+
+/// Memoization is not supported without `std`, so the body runs at each call.
+///
+/// The output has the same bound as the memoization with `std`,
+/// so the parsers compiled with `bare` are compiled with `trace` too.
+pub fn memoize<T, U: Clone + Send + 'static, V, F: FnOnce() -> IResult<T, U, V>>(
+    _input: T,
+    _name: &str,
+    body: F,
+) -> IResult<T, U, V> {
+    body()
+}
+
+/// The counters are not restored without `std`, because panics usually abort on embedded targets.
+pub struct UnwindGuard;
+
+/// Function to create the guard at the parser entry without `std`.
+pub fn unwind_guard() -> UnwindGuard {
+    UnwindGuard
+}
+
+/// Function to display forward trace without `std`.
+/// This is inserted by `#[tracable_parser]`.
+pub fn forward_trace<T: Tracable>(input: T, name: &str) -> (TracableInfo, T) {
+    forward_trace_args(input, name, &[])
+}
+
+/// Adapter to write the other arguments of the parser by `format_args!`.
+struct Args<'a>(&'a [(&'a str, &'a dyn fmt::Debug)]);
+
+impl fmt::Display for Args<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (name, value)) in self.0.iter().enumerate() {
+            f.write_str(if i == 0 { " : " } else { ", " })?;
+            write!(f, "{} = {:?}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Function to display forward trace with the other arguments of the parser without `std`.
+/// This is inserted by `#[tracable_parser(show_args)]`.
+pub fn forward_trace_args<T: Tracable>(
+    input: T,
+    name: &str,
+    args: &[(&str, &dyn fmt::Debug)],
+) -> (TracableInfo, T) {
+    let info = input.get_tracable_info();
+    // The switch is checked at the root, so an in-progress parse is traced consistently
+    let enabled = is_enabled();
+    let counted = update(|x| {
+        // The depth is not increased, so the children are counted to follow the root
+        if x.untraced > 0 || (x.depth == 0 && !enabled) {
+            x.untraced += 1;
+            return None;
+        }
+        if x.depth == 0 {
+            x.forward = 0;
+            x.backward = 0;
+        }
+        x.depth += 1;
+        x.forward += 1;
+        Some((x.depth - 1, x.forward))
+    });
+    let (depth, count) = match counted {
+        Some(x) => x,
+        None => return (info, input),
+    };
+
+    if depth == 0 {
+        emit_line(format_args!(
+            "{:<count_width$} {:<count_width$} : parser : offset",
+            "forward",
+            "backward",
+            count_width = COUNT_WIDTH
+        ));
+    }
+
+    emit_line(format_args!(
+        "{:<count_width$} {:<count_width$} : {:indent$}-> {} : {}{}",
+        count,
+        "",
+        "",
+        name,
+        input.offset(),
+        Args(args),
+        count_width = COUNT_WIDTH,
+        indent = depth
+    ));
+
+    (info, input)
+}
+
+/// Function to display backward trace without `std` and the details of the error.
+pub fn backward_trace<T: Tracable, U, V>(
+    input: IResult<T, U, V>,
+    name: &str,
+    info: TracableInfo,
+) -> IResult<T, U, V> {
+    backward_trace_detail(input, name, info, None)
+}
+
+/// Adapter to write the details of the error by `format_args!`.
+struct Detail<'a, V>(&'a nom::Err<V>, Option<ErrorDetail<V>>);
+
+impl<V> fmt::Display for Detail<'_, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.0, self.1) {
+            (nom::Err::Error(e) | nom::Err::Failure(e), Some(detail)) => {
+                f.write_str(" : ")?;
+                detail(e, f)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Adapter to write the parsed value by `format_args!`.
+struct Output<'a, U>(&'a U, Option<OutputDebug<U>>);
+
+type OutputDebug<U> = fn(&U, &mut fmt::Formatter<'_>) -> fmt::Result;
+
+impl<U> fmt::Display for Output<'_, U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.1 {
+            Some(debug) => {
+                f.write_str(" : ")?;
+                debug(self.0, f)
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// Function to display backward trace without `std`.
+/// This is inserted by `#[tracable_parser]`.
+pub fn backward_trace_detail<T: Tracable, U, V>(
+    input: IResult<T, U, V>,
+    name: &str,
+    info: TracableInfo,
+    detail: Option<ErrorDetail<V>>,
+) -> IResult<T, U, V> {
+    backward_trace_inner(input, name, info, detail, None)
+}
+
+/// Function to display backward trace with the parsed value on success without `std`.
+/// This is inserted by `#[tracable_parser(show_output)]`.
+pub fn backward_trace_output<T: Tracable, U: fmt::Debug, V>(
+    input: IResult<T, U, V>,
+    name: &str,
+    info: TracableInfo,
+    detail: Option<ErrorDetail<V>>,
+) -> IResult<T, U, V> {
+    backward_trace_inner(input, name, info, detail, Some(<U as fmt::Debug>::fmt))
+}
+
+fn backward_trace_inner<T: Tracable, U, V>(
+    input: IResult<T, U, V>,
+    name: &str,
+    _info: TracableInfo,
+    detail: Option<ErrorDetail<V>>,
+    output: Option<OutputDebug<U>>,
+) -> IResult<T, U, V> {
+    let counted = update(|x| {
+        // The root parse was not traced
+        if x.untraced > 0 {
+            x.untraced -= 1;
+            return None;
+        }
+        if x.depth == 0 {
+            return None;
+        }
+        x.depth -= 1;
+        x.backward += 1;
+        Some((x.depth, x.backward))
+    });
+    let (depth, count) = match counted {
+        Some(x) => x,
+        None => return input,
+    };
+
+    match &input {
+        Ok((s, x)) => emit_line(format_args!(
+            "{:<count_width$} {:<count_width$} : {:indent$}<- {} : {}{}",
+            "",
+            count,
+            "",
+            name,
+            s.offset(),
+            Output(x, output),
+            count_width = COUNT_WIDTH,
+            indent = depth
+        )),
+        Err(x) => {
+            let (arrow, kind) = match x {
+                nom::Err::Error(_) => ("<-", "error"),
+                nom::Err::Failure(_) => ("<!", "failure"),
+                nom::Err::Incomplete(_) => ("<?", "incomplete"),
+            };
+            emit_line(format_args!(
+                "{:<count_width$} {:<count_width$} : {:indent$}{} {} : {}{}",
+                "",
+                count,
+                "",
+                arrow,
+                name,
+                kind,
+                Detail(x, detail),
+                count_width = COUNT_WIDTH,
+                indent = depth
+            ))
+        }
+    }
+
+    input
+}
+
+impl<T: nom::AsBytes, U: HasTracableInfo> Tracable for nom_locate::LocatedSpan<T, U> {
+    // The depth is counted by the tracer because `TracableInfo` has no state without `trace`
+    fn inc_depth(self) -> Self {
+        self
+    }
+
+    fn dec_depth(self) -> Self {
+        self
+    }
+
+    #[cfg(feature = "std")]
+    fn format(&self) -> String {
+        format!("{:<8}", self.location_offset())
+    }
+
+    #[cfg(feature = "std")]
+    fn header(&self) -> String {
+        format!("{:<8}", "offset")
+    }
+
+    fn offset(&self) -> usize {
+        self.location_offset()
+    }
+
+    fn fragment_bytes(&self) -> &[u8] {
+        self.fragment().as_bytes()
+    }
+}
@@ -0,0 +1,137 @@
+use crate::writer;
+#[cfg(feature = "trace-core")]
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+/// Struct to have an edge of the call graph from the caller parser to the callee parser.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CallEdge {
+    pub caller: String,
+    pub callee: String,
+    /// The number of calls of the callee from the caller.
+    pub calls: usize,
+    /// The number of the calls which failed.
+    pub failures: usize,
+}
+
+/// Struct to have the call graph of the last parse.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CallGraph {
+    /// The edges sorted in descending order of the calls.
+    pub edges: Vec<CallEdge>,
+}
+
+impl CallGraph {
+    #[cfg(feature = "trace-core")]
+    pub(crate) fn new(edges: &HashMap<(String, String), (usize, usize)>) -> Self {
+        let mut edges: Vec<_> = edges
+            .iter()
+            .map(|((caller, callee), (calls, failures))| CallEdge {
+                caller: caller.clone(),
+                callee: callee.clone(),
+                calls: *calls,
+                failures: *failures,
+            })
+            .collect();
+        edges.sort_by(|x, y| {
+            y.calls
+                .cmp(&x.calls)
+                .then_with(|| x.caller.cmp(&y.caller))
+                .then_with(|| x.callee.cmp(&y.callee))
+        });
+        CallGraph { edges }
+    }
+
+    /// Show the call graph as a table.
+    pub fn show(&self) {
+        let max_caller_len = self
+            .edges
+            .iter()
+            .map(|x| x.caller.len())
+            .max()
+            .unwrap_or(0)
+            .max("caller".len());
+        let max_callee_len = self
+            .edges
+            .iter()
+            .map(|x| x.callee.len())
+            .max()
+            .unwrap_or(0)
+            .max("callee".len());
+
+        let mut lock = writer();
+
+        writeln!(
+            lock,
+            "\n{:<caller$} | {:<callee$} | {:>10} | {:>10}",
+            "caller",
+            "callee",
+            "calls",
+            "failures",
+            caller = max_caller_len,
+            callee = max_callee_len,
+        )
+        .unwrap();
+
+        writeln!(
+            lock,
+            "{} | {} | {} | {}",
+            "-".repeat(max_caller_len),
+            "-".repeat(max_callee_len),
+            "-".repeat(10),
+            "-".repeat(10),
+        )
+        .unwrap();
+
+        for x in &self.edges {
+            writeln!(
+                lock,
+                "{:<caller$} | {:<callee$} | {:>10} | {:>10}",
+                x.caller,
+                x.callee,
+                x.calls,
+                x.failures,
+                caller = max_caller_len,
+                callee = max_callee_len,
+            )
+            .unwrap();
+        }
+        writeln!(lock).unwrap();
+    }
+
+    /// Write the call graph in Graphviz DOT format.
+    ///
+    /// Each edge is labeled by the calls and the failures.
+    pub fn write_dot<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let quote = |x: &str| format!("\"{}\"", x.replace('"', "\\\""));
+        writeln!(w, "digraph {{")?;
+        for x in &self.edges {
+            writeln!(
+                w,
+                "    {} -> {} [label=\"{} ({} failed)\"];",
+                quote(&x.caller),
+                quote(&x.callee),
+                x.calls,
+                x.failures
+            )?;
+        }
+        writeln!(w, "}}")
+    }
+}
+
+/// Get the call graph of the last parse.
+///
+/// The statistics information is reset at each parser call like histogram.
+pub fn call_graph() -> CallGraph {
+    call_graph_internal()
+}
+
+#[cfg(feature = "trace-core")]
+fn call_graph_internal() -> CallGraph {
+    crate::TRACABLE_STORAGE.with(|storage| CallGraph::new(&storage.borrow().call_edges))
+}
+
+#[cfg(not(feature = "trace-core"))]
+fn call_graph_internal() -> CallGraph {
+    CallGraph::default()
+}
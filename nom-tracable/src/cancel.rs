@@ -0,0 +1,114 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Token to cancel an in-progress parse cooperatively.
+///
+/// The token can be cloned and cancelled from another thread.
+/// The tracer checks the token registered by `set_cancellation_token` at each parser call,
+/// records where the cancellation was observed, and calls the abort hook.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<Mutex<Option<Instant>>>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request the cancellation. The second and later requests are ignored.
+    pub fn cancel(&self) {
+        let mut cancelled = self.cancelled.lock().unwrap();
+        if cancelled.is_none() {
+            *cancelled = Some(Instant::now());
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.lock().unwrap().is_some()
+    }
+
+    /// Get the time when the cancellation was requested.
+    pub fn cancelled_at(&self) -> Option<Instant> {
+        *self.cancelled.lock().unwrap()
+    }
+}
+
+/// Struct to have the point where the cancellation was observed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cancellation {
+    /// The parser which observed the cancellation.
+    pub parser: String,
+    pub offset: usize,
+    /// The parser chain when the cancellation was observed.
+    pub chain: Vec<String>,
+    /// Elapsed time from the cancellation request to the observation.
+    pub latency: Duration,
+}
+
+/// Set the cancellation token checked at each parser call.
+///
+/// The token is thread independent because it is stored at thread local storage.
+/// To stop the parse, the abort hook set by `set_abort_hook` should `panic!`,
+/// or the parser should check `CancellationToken::is_cancelled` by itself.
+pub fn set_cancellation_token(token: Option<CancellationToken>) {
+    set_cancellation_token_internal(token);
+}
+
+#[cfg(feature = "trace-core")]
+fn set_cancellation_token_internal(token: Option<CancellationToken>) {
+    crate::TRACABLE_STORAGE.with(|storage| {
+        storage.borrow_mut().cancellation_token = token;
+    });
+}
+
+#[cfg(not(feature = "trace-core"))]
+fn set_cancellation_token_internal(_token: Option<CancellationToken>) {}
+
+/// Get the point where the cancellation was observed in the last parse.
+pub fn cancellation() -> Option<Cancellation> {
+    cancellation_internal()
+}
+
+#[cfg(feature = "trace-core")]
+fn cancellation_internal() -> Option<Cancellation> {
+    crate::TRACABLE_STORAGE.with(|storage| storage.borrow().cancellation.clone())
+}
+
+#[cfg(not(feature = "trace-core"))]
+fn cancellation_internal() -> Option<Cancellation> {
+    None
+}
+
+/// Check the cancellation token, and record the first observation of the cancellation.
+#[cfg(feature = "trace-core")]
+pub(crate) fn check_cancellation(name: &str, offset: usize, info: &crate::TracableInfo) {
+    let observed = crate::TRACABLE_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        if storage.cancellation.is_some() {
+            return None;
+        }
+        let requested = storage.cancellation_token.as_ref()?.cancelled_at()?;
+        let cancellation = Cancellation {
+            parser: String::from(name),
+            offset,
+            chain: storage.stack.iter().map(|x| x.name.clone()).collect(),
+            latency: requested.elapsed(),
+        };
+        storage.cancellation = Some(cancellation.clone());
+        Some(cancellation)
+    });
+
+    if let Some(x) = observed {
+        crate::report_warning(
+            crate::WarningKind::Cancelled,
+            name,
+            offset,
+            info,
+            &format!(
+                "cancelled : cancellation is observed at {} (offset {}) {:?} after the request",
+                x.parser, x.offset, x.latency
+            ),
+        );
+    }
+}
@@ -0,0 +1,135 @@
+/// Colors of the trace output.
+///
+/// Each color is a string inserted before the colored text like an ANSI escape sequence `"\u{001b}[1;37m"`,
+/// and `reset` is inserted after the colored text.
+/// Sinks which don't understand ANSI escape sequences can use any other markers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ColorScheme {
+    pub forward: &'static str,
+    pub ok: &'static str,
+    /// Color of `Err::Error`.
+    pub err: &'static str,
+    /// Color of `Err::Failure`.
+    pub failure: &'static str,
+    /// Color of `Err::Incomplete`.
+    pub incomplete: &'static str,
+    /// Color of custom traces of captured traces. The live trace uses the color given to `custom_trace`.
+    pub custom: &'static str,
+    pub warning: &'static str,
+    pub bookmark: &'static str,
+    pub watch: &'static str,
+    /// Color of the lines which summarize elided or folded events.
+    pub summary: &'static str,
+    /// Palette of parser names by `TracableInfo::parser_colors`.
+    pub parsers: &'static [&'static str],
+    pub reset: &'static str,
+}
+
+impl ColorScheme {
+    /// The default colors: white forward, green ok and red err.
+    pub const DEFAULT: ColorScheme = ColorScheme {
+        forward: "\u{001b}[1;37m",
+        ok: "\u{001b}[1;32m",
+        err: "\u{001b}[1;31m",
+        failure: "\u{001b}[1;95m",
+        incomplete: "\u{001b}[1;34m",
+        custom: "\u{001b}[1;35m",
+        warning: "\u{001b}[1;31m",
+        bookmark: "\u{001b}[1;36m",
+        watch: "\u{001b}[1;33m",
+        summary: "\u{001b}[1;30m",
+        parsers: &[
+            "\u{001b}[38;5;39m",
+            "\u{001b}[38;5;208m",
+            "\u{001b}[38;5;141m",
+            "\u{001b}[38;5;78m",
+            "\u{001b}[38;5;204m",
+            "\u{001b}[38;5;220m",
+            "\u{001b}[38;5;45m",
+            "\u{001b}[38;5;171m",
+            "\u{001b}[38;5;114m",
+            "\u{001b}[38;5;209m",
+            "\u{001b}[38;5;105m",
+            "\u{001b}[38;5;185m",
+        ],
+        reset: "\u{001b}[0m",
+    };
+
+    /// No colors.
+    pub const NONE: ColorScheme = ColorScheme {
+        forward: "",
+        ok: "",
+        err: "",
+        failure: "",
+        incomplete: "",
+        custom: "",
+        warning: "",
+        bookmark: "",
+        watch: "",
+        summary: "",
+        parsers: &[],
+        reset: "",
+    };
+
+    /// Pad the colored text to `width`.
+    ///
+    /// `width` is the visible width, so the padding doesn't depend on the colors.
+    #[cfg(feature = "trace-core")]
+    pub(crate) fn pad(&self, color: &str, text: &str, width: usize) -> String {
+        let padding = width.saturating_sub(text.chars().count());
+        format!("{}{}{}{}", color, text, self.reset, " ".repeat(padding))
+    }
+
+    /// Pad the label of the parser like `-> name +` to `width`.
+    ///
+    /// If `parser_color` is enabled, the name is colored by `parser` instead of `color`.
+    #[cfg(feature = "trace-core")]
+    pub(crate) fn pad_parser(
+        &self,
+        color: &str,
+        (prefix, name, suffix): (&str, &str, &str),
+        width: usize,
+        parser_color: bool,
+    ) -> String {
+        match self.parser(name).filter(|_| parser_color) {
+            Some(name_color) => {
+                let len = prefix.chars().count() + name.chars().count() + suffix.chars().count();
+                let padding = width.saturating_sub(len);
+                format!(
+                    "{}{}{}{}{}{}{}{}{}{}",
+                    color,
+                    prefix,
+                    self.reset,
+                    name_color,
+                    name,
+                    self.reset,
+                    color,
+                    suffix,
+                    self.reset,
+                    " ".repeat(padding)
+                )
+            }
+            None => self.pad(color, &format!("{}{}{}", prefix, name, suffix), width),
+        }
+    }
+
+    /// Get the color of the parser name from `parsers`.
+    ///
+    /// The color is chosen by the hash of the name, so it is stable across runs.
+    pub fn parser(&self, name: &str) -> Option<&'static str> {
+        if self.parsers.is_empty() {
+            return None;
+        }
+        // FNV-1a
+        let hash = name.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, x| {
+            (hash ^ u64::from(x)).wrapping_mul(0x0100_0000_01b3)
+        });
+        Some(self.parsers[(hash % self.parsers.len() as u64) as usize])
+    }
+}
+
+impl Default for ColorScheme {
+    fn default() -> Self {
+        ColorScheme::DEFAULT
+    }
+}
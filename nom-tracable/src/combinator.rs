@@ -0,0 +1,58 @@
+#[cfg(any(feature = "trace-core", feature = "bare"))]
+use crate::Tracable;
+use nom::error::ParseError;
+use nom::{IResult, Parser};
+
+/// Wrap a parser to trace it like `#[tracable_parser]`.
+///
+/// This is useful for closures and combinator chains which have no function to attach the attribute to.
+/// Unlike the attribute, the trace depends on `trace` (or `bare`) feature of nom-tracable itself.
+/// The abort hook and `depth_limit` work like the attribute, but the error detail of `TraceError` is not shown
+/// because the error type is generic here.
+///
+/// ```
+/// # use nom::character::complete::*;
+/// # use nom::sequence::separated_pair;
+/// # use nom::IResult;
+/// # use nom_locate::LocatedSpan;
+/// # use nom_tracable::{tracable, TracableInfo};
+/// #
+/// # type Span<'a> = LocatedSpan<&'a str, TracableInfo>;
+/// #
+/// pub fn pair(s: Span) -> IResult<Span, (Span, Span)> {
+///     tracable("pair_items", separated_pair(digit1, char(','), digit1))(s)
+/// }
+///
+/// let (_, x) = pair(LocatedSpan::new_extra("1,22", TracableInfo::new())).unwrap();
+/// assert_eq!(*x.1.fragment(), "22");
+/// ```
+#[cfg(any(feature = "trace-core", feature = "bare"))]
+pub fn tracable<I: Tracable, O, E: ParseError<I>, P: Parser<I, O, E>>(
+    name: &'static str,
+    mut parser: P,
+) -> impl FnMut(I) -> IResult<I, O, E> {
+    move |input: I| {
+        let _unwind = crate::unwind_guard();
+        let (depth, input) = crate::forward_trace(input, name);
+        let ret = if crate::depth_limit_exceeded(&input) {
+            #[cfg(feature = "trace-core")]
+            crate::report_depth_limit(&input, name);
+            Err(nom::Err::Failure(E::from_error_kind(
+                input,
+                nom::error::ErrorKind::TooLarge,
+            )))
+        } else {
+            parser.parse(input)
+        };
+        crate::backward_trace(ret, name, depth)
+    }
+}
+
+/// Wrap a parser to trace it like `#[tracable_parser]`.
+#[cfg(not(any(feature = "trace-core", feature = "bare")))]
+pub fn tracable<I, O, E: ParseError<I>, P: Parser<I, O, E>>(
+    _name: &'static str,
+    mut parser: P,
+) -> impl FnMut(I) -> IResult<I, O, E> {
+    move |input: I| parser.parse(input)
+}
@@ -0,0 +1,472 @@
+use crate::TracableInfo;
+#[cfg(feature = "trace-core")]
+use std::time::Duration;
+
+/// Keys whose value is a list continued by the following items without `=`.
+#[cfg(feature = "trace-core")]
+const LIST_KEYS: &[&str] = &["include", "exclude", "fold", "disable_group", "breakpoint"];
+
+/// Items which can be specified without value. These are not treated as the continuation of lists.
+#[cfg(feature = "trace-core")]
+const FLAGS: &[&str] = &[
+    "forward",
+    "backward",
+    "custom",
+    "color",
+    "no_color",
+    "no-color",
+    "timing",
+    "capture",
+    "failures_only",
+    "parser_colors",
+    "escape_control",
+    "show_consumed",
+    "show_matched",
+    "tree_style",
+    "collapse_repeats",
+    "snapshot",
+    "session_tag",
+    "break_on_watch",
+    "summary",
+];
+
+#[cfg(feature = "trace-core")]
+fn parse_value<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value of `{}` : {}", key, value))
+}
+
+/// Apply a configuration item to `info`.
+///
+/// This is shared by configuration sources like `TracableInfo::from_env`.
+#[cfg(feature = "trace-core")]
+pub(crate) fn apply_item(
+    info: TracableInfo,
+    key: &str,
+    values: &[&str],
+) -> Result<TracableInfo, String> {
+    let value = || match values {
+        [x] => Ok(*x),
+        [] => Err(format!("`{}` requires a value", key)),
+        _ => Err(format!("`{}` requires a single value", key)),
+    };
+    let ret = match key {
+        "forward" | "backward" | "custom" if values.is_empty() => match key {
+            "forward" => info.forward(true),
+            "backward" => info.backward(true),
+            _ => info.custom(true),
+        },
+        "color" if values.is_empty() => info.color(true),
+        "no_color" | "no-color" => info.color(false),
+        "timing" if values.is_empty() => info.timing(true),
+        "capture" if values.is_empty() => info.capture(true),
+        "failures_only" if values.is_empty() => info.failures_only(true),
+        "parser_colors" if values.is_empty() => info.parser_colors(true),
+        "escape_control" if values.is_empty() => info.escape_control(true),
+        "show_consumed" if values.is_empty() => info.show_consumed(true),
+        "show_matched" if values.is_empty() => info.show_matched(true),
+        "tree_style" if values.is_empty() => info.tree_style(true),
+        "collapse_repeats" if values.is_empty() => info.collapse_repeats(true),
+        "snapshot" if values.is_empty() => info.snapshot(true),
+        "session_tag" if values.is_empty() => info.session_tag(true),
+        "break_on_watch" if values.is_empty() => info.break_on_watch(true),
+        "summary" if values.is_empty() => info.summary(true),
+        "forward" => info.forward(parse_value(key, value()?)?),
+        "backward" => info.backward(parse_value(key, value()?)?),
+        "custom" => info.custom(parse_value(key, value()?)?),
+        "color" => info.color(parse_value(key, value()?)?),
+        "timing" => info.timing(parse_value(key, value()?)?),
+        "capture" => info.capture(parse_value(key, value()?)?),
+        "failures_only" => info.failures_only(parse_value(key, value()?)?),
+        "parser_colors" => info.parser_colors(parse_value(key, value()?)?),
+        "escape_control" => info.escape_control(parse_value(key, value()?)?),
+        "show_consumed" => info.show_consumed(parse_value(key, value()?)?),
+        "show_matched" => info.show_matched(parse_value(key, value()?)?),
+        "tree_style" => info.tree_style(parse_value(key, value()?)?),
+        "collapse_repeats" => info.collapse_repeats(parse_value(key, value()?)?),
+        "snapshot" => info.snapshot(parse_value(key, value()?)?),
+        "session_tag" => info.session_tag(parse_value(key, value()?)?),
+        "break_on_watch" => info.break_on_watch(parse_value(key, value()?)?),
+        "summary" => info.summary(parse_value(key, value()?)?),
+        "session_id" => info.session_id(value()?),
+        "width" | "parser_width" => info.parser_width(parse_value(key, value()?)?),
+        "count_width" => info.count_width(parse_value(key, value()?)?),
+        "fragment_width" => info.fragment_width(parse_value(key, value()?)?),
+        "max_depth" => info.max_depth(parse_value(key, value()?)?),
+        "depth_limit" => info.depth_limit(parse_value(key, value()?)?),
+        "failure_snippet" => info.failure_snippet(parse_value(key, value()?)?),
+        "watchdog" => info.watchdog(parse_value(key, value()?)?),
+        "sampling" => info.sampling(parse_value(key, value()?)?),
+        "ring_buffer" => info.ring_buffer(parse_value(key, value()?)?),
+        "time_budget" => info.time_budget(Duration::from_millis(parse_value(key, value()?)?)),
+        "ndjson" => info.ndjson(value()?),
+        "output_file" => info.output_file(value()?),
+        "flush" => match value()? {
+            "line" => info.flush(crate::TraceFlush::Line),
+            "parse" => info.flush(crate::TraceFlush::Parse),
+            x => return Err(format!("invalid value of `{}` : {}", key, x)),
+        },
+        "location_style" => match value()? {
+            "offset" => info.location_style(crate::LocationStyle::Offset),
+            "line_col" => info.location_style(crate::LocationStyle::LineCol),
+            "both" => info.location_style(crate::LocationStyle::Both),
+            x => return Err(format!("invalid value of `{}` : {}", key, x)),
+        },
+        "output" => match value()? {
+            "stdout" => info.output(crate::TraceOutput::Stdout),
+            "stderr" => info.output(crate::TraceOutput::Stderr),
+            x => return Err(format!("invalid value of `{}` : {}", key, x)),
+        },
+        "trace_range" => {
+            let value = value()?;
+            let (start, end) = value
+                .split_once("..")
+                .ok_or_else(|| format!("invalid value of `{}` : {}", key, value))?;
+            info.trace_range(parse_value(key, start)?..parse_value(key, end)?)
+        }
+        "include" => info.include(values),
+        "exclude" => info.exclude(values),
+        "fold" => values.iter().fold(info, |info, x| info.fold(x)),
+        "breakpoint" => values.iter().fold(info, |info, x| info.breakpoint(x)),
+        "disable_group" => values
+            .iter()
+            .fold(info, |info, x| info.enable_group(x, false)),
+        _ => return Err(format!("unknown item `{}`", key)),
+    };
+    Ok(ret)
+}
+
+/// Parse a comma-separated configuration like `forward,backward,width=64,include=expr,term`.
+#[cfg(feature = "trace-core")]
+pub(crate) fn parse_config(info: TracableInfo, config: &str) -> (TracableInfo, Vec<String>) {
+    let mut items: Vec<(&str, Vec<&str>)> = Vec::new();
+    for item in config
+        .split(',')
+        .map(|x| x.trim())
+        .filter(|x| !x.is_empty())
+    {
+        match item.split_once('=') {
+            Some((key, value)) => items.push((key.trim(), vec![value.trim()])),
+            None => match items.last_mut() {
+                Some((key, values)) if LIST_KEYS.contains(key) && !FLAGS.contains(&item) => {
+                    values.push(item)
+                }
+                _ => items.push((item, Vec::new())),
+            },
+        }
+    }
+
+    // Only the listed directions are enabled if any direction is listed
+    let directions = ["forward", "backward", "custom"];
+    let mut info = info;
+    if items
+        .iter()
+        .any(|(key, values)| directions.contains(key) && values.is_empty())
+    {
+        info = info.forward(false).backward(false).custom(false);
+    }
+
+    let mut errors = Vec::new();
+    for (key, values) in items {
+        match apply_item(info.clone(), key, &values) {
+            Ok(x) => info = x,
+            Err(x) => errors.push(x),
+        }
+    }
+    (info, errors)
+}
+
+/// Token of configuration files.
+#[cfg(feature = "trace-core")]
+#[derive(Debug, PartialEq)]
+enum Token {
+    Str(String),
+    Bare(String),
+    Punct(char),
+}
+
+#[cfg(feature = "trace-core")]
+const PUNCTS: &[char] = &['{', '}', '[', ']', ',', ':', '='];
+
+/// Split a configuration file into the tokens with the line numbers.
+/// `#` starts a comment to the end of the line outside strings.
+///
+/// The literal strings like `'C:\trace'` are TOML only, and the multi-line strings are rejected.
+#[cfg(feature = "trace-core")]
+fn tokenize(text: &str, json: bool) -> Result<Vec<(Token, usize)>, String> {
+    let mut ret = Vec::new();
+    let mut line = 1;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => line += 1,
+            '#' => while chars.next_if(|x| *x != '\n').is_some() {},
+            '"' | '\'' if chars.clone().take(2).eq([c, c]) => {
+                return Err(format!(
+                    "line {} : multi-line strings are not supported",
+                    line
+                ))
+            }
+            '\'' if json => {
+                return Err(format!(
+                    "line {} : single-quoted strings are not supported in JSON",
+                    line
+                ))
+            }
+            '\'' => {
+                let mut x = String::new();
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some('\n') | None => {
+                            return Err(format!("line {} : unterminated string", line))
+                        }
+                        Some(c) => x.push(c),
+                    }
+                }
+                ret.push((Token::Str(x), line));
+            }
+            '"' => {
+                let mut x = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('n') => x.push('\n'),
+                            Some('t') => x.push('\t'),
+                            Some('r') => x.push('\r'),
+                            Some('u') => {
+                                let hex: String = chars.by_ref().take(4).collect();
+                                let c = u32::from_str_radix(&hex, 16)
+                                    .ok()
+                                    .and_then(char::from_u32)
+                                    .ok_or_else(|| {
+                                        format!("line {} : invalid escape `\\u{}`", line, hex)
+                                    })?;
+                                x.push(c);
+                            }
+                            Some(c @ ('"' | '\\' | '/')) => x.push(c),
+                            Some(c) => {
+                                return Err(format!("line {} : invalid escape `\\{}`", line, c))
+                            }
+                            None => return Err(format!("line {} : unterminated string", line)),
+                        },
+                        Some('\n') | None => {
+                            return Err(format!("line {} : unterminated string", line))
+                        }
+                        Some(c) => x.push(c),
+                    }
+                }
+                ret.push((Token::Str(x), line));
+            }
+            c if PUNCTS.contains(&c) => ret.push((Token::Punct(c), line)),
+            c if c.is_whitespace() => (),
+            c => {
+                let mut x = String::from(c);
+                while let Some(c) = chars
+                    .next_if(|x| !x.is_whitespace() && !PUNCTS.contains(x) && !"#\"'".contains(*x))
+                {
+                    x.push(c);
+                }
+                ret.push((Token::Bare(x), line));
+            }
+        }
+    }
+    Ok(ret)
+}
+
+/// Check whether the unquoted value is a boolean or a number.
+#[cfg(feature = "trace-core")]
+fn is_literal(x: &str) -> bool {
+    x == "true" || x == "false" || x.parse::<f64>().is_ok()
+}
+
+/// Check whether the unquoted key is a bare key of TOML.
+#[cfg(feature = "trace-core")]
+fn is_bare_key(x: &str) -> bool {
+    x.chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Parse a flat TOML table or a flat JSON object into the items of `apply_item`.
+///
+/// The values are strings, booleans, numbers, or arrays of them.
+/// The other syntax like tables, dotted keys, inline tables and unquoted strings is rejected.
+#[cfg(feature = "trace-core")]
+fn parse_file(text: &str) -> Result<Vec<(String, Vec<String>)>, String> {
+    let unexpected = |x: Option<(Token, usize)>| match x {
+        Some((Token::Str(x), line)) => format!("line {} : unexpected string {:?}", line, x),
+        Some((Token::Bare(x), line)) => {
+            format!("line {} : unexpected `{}`", line, x)
+        }
+        Some((Token::Punct(x), line)) => format!("line {} : unexpected `{}`", line, x),
+        None => String::from("unexpected end of file"),
+    };
+
+    let json = text.trim_start().starts_with('{');
+    let mut tokens = tokenize(text, json)?.into_iter();
+    if json {
+        tokens.next();
+    }
+    let separator = if json { ':' } else { '=' };
+    let value = |x: Option<(Token, usize)>| match x {
+        Some((Token::Str(x), _)) => Ok(x),
+        Some((Token::Bare(x), _)) if is_literal(&x) => Ok(x),
+        Some((Token::Bare(x), line)) => Err(format!(
+            "line {} : unquoted string `{}` is not supported",
+            line, x
+        )),
+        Some((Token::Punct('{'), line)) if json => {
+            Err(format!("line {} : nested objects are not supported", line))
+        }
+        Some((Token::Punct('{'), line)) => {
+            Err(format!("line {} : inline tables are not supported", line))
+        }
+        Some((Token::Punct('['), line)) => {
+            Err(format!("line {} : nested arrays are not supported", line))
+        }
+        x => Err(unexpected(x)),
+    };
+
+    let mut items = Vec::new();
+    loop {
+        let key = match tokens.next() {
+            None if !json => break,
+            Some((Token::Punct('}'), _)) if json => break,
+            Some((Token::Punct('['), line)) if !json => {
+                return Err(format!("line {} : tables are not supported", line))
+            }
+            Some((Token::Bare(x), line)) if !json && x.contains('.') => {
+                return Err(format!(
+                    "line {} : dotted key `{}` is not supported",
+                    line, x
+                ))
+            }
+            Some((Token::Str(x), _)) => x,
+            Some((Token::Bare(x), _)) if !json && is_bare_key(&x) => x,
+            x => return Err(unexpected(x)),
+        };
+        match tokens.next() {
+            Some((Token::Punct(x), _)) if x == separator => (),
+            x => return Err(unexpected(x)),
+        }
+        let values = match tokens.next() {
+            Some((Token::Punct('['), _)) => {
+                let mut values = Vec::new();
+                loop {
+                    match tokens.next() {
+                        Some((Token::Punct(']'), _)) => break,
+                        x => values.push(value(x)?),
+                    }
+                    match tokens.next() {
+                        Some((Token::Punct(']'), _)) => break,
+                        Some((Token::Punct(','), _)) => (),
+                        x => return Err(unexpected(x)),
+                    }
+                }
+                values
+            }
+            x => vec![value(x)?],
+        };
+        items.push((key, values));
+        if json {
+            match tokens.next() {
+                Some((Token::Punct(','), _)) => (),
+                Some((Token::Punct('}'), _)) => break,
+                x => return Err(unexpected(x)),
+            }
+        }
+    }
+    match tokens.next() {
+        None => Ok(items),
+        x => Err(unexpected(x)),
+    }
+}
+
+#[cfg(feature = "trace-core")]
+impl TracableInfo {
+    /// Create `TracableInfo` from `NOM_TRACABLE` environment variable.
+    ///
+    /// The variable is a comma-separated list like `forward,backward,width=64,include=expr,term`.
+    /// If any of `forward`/`backward`/`custom` is listed, the other directions are disabled.
+    /// Invalid items are reported to stderr and ignored, so they don't mix with the trace output.
+    /// If the variable is not set, this is same as `TracableInfo::new`.
+    pub fn from_env() -> Self {
+        let config = match std::env::var("NOM_TRACABLE") {
+            Ok(x) => x,
+            Err(_) => return TracableInfo::new(),
+        };
+
+        let (info, errors) = parse_config(TracableInfo::new(), &config);
+        for error in errors {
+            eprintln!("!!! NOM_TRACABLE : {}", error);
+        }
+        info
+    }
+
+    /// Create `TracableInfo` from a configuration file.
+    ///
+    /// The file is a flat TOML table or a flat JSON object of the items of `NOM_TRACABLE`:
+    ///
+    /// ```toml
+    /// backward = false
+    /// width = 64
+    /// include = ["expr", "term"]
+    /// output = "stderr"
+    /// ```
+    ///
+    /// Flags like `timing` take a boolean, and lists like `include` take an array.
+    ///
+    /// Only the following subset of TOML is supported, and the other syntax is rejected:
+    ///
+    /// * `key = value` lines with bare keys like `parser_width` or quoted keys
+    /// * basic strings like `"expr"` and literal strings like `'C:\trace'`, but not multi-line strings
+    /// * booleans, numbers, and single-line arrays of them
+    /// * `#` comments
+    ///
+    /// Tables like `[trace]`, dotted keys like `trace.width`, inline tables, and unquoted strings are rejected.
+    /// JSON is a single object of the same values, and nested objects are rejected.
+    ///
+    /// `ColorScheme` can't be configured by the file, because its colors are `&'static str`.
+    /// Use `TracableInfo::color_scheme` on the returned `TracableInfo` instead.
+    ///
+    /// Syntax errors and invalid items are returned as `ErrorKind::InvalidData` with the path.
+    pub fn from_file<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<Self> {
+        let path = path.as_ref();
+        let invalid = |x: String| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("{} : {}", path.display(), x),
+            )
+        };
+        let text = std::fs::read_to_string(path)?;
+        let items = parse_file(&text).map_err(invalid)?;
+
+        let mut info = TracableInfo::new();
+        let mut errors = Vec::new();
+        for (key, values) in items {
+            let values: Vec<_> = values.iter().map(|x| x.as_str()).collect();
+            match apply_item(info.clone(), &key, &values) {
+                Ok(x) => info = x,
+                Err(x) => errors.push(x),
+            }
+        }
+        if errors.is_empty() {
+            Ok(info)
+        } else {
+            Err(invalid(errors.join(", ")))
+        }
+    }
+}
+
+#[cfg(not(feature = "trace-core"))]
+impl TracableInfo {
+    pub fn from_env() -> Self {
+        TracableInfo::new()
+    }
+
+    pub fn from_file<P: AsRef<std::path::Path>>(_path: P) -> std::io::Result<Self> {
+        Ok(TracableInfo::new())
+    }
+}
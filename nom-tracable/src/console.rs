@@ -0,0 +1,47 @@
+use crate::TraceOutput;
+use std::io::Write;
+
+/// Writer to the browser console by `wasm` feature.
+///
+/// The lines to `TraceOutput::Stdout` are passed to `console.log`, and the lines to `TraceOutput::Stderr` are
+/// passed to `console.debug`. Each line is a call when the writer is dropped.
+pub(crate) struct ConsoleWriter {
+    output: TraceOutput,
+    buf: Vec<u8>,
+}
+
+impl ConsoleWriter {
+    pub(crate) fn new(output: TraceOutput) -> Self {
+        ConsoleWriter {
+            output,
+            buf: Vec::new(),
+        }
+    }
+}
+
+impl Write for ConsoleWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for ConsoleWriter {
+    fn drop(&mut self) {
+        if self.buf.is_empty() {
+            return;
+        }
+        let buf = String::from_utf8_lossy(&self.buf);
+        for line in buf.strip_suffix('\n').unwrap_or(&buf).split('\n') {
+            let line = wasm_bindgen::JsValue::from_str(line);
+            match self.output {
+                TraceOutput::Stdout => web_sys::console::log_1(&line),
+                TraceOutput::Stderr => web_sys::console::debug_1(&line),
+            }
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use crate::{writer, Trace, TraceEventKind};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+
+/// Struct to have a pair of parsers which failed at the same offsets.
+///
+/// Such parsers usually compete in the same `alt`, so they may be reordered or factored.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cooccurrence {
+    /// The first parser of the pair in name order.
+    pub first: String,
+    pub second: String,
+    /// The entry offsets at which both parsers failed.
+    pub offsets: Vec<usize>,
+}
+
+impl Trace {
+    /// Get the set of failed parsers at each entry offset.
+    fn failures_by_offset(&self) -> BTreeMap<usize, BTreeSet<&str>> {
+        let mut ret: BTreeMap<usize, BTreeSet<&str>> = BTreeMap::new();
+        for event in &self.events {
+            if event.kind == TraceEventKind::Err {
+                ret.entry(event.offset)
+                    .or_default()
+                    .insert(event.parser.as_str());
+            }
+        }
+        ret
+    }
+
+    /// Find pairs of parsers which failed at the same offsets at least `min_count` times.
+    ///
+    /// The pairs are sorted in descending order of the count.
+    pub fn failure_cooccurrence(&self, min_count: usize) -> Vec<Cooccurrence> {
+        let mut pairs: BTreeMap<(&str, &str), Vec<usize>> = BTreeMap::new();
+        for (offset, parsers) in self.failures_by_offset() {
+            for (i, first) in parsers.iter().enumerate() {
+                for second in parsers.iter().skip(i + 1) {
+                    pairs.entry((first, second)).or_default().push(offset);
+                }
+            }
+        }
+
+        let mut ret: Vec<_> = pairs
+            .into_iter()
+            .filter(|(_, offsets)| offsets.len() >= min_count)
+            .map(|((first, second), offsets)| Cooccurrence {
+                first: String::from(first),
+                second: String::from(second),
+                offsets,
+            })
+            .collect();
+        ret.sort_by_key(|x| std::cmp::Reverse(x.offsets.len()));
+        ret
+    }
+
+    /// Find pairs by `failure_cooccurrence`, and show the report.
+    pub fn show_failure_cooccurrence(&self, min_count: usize) -> Vec<Cooccurrence> {
+        let pairs = self.failure_cooccurrence(min_count);
+
+        let max_first_len = pairs
+            .iter()
+            .map(|x| x.first.len())
+            .max()
+            .unwrap_or(0)
+            .max("parser".len());
+        let max_second_len = pairs
+            .iter()
+            .map(|x| x.second.len())
+            .max()
+            .unwrap_or(0)
+            .max("parser".len());
+
+        let mut lock = writer();
+
+        writeln!(
+            lock,
+            "\n{:<first$} | {:<second$} | {:>10} | offsets",
+            "parser",
+            "parser",
+            "count",
+            first = max_first_len,
+            second = max_second_len,
+        )
+        .unwrap();
+
+        writeln!(
+            lock,
+            "{} | {} | {} | {}",
+            "-".repeat(max_first_len),
+            "-".repeat(max_second_len),
+            "-".repeat(10),
+            "-".repeat(10),
+        )
+        .unwrap();
+
+        for x in &pairs {
+            let offsets: Vec<_> = x.offsets.iter().map(|x| x.to_string()).collect();
+            writeln!(
+                lock,
+                "{:<first$} | {:<second$} | {:>10} | {}",
+                x.first,
+                x.second,
+                x.offsets.len(),
+                offsets.join(" "),
+                first = max_first_len,
+                second = max_second_len,
+            )
+            .unwrap();
+        }
+        writeln!(lock).unwrap();
+
+        pairs
+    }
+
+    /// Write the co-occurrence matrix of parser failures as CSV.
+    ///
+    /// Each cell is the number of offsets at which both parsers failed.
+    /// The diagonal is the number of offsets at which the parser failed.
+    pub fn write_failure_cooccurrence<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let failures = self.failures_by_offset();
+        let parsers: BTreeSet<&str> = failures.values().flatten().copied().collect();
+        let index: BTreeMap<&str, usize> =
+            parsers.iter().enumerate().map(|(i, x)| (*x, i)).collect();
+
+        let mut matrix = vec![vec![0; parsers.len()]; parsers.len()];
+        for failed in failures.values() {
+            for x in failed {
+                for y in failed {
+                    matrix[index[x]][index[y]] += 1;
+                }
+            }
+        }
+
+        let quote = |x: &str| format!("\"{}\"", x.replace('"', "\"\""));
+        let header: Vec<_> = parsers.iter().map(|x| quote(x)).collect();
+        writeln!(w, "parser,{}", header.join(","))?;
+        for (parser, row) in parsers.iter().zip(matrix) {
+            let row: Vec<_> = row.iter().map(|x| x.to_string()).collect();
+            writeln!(w, "{},{}", quote(parser), row.join(","))?;
+        }
+        Ok(())
+    }
+}
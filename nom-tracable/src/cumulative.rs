@@ -0,0 +1,174 @@
+use crate::writer;
+use std::collections::HashMap;
+use std::io::Write;
+use std::time::Duration;
+
+/// Struct to have the totals of a parser over the root parses by `cumulative_stats`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ParserTotal {
+    pub parser: String,
+    pub calls: usize,
+    pub success: usize,
+    pub failure: usize,
+    /// The elapsed time of the calls including the children.
+    pub time: Duration,
+}
+
+/// Struct to have the statistics accumulated over the root parses since `reset_cumulative`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CumulativeStats {
+    /// The number of the traced root parses.
+    pub parses: usize,
+    /// The number of the parser entries.
+    pub forward: usize,
+    /// The number of the parser exits.
+    pub backward: usize,
+    /// The totals of each parser in descending order of the calls.
+    pub parsers: Vec<ParserTotal>,
+}
+
+/// Counters in `TracableStorage` which are not reset by root parses.
+#[allow(dead_code)]
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Totals {
+    parses: usize,
+    forward: usize,
+    backward: usize,
+    parsers: HashMap<String, ParserTotal>,
+}
+
+#[allow(dead_code)]
+impl Totals {
+    pub(crate) fn inc_parses(&mut self) {
+        self.parses += 1;
+    }
+
+    pub(crate) fn inc_forward(&mut self, name: &str) {
+        self.forward += 1;
+        self.entry(name).calls += 1;
+    }
+
+    pub(crate) fn inc_backward(&mut self, name: &str, success: bool, elapsed: Duration) {
+        self.backward += 1;
+        let entry = self.entry(name);
+        if success {
+            entry.success += 1;
+        } else {
+            entry.failure += 1;
+        }
+        entry.time += elapsed;
+    }
+
+    fn entry(&mut self, name: &str) -> &mut ParserTotal {
+        self.parsers
+            .entry(String::from(name))
+            .or_insert_with(|| ParserTotal {
+                parser: String::from(name),
+                ..Default::default()
+            })
+    }
+
+    fn stats(&self) -> CumulativeStats {
+        let mut parsers: Vec<_> = self.parsers.values().cloned().collect();
+        parsers.sort_by(|x, y| y.calls.cmp(&x.calls).then_with(|| x.parser.cmp(&y.parser)));
+        CumulativeStats {
+            parses: self.parses,
+            forward: self.forward,
+            backward: self.backward,
+            parsers,
+        }
+    }
+}
+
+/// Get the statistics accumulated over the root parses of the current thread.
+///
+/// Unlike `histogram`, the statistics are not reset at each root parse, but only by `reset_cumulative`.
+/// So long-running processes can report the aggregate behavior of the parsers.
+pub fn cumulative_stats() -> CumulativeStats {
+    cumulative_stats_internal()
+}
+
+#[cfg(feature = "trace-core")]
+fn cumulative_stats_internal() -> CumulativeStats {
+    crate::TRACABLE_STORAGE.with(|storage| storage.borrow().totals.stats())
+}
+
+#[cfg(not(feature = "trace-core"))]
+fn cumulative_stats_internal() -> CumulativeStats {
+    CumulativeStats::default()
+}
+
+/// Reset the statistics by `cumulative_stats`.
+pub fn reset_cumulative() {
+    reset_cumulative_internal();
+}
+
+#[cfg(feature = "trace-core")]
+fn reset_cumulative_internal() {
+    crate::TRACABLE_STORAGE.with(|storage| storage.borrow_mut().totals = Totals::default());
+}
+
+#[cfg(not(feature = "trace-core"))]
+fn reset_cumulative_internal() {}
+
+/// Get the statistics by `cumulative_stats`, and show the report.
+pub fn show_cumulative_stats() -> CumulativeStats {
+    let stats = cumulative_stats();
+
+    let max_parser_len = stats
+        .parsers
+        .iter()
+        .map(|x| x.parser.len())
+        .max()
+        .unwrap_or(0)
+        .max("parser".len());
+
+    let mut lock = writer();
+
+    writeln!(
+        lock,
+        "\n{} parses : {} forward : {} backward",
+        stats.parses, stats.forward, stats.backward
+    )
+    .unwrap();
+
+    writeln!(
+        lock,
+        "\n{:<parser$} | {:>10} | {:>10} | {:>10} | {:>12}",
+        "parser",
+        "calls",
+        "success",
+        "failure",
+        "time",
+        parser = max_parser_len,
+    )
+    .unwrap();
+
+    writeln!(
+        lock,
+        "{} | {} | {} | {} | {}",
+        "-".repeat(max_parser_len),
+        "-".repeat(10),
+        "-".repeat(10),
+        "-".repeat(10),
+        "-".repeat(12),
+    )
+    .unwrap();
+
+    for x in &stats.parsers {
+        writeln!(
+            lock,
+            "{:<parser$} | {:>10} | {:>10} | {:>10} | {:>12}",
+            x.parser,
+            x.calls,
+            x.success,
+            x.failure,
+            format!("{:?}", x.time),
+            parser = max_parser_len,
+        )
+        .unwrap();
+    }
+    writeln!(lock).unwrap();
+
+    stats
+}
@@ -0,0 +1,109 @@
+#[cfg(feature = "trace-core")]
+use crate::{Tracable, TracableInfo};
+
+/// Set the source of the commands of the debugger by `TracableInfo::breakpoint`.
+///
+/// The source returns a command line, or `None` at the end of the commands.
+/// If the source is not set, the commands are read from stdin.
+/// The source is thread independent because it is stored at thread local storage.
+pub fn set_debugger_input(input: Option<fn() -> Option<String>>) {
+    set_debugger_input_internal(input);
+}
+
+#[cfg(feature = "trace-core")]
+fn set_debugger_input_internal(input: Option<fn() -> Option<String>>) {
+    crate::TRACABLE_STORAGE.with(|storage| {
+        storage.borrow_mut().debugger_input = input;
+    });
+}
+
+#[cfg(not(feature = "trace-core"))]
+fn set_debugger_input_internal(_input: Option<fn() -> Option<String>>) {}
+
+#[cfg(feature = "trace-core")]
+const HELP: &str = "\
+commands :
+  s, step     : step into the next parser
+  n, next     : step over the children of the current parser
+  c, continue : continue to the next breakpoint
+  p, print    : print the remaining input
+  bt, stack   : print the parser stack
+  h, help     : print this help";
+
+/// Read a command from the source by `set_debugger_input` or stdin.
+#[cfg(feature = "trace-core")]
+fn read_command() -> Option<String> {
+    let input = crate::TRACABLE_STORAGE.with(|storage| storage.borrow().debugger_input);
+    if let Some(input) = input {
+        return input();
+    }
+
+    crate::write_lines("(debug) ");
+    let mut line = String::new();
+    match std::io::stdin().read_line(&mut line) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(line),
+    }
+}
+
+/// Pause at the parser entry if a breakpoint, a watchpoint or stepping hits, and run the debugger commands.
+///
+/// The parser is on the top of the stack.
+#[cfg(feature = "trace-core")]
+pub(crate) fn debug_break<T: Tracable>(input: &T, name: &str, info: &TracableInfo, watched: bool) {
+    let depth = info.depth;
+    let stepped = crate::TRACABLE_STORAGE.with(|storage| storage.borrow().step);
+    let reason = if info.options.breakpoints.iter().any(|x| x == name) {
+        "breakpoint"
+    } else if watched && info.options.break_on_watch {
+        "watch"
+    } else if stepped.is_some_and(|x| depth <= x) {
+        "step"
+    } else {
+        return;
+    };
+
+    let colors = info.colors();
+    let (color, reset) = (colors.watch, colors.reset);
+    let print = |x: &str| crate::write_lines(&format!("{}\n", x));
+
+    crate::flush_lines();
+    print(&format!(
+        "{}*** break : {} at offset {} (depth {}) : {}{}",
+        color,
+        name,
+        input.offset(),
+        depth,
+        reason,
+        reset
+    ));
+
+    // The end of the commands continues the parse to avoid blocking
+    let step = loop {
+        let command = match read_command() {
+            Some(x) => x,
+            None => break None,
+        };
+        match command.trim() {
+            "" | "s" | "step" => break Some(usize::MAX),
+            "n" | "next" => break Some(depth),
+            "c" | "continue" => break None,
+            "p" | "print" => print(&format!(
+                "{:?}",
+                String::from_utf8_lossy(input.fragment_bytes())
+            )),
+            "bt" | "stack" => crate::TRACABLE_STORAGE.with(|storage| {
+                for (i, x) in storage.borrow().stack.iter().enumerate() {
+                    print(&format!("#{} {} at offset {}", i, x.name, x.offset));
+                }
+            }),
+            "h" | "help" => print(HELP),
+            x => print(&format!(
+                "unknown command `{}` : `help` shows the commands",
+                x
+            )),
+        }
+    };
+
+    crate::TRACABLE_STORAGE.with(|storage| storage.borrow_mut().step = step);
+}
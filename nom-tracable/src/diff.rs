@@ -0,0 +1,145 @@
+use crate::{Trace, TraceEvent, TraceEventKind};
+use std::fmt;
+
+/// Struct to have the first difference between two traces by `diff`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceDiff {
+    /// Index of the diverging event in the first trace.
+    /// This is the length of the trace if the first trace is shorter.
+    pub left_index: usize,
+    /// Index of the diverging event in the second trace.
+    pub right_index: usize,
+    /// The event of the first trace. `None` if the first trace is shorter.
+    pub left: Option<TraceEvent>,
+    /// The event of the second trace. `None` if the second trace is shorter.
+    pub right: Option<TraceEvent>,
+    /// The parsers on the stack at the divergence from the root.
+    pub stack: Vec<String>,
+}
+
+fn show_event(x: &Option<TraceEvent>) -> String {
+    match x {
+        Some(x) => {
+            let arrow = match x.kind {
+                TraceEventKind::Forward => "->",
+                TraceEventKind::Ok | TraceEventKind::Err => "<-",
+                TraceEventKind::Custom | TraceEventKind::Bookmark => "",
+            };
+            let result = match x.kind {
+                TraceEventKind::Ok => " (ok)",
+                TraceEventKind::Err => " (err)",
+                _ => "",
+            };
+            format!("{} {}{} at {}", arrow, x.parser, result, x.offset)
+        }
+        None => String::from("nothing"),
+    }
+}
+
+impl fmt::Display for TraceDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "event {}/{} in {} : {} vs {}",
+            self.left_index,
+            self.right_index,
+            self.stack.join(" > "),
+            show_event(&self.left),
+            show_event(&self.right)
+        )
+    }
+}
+
+/// Whether the event is compared by `diff`.
+fn aligned(x: &TraceEvent) -> bool {
+    matches!(
+        x.kind,
+        TraceEventKind::Forward | TraceEventKind::Ok | TraceEventKind::Err
+    )
+}
+
+/// Align two captured traces, and get the first divergence.
+///
+/// The forward/backward events are compared by the kind, the depth, the parser name and the offset.
+/// The fragments are not compared, and the custom traces and the bookmarks are skipped.
+/// `None` is returned if the traces are the same.
+///
+/// ```
+/// # use nom::branch::alt;
+/// # use nom::character::complete::*;
+/// # use nom::IResult;
+/// # use nom_locate::LocatedSpan;
+/// # use nom_tracable::{captured_trace, diff, tracable_parser, TracableInfo};
+/// #
+/// # type Span<'a> = LocatedSpan<&'a str, TracableInfo>;
+/// #
+/// #[tracable_parser]
+/// pub fn one(s: Span) -> IResult<Span, char> {
+///     char('1')(s)
+/// }
+///
+/// #[tracable_parser]
+/// pub fn two(s: Span) -> IResult<Span, char> {
+///     char('2')(s)
+/// }
+///
+/// #[tracable_parser]
+/// pub fn digit(s: Span) -> IResult<Span, char> {
+///     alt((one, two))(s)
+/// }
+///
+/// let _ = digit(LocatedSpan::new_extra("1", TracableInfo::new().capture(true)));
+/// let a = captured_trace();
+/// let _ = digit(LocatedSpan::new_extra("2", TracableInfo::new().capture(true)));
+/// let b = captured_trace();
+///
+/// # #[cfg(feature = "trace")]
+/// # {
+/// let x = diff(&a.events, &b.events).unwrap();
+/// assert_eq!(x.stack, vec!["digit", "one"]);
+/// # }
+/// ```
+pub fn diff(a: &[TraceEvent], b: &[TraceEvent]) -> Option<TraceDiff> {
+    let mut left = a.iter().enumerate().filter(|(_, x)| aligned(x));
+    let mut right = b.iter().enumerate().filter(|(_, x)| aligned(x));
+    let mut stack = Vec::new();
+    loop {
+        let (l, r) = match (left.next(), right.next()) {
+            (None, None) => return None,
+            (l, r) => (l, r),
+        };
+        let same = match (l, r) {
+            (Some((_, l)), Some((_, r))) => {
+                l.kind == r.kind
+                    && l.depth == r.depth
+                    && l.parser == r.parser
+                    && l.offset == r.offset
+            }
+            _ => false,
+        };
+        if !same {
+            return Some(TraceDiff {
+                left_index: l.map(|x| x.0).unwrap_or(a.len()),
+                right_index: r.map(|x| x.0).unwrap_or(b.len()),
+                left: l.map(|x| x.1.clone()),
+                right: r.map(|x| x.1.clone()),
+                stack,
+            });
+        }
+        if let Some((_, x)) = l {
+            match x.kind {
+                TraceEventKind::Forward => stack.push(x.parser.clone()),
+                _ => {
+                    stack.pop();
+                }
+            }
+        }
+    }
+}
+
+impl Trace {
+    /// Get the first divergence from the other trace by `diff`.
+    pub fn diff(&self, other: &Trace) -> Option<TraceDiff> {
+        diff(&self.events, &other.events)
+    }
+}
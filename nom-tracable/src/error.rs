@@ -0,0 +1,123 @@
+use crate::Tracable;
+use core::fmt::{self, Write};
+use core::marker::PhantomData;
+use nom::error::{ErrorKind, ParseError};
+use nom::IResult;
+
+/// Trait to show the details of a parser error in the failing backward trace.
+///
+/// This is implemented for the error types of nom.
+/// Custom error types can implement it to show their details.
+/// Errors which don't implement it are shown without details.
+pub trait TraceError {
+    fn trace_detail(&self, w: &mut dyn Write) -> fmt::Result;
+}
+
+impl<I: Tracable> TraceError for nom::error::Error<I> {
+    fn trace_detail(&self, w: &mut dyn Write) -> fmt::Result {
+        write!(w, "{:?} at offset {}", self.code, self.input.offset())
+    }
+}
+
+impl<I: Tracable> TraceError for (I, ErrorKind) {
+    fn trace_detail(&self, w: &mut dyn Write) -> fmt::Result {
+        write!(w, "{:?} at offset {}", self.1, self.0.offset())
+    }
+}
+
+/// The errors are shown from the innermost to the outermost context.
+#[cfg(feature = "std")]
+impl<I: Tracable> TraceError for nom::error::VerboseError<I> {
+    fn trace_detail(&self, w: &mut dyn Write) -> fmt::Result {
+        use nom::error::VerboseErrorKind;
+        for (i, (input, kind)) in self.errors.iter().enumerate() {
+            if i != 0 {
+                write!(w, ", ")?;
+            }
+            match kind {
+                VerboseErrorKind::Context(x) => write!(w, "in {}", x)?,
+                VerboseErrorKind::Char(x) => write!(w, "expected {:?}", x)?,
+                VerboseErrorKind::Nom(x) => write!(w, "{:?}", x)?,
+            }
+            write!(w, " at offset {}", input.offset())?;
+        }
+        Ok(())
+    }
+}
+
+/// Function to write the details of an error.
+pub type ErrorDetail<E> = fn(&E, &mut dyn Write) -> fmt::Result;
+
+/// Probe to get `TraceError::trace_detail` if the error type implements it.
+///
+/// This is used by `#[tracable_parser]` through the method resolution:
+/// `ErrorProbeDetail` is preferred, and `ErrorProbeFallback` is used if `TraceError` is not implemented.
+#[doc(hidden)]
+pub struct ErrorProbe<E>(PhantomData<E>);
+
+impl<E> ErrorProbe<E> {
+    pub fn of<T, U>(_: &IResult<T, U, E>) -> Self {
+        ErrorProbe(PhantomData)
+    }
+}
+
+#[doc(hidden)]
+pub trait ErrorProbeDetail<E> {
+    fn detail(&self) -> Option<ErrorDetail<E>>;
+}
+
+impl<E: TraceError> ErrorProbeDetail<E> for ErrorProbe<E> {
+    fn detail(&self) -> Option<ErrorDetail<E>> {
+        Some(E::trace_detail)
+    }
+}
+
+#[doc(hidden)]
+pub trait ErrorProbeFallback<E> {
+    fn detail(&self) -> Option<ErrorDetail<E>>;
+}
+
+impl<E> ErrorProbeFallback<E> for &ErrorProbe<E> {
+    fn detail(&self) -> Option<ErrorDetail<E>> {
+        None
+    }
+}
+
+/// Probe to make the error of `TracableInfo::depth_limit` if the error type implements `ParseError`.
+///
+/// This is used by `#[tracable_parser]` like `ErrorProbe`.
+#[doc(hidden)]
+pub struct LimitProbe<T, E>(PhantomData<(T, E)>);
+
+impl<T, E> LimitProbe<T, E> {
+    pub fn of<U>(_: &Option<IResult<T, U, E>>) -> Self {
+        LimitProbe(PhantomData)
+    }
+}
+
+#[doc(hidden)]
+pub trait LimitProbeError<T, E> {
+    fn limit_error(&self, input: &T, name: &str) -> Option<nom::Err<E>>;
+}
+
+impl<T: Tracable + Clone, E: ParseError<T>> LimitProbeError<T, E> for LimitProbe<T, E> {
+    fn limit_error(&self, input: &T, _name: &str) -> Option<nom::Err<E>> {
+        #[cfg(feature = "trace-core")]
+        crate::report_depth_limit(input, _name);
+        Some(nom::Err::Failure(E::from_error_kind(
+            input.clone(),
+            ErrorKind::TooLarge,
+        )))
+    }
+}
+
+#[doc(hidden)]
+pub trait LimitProbeFallback<T, E> {
+    fn limit_error(&self, input: &T, name: &str) -> Option<nom::Err<E>>;
+}
+
+impl<T, E> LimitProbeFallback<T, E> for &LimitProbe<T, E> {
+    fn limit_error(&self, _input: &T, _name: &str) -> Option<nom::Err<E>> {
+        None
+    }
+}
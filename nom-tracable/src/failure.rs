@@ -0,0 +1,136 @@
+use crate::writer;
+#[cfg(feature = "trace-core")]
+use std::collections::HashMap;
+use std::io::Write;
+use std::time::Duration;
+
+/// Struct to have the success and failure counts of a parser, and the work wasted by the failures.
+///
+/// The wasted work is a hint to reorder `alt` branches:
+/// a branch which fails often after deep parsing should be tried later, or rejected earlier.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FailureStat {
+    pub parser: String,
+    pub success: usize,
+    pub failure: usize,
+    /// The number of parser calls in the failed invocations including themselves.
+    pub wasted_calls: usize,
+    /// The elapsed time of the failed invocations.
+    pub wasted_time: Duration,
+}
+
+impl FailureStat {
+    /// Get the ratio of the failures to the calls.
+    pub fn failure_rate(&self) -> f64 {
+        let calls = self.success + self.failure;
+        if calls == 0 {
+            0.0
+        } else {
+            self.failure as f64 / calls as f64
+        }
+    }
+}
+
+#[cfg(feature = "trace-core")]
+fn collect(
+    results: &HashMap<String, (usize, usize)>,
+    wasted: &HashMap<String, (usize, Duration)>,
+) -> Vec<FailureStat> {
+    let mut ret: Vec<_> = results
+        .iter()
+        .map(|(parser, (success, failure))| {
+            let (wasted_calls, wasted_time) = wasted.get(parser).copied().unwrap_or_default();
+            FailureStat {
+                parser: parser.clone(),
+                success: *success,
+                failure: *failure,
+                wasted_calls,
+                wasted_time,
+            }
+        })
+        .collect();
+    ret.sort_by(|x, y| {
+        y.wasted_calls
+            .cmp(&x.wasted_calls)
+            .then_with(|| y.wasted_time.cmp(&x.wasted_time))
+            .then_with(|| x.parser.cmp(&y.parser))
+    });
+    ret
+}
+
+/// Get the failure statistics of each parser of the last parse in descending order of the wasted calls.
+///
+/// The statistics information is reset at each parser call like histogram.
+pub fn failure_stats() -> Vec<FailureStat> {
+    failure_stats_internal()
+}
+
+#[cfg(feature = "trace-core")]
+fn failure_stats_internal() -> Vec<FailureStat> {
+    crate::TRACABLE_STORAGE.with(|storage| {
+        let storage = storage.borrow();
+        collect(&storage.results, &storage.wasted)
+    })
+}
+
+#[cfg(not(feature = "trace-core"))]
+fn failure_stats_internal() -> Vec<FailureStat> {
+    Vec::new()
+}
+
+/// Get the statistics by `failure_stats`, and show the report.
+pub fn show_failure_stats() -> Vec<FailureStat> {
+    let stats = failure_stats();
+
+    let max_parser_len = stats
+        .iter()
+        .map(|x| x.parser.len())
+        .max()
+        .unwrap_or(0)
+        .max("parser".len());
+
+    let mut lock = writer();
+
+    writeln!(
+        lock,
+        "\n{:<parser$} | {:>10} | {:>10} | {:>7} | {:>12} | {:>12}",
+        "parser",
+        "success",
+        "failure",
+        "rate",
+        "wasted calls",
+        "wasted time",
+        parser = max_parser_len,
+    )
+    .unwrap();
+
+    writeln!(
+        lock,
+        "{} | {} | {} | {} | {} | {}",
+        "-".repeat(max_parser_len),
+        "-".repeat(10),
+        "-".repeat(10),
+        "-".repeat(7),
+        "-".repeat(12),
+        "-".repeat(12),
+    )
+    .unwrap();
+
+    for x in &stats {
+        writeln!(
+            lock,
+            "{:<parser$} | {:>10} | {:>10} | {:>6.1}% | {:>12} | {:>12}",
+            x.parser,
+            x.success,
+            x.failure,
+            x.failure_rate() * 100.0,
+            x.wasted_calls,
+            format!("{:?}", x.wasted_time),
+            parser = max_parser_len,
+        )
+        .unwrap();
+    }
+    writeln!(lock).unwrap();
+
+    stats
+}
@@ -0,0 +1,253 @@
+use crate::{captured_trace, ndjson::escape, Trace, TraceEventKind};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+const GOLDEN_HEADER: &str = "# nom-tracable golden v1";
+
+/// Struct to have a parser invocation in the shape of a trace.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShapeEntry {
+    pub depth: usize,
+    pub parser: String,
+    /// `false` if the parser returned `Err` or did not return.
+    pub success: bool,
+}
+
+impl fmt::Display for ShapeEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let result = if self.success { "ok" } else { "err" };
+        write!(f, "{} at depth {} ({})", self.parser, self.depth, result)
+    }
+}
+
+/// Struct to have the structure of a trace: the parser names, the nesting and the results.
+///
+/// The offsets and fragments are not included, so the shape is stable for grammar refactoring
+/// which does not change the parse.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TraceShape {
+    /// The invocations in the call order.
+    pub entries: Vec<ShapeEntry>,
+}
+
+/// Struct to have the first difference between the shapes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// Index of the invocation in the call order.
+    pub index: usize,
+    /// The invocation of the golden shape. `None` if the golden shape is shorter.
+    pub expected: Option<ShapeEntry>,
+    /// The invocation of the current shape. `None` if the current shape is shorter.
+    pub actual: Option<ShapeEntry>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |x: &Option<ShapeEntry>| match x {
+            Some(x) => x.to_string(),
+            None => String::from("nothing"),
+        };
+        write!(
+            f,
+            "invocation {} : expected {}, found {}",
+            self.index,
+            show(&self.expected),
+            show(&self.actual)
+        )
+    }
+}
+
+impl Trace {
+    /// Get the shape of the trace.
+    pub fn shape(&self) -> TraceShape {
+        let mut entries = Vec::new();
+        let mut stack = Vec::new();
+        for event in &self.events {
+            match event.kind {
+                TraceEventKind::Forward => {
+                    stack.push(entries.len());
+                    entries.push(ShapeEntry {
+                        depth: event.depth,
+                        parser: event.parser.clone(),
+                        success: false,
+                    });
+                }
+                TraceEventKind::Ok | TraceEventKind::Err => {
+                    if let Some(index) = stack.pop() {
+                        entries[index].success = event.kind == TraceEventKind::Ok;
+                    }
+                }
+                TraceEventKind::Custom | TraceEventKind::Bookmark => (),
+            }
+        }
+        TraceShape { entries }
+    }
+}
+
+impl TraceShape {
+    /// Compare the shape against the golden shape, and get the first divergence.
+    pub fn first_divergence(&self, golden: &TraceShape) -> Option<Divergence> {
+        let len = self.entries.len().max(golden.entries.len());
+        (0..len).find_map(|index| {
+            let expected = golden.entries.get(index);
+            let actual = self.entries.get(index);
+            if expected == actual {
+                None
+            } else {
+                Some(Divergence {
+                    index,
+                    expected: expected.cloned(),
+                    actual: actual.cloned(),
+                })
+            }
+        })
+    }
+}
+
+/// Struct to have the golden shapes of a corpus of inputs.
+///
+/// The corpus is recorded by the reference grammar, and verified after refactoring.
+/// This requires `TracableInfo::capture` because the shapes are taken from the captured trace.
+///
+/// ```
+/// # use nom::character::complete::*;
+/// # use nom::IResult;
+/// # use nom_locate::LocatedSpan;
+/// # use nom_tracable::{tracable_parser, GoldenCorpus, TracableInfo};
+/// #
+/// # type Span<'a> = LocatedSpan<&'a str, TracableInfo>;
+/// #
+/// #[tracable_parser]
+/// pub fn term(s: Span) -> IResult<Span, String> {
+///     let (s, x) = char('1')(s)?;
+///     Ok((s, x.to_string()))
+/// }
+///
+/// let parse = |x: &str| {
+///     let _ = term(LocatedSpan::new_extra(x, TracableInfo::new().capture(true)));
+/// };
+/// let corpus = GoldenCorpus::record(&["1", "2"], parse);
+/// assert!(corpus.verify(parse).is_empty());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GoldenCorpus {
+    pub shapes: BTreeMap<String, TraceShape>,
+}
+
+impl GoldenCorpus {
+    /// Record the shapes of the inputs parsed by `f`.
+    ///
+    /// `f` should call the root parser once because the captured trace is reset at each root parse.
+    pub fn record<F: FnMut(&str)>(inputs: &[&str], mut f: F) -> Self {
+        let mut ret = GoldenCorpus::default();
+        for input in inputs {
+            f(input);
+            ret.shapes
+                .insert(String::from(*input), captured_trace().shape());
+        }
+        ret
+    }
+
+    /// Parse the inputs of the corpus by `f`, and get the first divergence per input.
+    pub fn verify<F: FnMut(&str)>(&self, mut f: F) -> Vec<(String, Divergence)> {
+        let mut ret = Vec::new();
+        for (input, golden) in &self.shapes {
+            f(input);
+            if let Some(x) = captured_trace().shape().first_divergence(golden) {
+                ret.push((input.clone(), x));
+            }
+        }
+        ret
+    }
+
+    /// Save the corpus to the specified path.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = io::BufWriter::new(std::fs::File::create(path)?);
+        writeln!(file, "{}", GOLDEN_HEADER)?;
+        for (input, shape) in &self.shapes {
+            writeln!(file, "input\t{}", escape(input))?;
+            for x in &shape.entries {
+                let result = if x.success { "ok" } else { "err" };
+                writeln!(file, "{}\t{}\t{}", x.depth, escape(&x.parser), result)?;
+            }
+        }
+        file.flush()
+    }
+
+    /// Load the corpus from the specified path.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = io::BufReader::new(std::fs::File::open(path)?);
+        let invalid = |line: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid golden line: {}", line),
+            )
+        };
+
+        let mut ret = GoldenCorpus::default();
+        let mut current = None;
+        for line in file.lines() {
+            let line = line?;
+            if line.starts_with('#') || line.is_empty() {
+                continue;
+            }
+            if let Some(input) = line.strip_prefix("input\t") {
+                let input = unescape(input).ok_or_else(|| invalid(&line))?;
+                ret.shapes.insert(input.clone(), TraceShape::default());
+                current = Some(input);
+                continue;
+            }
+            let shape = current
+                .as_ref()
+                .and_then(|x| ret.shapes.get_mut(x))
+                .ok_or_else(|| invalid(&line))?;
+            let mut columns = line.split('\t');
+            let depth = columns
+                .next()
+                .and_then(|x| x.parse().ok())
+                .ok_or_else(|| invalid(&line))?;
+            let parser = columns
+                .next()
+                .and_then(unescape)
+                .ok_or_else(|| invalid(&line))?;
+            let success = match columns.next() {
+                Some("ok") => true,
+                Some("err") => false,
+                _ => return Err(invalid(&line)),
+            };
+            shape.entries.push(ShapeEntry {
+                depth,
+                parser,
+                success,
+            });
+        }
+        Ok(ret)
+    }
+}
+
+/// Reverse of `escape`.
+pub(crate) fn unescape(x: &str) -> Option<String> {
+    let mut chars = x.strip_prefix('"')?.strip_suffix('"')?.chars();
+    let mut ret = String::new();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            ret.push(c);
+            continue;
+        }
+        match chars.next()? {
+            '"' => ret.push('"'),
+            '\\' => ret.push('\\'),
+            'n' => ret.push('\n'),
+            'r' => ret.push('\r'),
+            't' => ret.push('\t'),
+            'u' => {
+                let code: String = chars.by_ref().take(4).collect();
+                ret.push(char::from_u32(u32::from_str_radix(&code, 16).ok()?)?);
+            }
+            _ => return None,
+        }
+    }
+    Some(ret)
+}
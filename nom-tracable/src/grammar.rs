@@ -0,0 +1,162 @@
+use crate::{Trace, TraceEventKind};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+use std::path::Path;
+
+/// The maximum number of example fragments of each parser.
+const MAX_EXAMPLES: usize = 3;
+
+/// The maximum length of an example fragment.
+const MAX_EXAMPLE_LEN: usize = 40;
+
+/// Struct to have the observed behavior of a parser.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GrammarRule {
+    pub success: usize,
+    pub failure: usize,
+    /// Parsers which called this parser.
+    pub callers: BTreeSet<String>,
+    /// Parsers which were called by this parser.
+    pub callees: BTreeSet<String>,
+    /// Consumed lengths of the successful calls.
+    pub consumed: Vec<usize>,
+    /// Fragments matched by the successful calls.
+    pub examples: Vec<String>,
+}
+
+/// Struct to generate the reference of the grammar from captured traces.
+///
+/// The caller/callee edges, the consumed lengths and the matched fragments are collected
+/// from real runs, so the document reflects the actual behavior of the grammar.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GrammarDoc {
+    pub rules: BTreeMap<String, GrammarRule>,
+}
+
+impl GrammarDoc {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the observations of the captured trace.
+    pub fn add(&mut self, trace: &Trace) {
+        let base = trace
+            .events
+            .iter()
+            .find(|x| x.kind == TraceEventKind::Forward)
+            .map_or(0, |x| x.offset);
+
+        let mut stack: Vec<(&str, usize)> = Vec::new();
+        for event in &trace.events {
+            match event.kind {
+                TraceEventKind::Forward => {
+                    let name = event.parser.as_str();
+                    let rule = self.rules.entry(String::from(name)).or_default();
+                    if let Some((caller, _)) = stack.last() {
+                        rule.callers.insert(String::from(*caller));
+                        let caller = self.rules.entry(String::from(*caller)).or_default();
+                        caller.callees.insert(String::from(name));
+                    }
+                    stack.push((name, event.offset));
+                }
+                TraceEventKind::Ok => {
+                    let (name, entry) = match stack.pop() {
+                        Some(x) => x,
+                        None => continue,
+                    };
+                    let rule = self.rules.entry(String::from(name)).or_default();
+                    rule.success += 1;
+                    rule.consumed.push(event.offset.saturating_sub(entry));
+
+                    let start = entry.saturating_sub(base).min(trace.input.len());
+                    let end = event.offset.saturating_sub(base).min(trace.input.len());
+                    let example = example(&trace.input[start..end]);
+                    if !example.is_empty()
+                        && rule.examples.len() < MAX_EXAMPLES
+                        && !rule.examples.contains(&example)
+                    {
+                        rule.examples.push(example);
+                    }
+                }
+                TraceEventKind::Err => {
+                    if let Some((name, _)) = stack.pop() {
+                        self.rules.entry(String::from(name)).or_default().failure += 1;
+                    }
+                }
+                TraceEventKind::Custom | TraceEventKind::Bookmark => (),
+            }
+        }
+    }
+
+    /// Write the reference as Markdown.
+    pub fn write_markdown<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "# Grammar reference")?;
+        for (name, rule) in &self.rules {
+            writeln!(w, "\n## `{}`\n", name)?;
+            writeln!(
+                w,
+                "* calls : {} ({} ok, {} err)",
+                rule.success + rule.failure,
+                rule.success,
+                rule.failure
+            )?;
+            writeln!(w, "* called by : {}", links(&rule.callers))?;
+            writeln!(w, "* calls into : {}", links(&rule.callees))?;
+            if let Some((min, median, max)) = rule.consumption() {
+                writeln!(
+                    w,
+                    "* consumed bytes : min {} / median {} / max {}",
+                    min, median, max
+                )?;
+            }
+            if !rule.examples.is_empty() {
+                writeln!(w, "* examples :")?;
+                for x in &rule.examples {
+                    writeln!(w, "    * `{}`", x.replace('`', "'"))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Save the reference as Markdown to the specified path.
+    pub fn save_markdown<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = io::BufWriter::new(std::fs::File::create(path)?);
+        self.write_markdown(&mut file)?;
+        file.flush()
+    }
+}
+
+impl GrammarRule {
+    /// Get the minimum, median and maximum of the consumed lengths.
+    pub fn consumption(&self) -> Option<(usize, usize, usize)> {
+        let mut x = self.consumed.clone();
+        x.sort_unstable();
+        Some((*x.first()?, x[x.len() / 2], *x.last()?))
+    }
+}
+
+/// Format parser names as links to the sections.
+fn links(names: &BTreeSet<String>) -> String {
+    if names.is_empty() {
+        return String::from("-");
+    }
+    let links: Vec<_> = names
+        .iter()
+        .map(|x| format!("[`{}`](#{})", x, x.to_lowercase()))
+        .collect();
+    links.join(", ")
+}
+
+fn example(x: &[u8]) -> String {
+    let x = String::from_utf8_lossy(x);
+    let mut ret: String = x
+        .chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .take(MAX_EXAMPLE_LEN)
+        .collect();
+    if x.chars().count() > MAX_EXAMPLE_LEN {
+        ret.push('…');
+    }
+    ret
+}
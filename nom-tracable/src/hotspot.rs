@@ -0,0 +1,96 @@
+use crate::writer;
+#[cfg(feature = "trace-core")]
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+
+/// Struct to have an input offset which is visited by the forward traces many times.
+///
+/// Many visits at the same offset mean that the parsers backtrack to it repeatedly.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hotspot {
+    pub offset: usize,
+    /// The number of the forward traces at the offset.
+    pub visits: usize,
+    /// The parsers entered at the offset and the count of each, in descending order of the count.
+    pub parsers: Vec<(String, usize)>,
+}
+
+#[cfg(feature = "trace-core")]
+fn collect(visits: &HashMap<(String, usize), usize>, n: usize) -> Vec<Hotspot> {
+    let mut offsets: BTreeMap<usize, Vec<(String, usize)>> = BTreeMap::new();
+    for ((parser, offset), count) in visits {
+        offsets
+            .entry(*offset)
+            .or_default()
+            .push((parser.clone(), *count));
+    }
+
+    let mut ret: Vec<_> = offsets
+        .into_iter()
+        .map(|(offset, mut parsers)| {
+            parsers.sort_by(|x, y| y.1.cmp(&x.1).then_with(|| x.0.cmp(&y.0)));
+            Hotspot {
+                offset,
+                visits: parsers.iter().map(|x| x.1).sum(),
+                parsers,
+            }
+        })
+        .collect();
+    // The sort is stable, so the same visits are in offset order
+    ret.sort_by_key(|x| std::cmp::Reverse(x.visits));
+    ret.truncate(n);
+    ret
+}
+
+/// Get the `n` most visited input offsets of the last parse.
+///
+/// The statistics information is reset at each parser call like histogram.
+pub fn hotspots(n: usize) -> Vec<Hotspot> {
+    hotspots_internal(n)
+}
+
+#[cfg(feature = "trace-core")]
+fn hotspots_internal(n: usize) -> Vec<Hotspot> {
+    crate::TRACABLE_STORAGE.with(|storage| collect(&storage.borrow().visits, n))
+}
+
+#[cfg(not(feature = "trace-core"))]
+fn hotspots_internal(_n: usize) -> Vec<Hotspot> {
+    Vec::new()
+}
+
+/// Find the offsets by `hotspots`, and show the report.
+pub fn show_hotspots(n: usize) -> Vec<Hotspot> {
+    let hotspots = hotspots(n);
+
+    let mut lock = writer();
+
+    writeln!(lock, "\n{:>10} | {:>10} | parsers", "offset", "visits").unwrap();
+    writeln!(
+        lock,
+        "{} | {} | {}",
+        "-".repeat(10),
+        "-".repeat(10),
+        "-".repeat(10)
+    )
+    .unwrap();
+
+    for x in &hotspots {
+        let parsers: Vec<_> = x
+            .parsers
+            .iter()
+            .map(|(parser, count)| format!("{}({})", parser, count))
+            .collect();
+        writeln!(
+            lock,
+            "{:>10} | {:>10} | {}",
+            x.offset,
+            x.visits,
+            parsers.join(" ")
+        )
+        .unwrap();
+    }
+    writeln!(lock).unwrap();
+
+    hotspots
+}
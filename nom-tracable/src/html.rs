@@ -0,0 +1,225 @@
+use crate::{IncludedTrace, Trace, TraceEvent, TraceEventKind};
+use std::io::{self, Write};
+use std::path::Path;
+
+const STYLE: &str = "
+body { font-family: monospace; background: #1e1e1e; color: #d4d4d4; }
+details { margin-left: 1.5em; border-left: 1px dotted #555; padding-left: 0.5em; }
+summary { cursor: pointer; white-space: pre; }
+.parser { color: #ffffff; font-weight: bold; }
+.ok { color: #4ec94e; }
+.err { color: #f14c4c; }
+.unknown { color: #cca700; }
+.fragment { color: #9cdcfe; }
+.custom { margin-left: 2.5em; white-space: pre; color: #c586c0; }
+.include { color: #cca700; }
+.bookmark { margin: 0.3em 0 0.3em 1.5em; color: #4fc1ff; font-weight: bold; }
+nav a { color: #4fc1ff; margin-right: 1em; }
+";
+
+const SCRIPT: &str = "
+function setAll(open) {
+  document.querySelectorAll('details').forEach(function (d) { d.open = open; });
+}
+function openTarget() {
+  var e = document.getElementById(location.hash.slice(1));
+  for (; e; e = e.parentElement) { if (e.tagName === 'DETAILS') { e.open = true; } }
+}
+window.addEventListener('hashchange', openTarget);
+";
+
+/// A parser invocation rebuilt from forward/backward events.
+struct Node<'a> {
+    forward: &'a TraceEvent,
+    backward: Option<&'a TraceEvent>,
+    children: Vec<Child<'a>>,
+}
+
+enum Child<'a> {
+    Node(Node<'a>),
+    Custom(&'a TraceEvent),
+    Bookmark(usize, &'a TraceEvent),
+    Include(usize, &'a IncludedTrace),
+}
+
+fn build_tree(trace: &Trace) -> Vec<Child<'_>> {
+    let mut roots = Vec::new();
+    let mut stack: Vec<Node> = Vec::new();
+    let mut bookmarks = 0;
+    let mut includes = trace.includes.iter().enumerate().peekable();
+
+    for (position, event) in trace.events.iter().enumerate() {
+        while let Some((index, include)) = includes.next_if(|(_, x)| x.position <= position) {
+            let child = Child::Include(index, include);
+            match stack.last_mut() {
+                Some(parent) => parent.children.push(child),
+                None => roots.push(child),
+            }
+        }
+        match event.kind {
+            TraceEventKind::Forward => stack.push(Node {
+                forward: event,
+                backward: None,
+                children: Vec::new(),
+            }),
+            TraceEventKind::Ok | TraceEventKind::Err => {
+                if let Some(mut node) = stack.pop() {
+                    node.backward = Some(event);
+                    match stack.last_mut() {
+                        Some(parent) => parent.children.push(Child::Node(node)),
+                        None => roots.push(Child::Node(node)),
+                    }
+                }
+            }
+            TraceEventKind::Custom => match stack.last_mut() {
+                Some(parent) => parent.children.push(Child::Custom(event)),
+                None => roots.push(Child::Custom(event)),
+            },
+            TraceEventKind::Bookmark => {
+                let child = Child::Bookmark(bookmarks, event);
+                bookmarks += 1;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(child),
+                    None => roots.push(child),
+                }
+            }
+        }
+    }
+
+    // Invocations without backward event (e.g. panic in the parser)
+    while let Some(node) = stack.pop() {
+        match stack.last_mut() {
+            Some(parent) => parent.children.push(Child::Node(node)),
+            None => roots.push(Child::Node(node)),
+        }
+    }
+
+    for (index, include) in includes {
+        roots.push(Child::Include(index, include));
+    }
+
+    roots
+}
+
+fn escape(x: &str) -> String {
+    let mut ret = String::with_capacity(x.len());
+    for c in x.chars() {
+        match c {
+            '&' => ret.push_str("&amp;"),
+            '<' => ret.push_str("&lt;"),
+            '>' => ret.push_str("&gt;"),
+            '"' => ret.push_str("&quot;"),
+            '\'' => ret.push_str("&#39;"),
+            _ => ret.push(c),
+        }
+    }
+    ret
+}
+
+/// `prefix` makes the ids of bookmarks in nested contexts unique.
+fn write_child<W: Write>(w: &mut W, child: &Child, prefix: &str) -> io::Result<()> {
+    match child {
+        Child::Node(node) => {
+            let (class, result, remaining) = match node.backward {
+                Some(x) if x.kind == TraceEventKind::Ok => ("ok", "ok", Some(&x.fragment)),
+                Some(_) => ("err", "err", None),
+                None => ("unknown", "?", None),
+            };
+            write!(
+                w,
+                "<details open><summary><span class=\"parser\">{}</span> <span class=\"{}\">{}</span> <span class=\"fragment\">{}</span>",
+                escape(&node.forward.parser),
+                class,
+                result,
+                escape(&node.forward.fragment),
+            )?;
+            if let Some(remaining) = remaining {
+                write!(
+                    w,
+                    " =&gt; <span class=\"fragment\">{}</span>",
+                    escape(remaining)
+                )?;
+            }
+            writeln!(w, "</summary>")?;
+            for child in &node.children {
+                write_child(w, child, prefix)?;
+            }
+            writeln!(w, "</details>")
+        }
+        Child::Custom(event) => writeln!(
+            w,
+            "<div class=\"custom\">{} : {}</div>",
+            escape(&event.parser),
+            escape(&event.fragment)
+        ),
+        Child::Bookmark(index, event) => writeln!(
+            w,
+            "<div class=\"bookmark\" id=\"{}bookmark-{}\">=== {} (offset {}) ===</div>",
+            prefix,
+            index,
+            escape(&event.parser),
+            event.offset
+        ),
+        Child::Include(index, include) => {
+            writeln!(
+                w,
+                "<details open><summary><span class=\"include\">include : {}</span></summary>",
+                escape(&include.source)
+            )?;
+            let prefix = format!("{}include-{}-", prefix, index);
+            for child in &build_tree(&include.trace) {
+                write_child(w, child, &prefix)?;
+            }
+            writeln!(w, "</details>")
+        }
+    }
+}
+
+impl Trace {
+    /// Write the trace as a self-contained HTML report.
+    ///
+    /// Each parser invocation is a collapsible node which shows the input fragment,
+    /// the result and the children.
+    pub fn write_html<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "<!DOCTYPE html>")?;
+        writeln!(w, "<html>\n<head>\n<meta charset=\"utf-8\">")?;
+        writeln!(w, "<title>nom-tracable report</title>")?;
+        writeln!(w, "<style>{}</style>", STYLE)?;
+        writeln!(w, "<script>{}</script>", SCRIPT)?;
+        writeln!(w, "</head>\n<body>")?;
+        writeln!(
+            w,
+            "<button onclick=\"setAll(true)\">expand all</button> <button onclick=\"setAll(false)\">collapse all</button>"
+        )?;
+        let bookmarks = self
+            .events
+            .iter()
+            .filter(|x| x.kind == TraceEventKind::Bookmark);
+        writeln!(w, "<nav>")?;
+        for (index, event) in bookmarks.enumerate() {
+            writeln!(
+                w,
+                "<a href=\"#bookmark-{}\">{}</a>",
+                index,
+                escape(&event.parser)
+            )?;
+        }
+        writeln!(w, "</nav>")?;
+        writeln!(
+            w,
+            "<p>parser : <span class=\"fragment\">{}</span></p>",
+            escape(&self.header)
+        )?;
+        for child in &build_tree(self) {
+            write_child(w, child, "")?;
+        }
+        writeln!(w, "</body>\n</html>")
+    }
+
+    /// Save the trace as a self-contained HTML report to the specified path.
+    pub fn save_html<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = io::BufWriter::new(std::fs::File::create(path)?);
+        self.write_html(&mut file)?;
+        file.flush()
+    }
+}
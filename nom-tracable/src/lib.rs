@@ -21,43 +21,204 @@
 //!     Ok((s, x.to_string()))
 //! }
 //!
-//! #[test]
-//! fn test() {
-//!     // Configure trace setting
-//!     let info = TracableInfo::new().forward(true).backward(true);
-//!     let ret = term(LocatedSpan::new_extra("1", info));
-//!     assert_eq!("\"1\"", format!("{:?}", ret.unwrap().1));
-//! }
+//! // Configure trace setting
+//! let info = TracableInfo::new().forward(true).backward(true);
+//! let ret = term(LocatedSpan::new_extra("1", info));
+//! assert_eq!("\"1\"", format!("{:?}", ret.unwrap().1));
 //! ```
 
-#[cfg(feature = "trace")]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use core::time::Duration;
+#[cfg(feature = "trace-core")]
 use nom::IResult;
 /// Custom attribute to enable trace
 pub use nom_tracable_macros::tracable_parser;
-use std::{collections::HashMap, io::Write};
+#[cfg(feature = "std")]
+use std::{collections::HashMap, io::Write, time::Instant};
+
+#[cfg(feature = "std")]
+mod anomaly;
+#[cfg(all(feature = "bare", not(feature = "trace-core")))]
+mod bare;
+#[cfg(feature = "std")]
+mod callgraph;
+#[cfg(feature = "std")]
+mod cancel;
+mod color;
+mod combinator;
+#[cfg(feature = "std")]
+mod config;
+#[cfg(all(feature = "trace-core", feature = "wasm", target_arch = "wasm32"))]
+mod console;
+#[cfg(feature = "std")]
+mod cooccurrence;
+#[cfg(feature = "std")]
+mod cumulative;
+#[cfg(feature = "std")]
+mod debugger;
+#[cfg(feature = "std")]
+mod diff;
+mod error;
+#[cfg(feature = "std")]
+mod failure;
+#[cfg(feature = "std")]
+mod golden;
+#[cfg(feature = "std")]
+mod grammar;
+#[cfg(feature = "std")]
+mod hotspot;
+#[cfg(feature = "std")]
+mod html;
+#[cfg(feature = "std")]
+mod ndjson;
+#[cfg(feature = "std")]
+mod profile;
+#[cfg(feature = "std")]
+mod record;
+#[cfg(feature = "std")]
+mod render;
+#[cfg(feature = "std")]
+mod snippet;
+mod span;
+#[cfg(feature = "trace-core")]
+mod summary;
+#[cfg(feature = "trace-core")]
+mod task;
+#[cfg(feature = "std")]
+mod testgen;
+#[cfg(feature = "std")]
+mod warning;
+#[cfg(feature = "std")]
+pub use anomaly::{Anomaly, AnomalyKind};
+#[cfg(all(feature = "bare", not(feature = "trace-core")))]
+pub use bare::{
+    backward_trace, backward_trace_detail, backward_trace_output, forward_trace, forward_trace_args,
+};
+#[doc(hidden)]
+#[cfg(all(feature = "bare", not(feature = "trace-core")))]
+pub use bare::{depth_limit_exceeded, memoize, register_group, unwind_guard, UnwindGuard};
+#[cfg(feature = "std")]
+pub use callgraph::{call_graph, CallEdge, CallGraph};
+#[cfg(feature = "std")]
+pub use cancel::{cancellation, set_cancellation_token, Cancellation, CancellationToken};
+pub use color::ColorScheme;
+pub use combinator::tracable;
+#[cfg(feature = "std")]
+pub use cooccurrence::Cooccurrence;
+#[cfg(feature = "std")]
+pub use cumulative::{
+    cumulative_stats, reset_cumulative, show_cumulative_stats, CumulativeStats, ParserTotal,
+};
+#[cfg(feature = "std")]
+pub use debugger::set_debugger_input;
+#[cfg(feature = "std")]
+pub use diff::{diff, TraceDiff};
+pub use error::{ErrorDetail, TraceError};
+#[doc(hidden)]
+pub use error::{
+    ErrorProbe, ErrorProbeDetail, ErrorProbeFallback, LimitProbe, LimitProbeError,
+    LimitProbeFallback,
+};
+#[cfg(feature = "std")]
+pub use failure::{failure_stats, show_failure_stats, FailureStat};
+#[cfg(feature = "std")]
+pub use golden::{Divergence, GoldenCorpus, ShapeEntry, TraceShape};
+#[cfg(feature = "std")]
+pub use grammar::{GrammarDoc, GrammarRule};
+#[cfg(feature = "std")]
+pub use hotspot::{hotspots, show_hotspots, Hotspot};
+#[cfg(feature = "std")]
+pub use profile::{profile, Profile, ProfileEntry, Regression};
+#[cfg(feature = "std")]
+pub use render::{render, render_event, render_page, RenderOptions};
+pub use span::{TracableBytes, TracableSpan, TracableStr};
+#[cfg(feature = "trace-core")]
+pub use task::{task_context, TaskContext};
+#[cfg(feature = "trace-core")]
+use warning::report_warning;
+#[cfg(feature = "std")]
+pub use warning::{set_warning_subscriber, warnings, Warning, WarningKind};
 
 /// Trait to indicate the type can display as fragment.
+#[cfg(feature = "std")]
 pub trait FragmentDisplay {
     fn display(&self, width: usize) -> String;
+
+    /// Display the fragment with control characters escaped by `TracableInfo::escape_control`.
+    ///
+    /// The default is `display`, which is for types without control characters.
+    fn display_escaped(&self, width: usize) -> String {
+        self.display(width)
+    }
 }
 
+/// Bytes are shown as a hexdump with an ASCII gutter like `31 2B 00 |1+.|`.
+/// Truncated bytes are followed by `…`.
+#[cfg(feature = "std")]
 impl FragmentDisplay for &[u8] {
     fn display(&self, width: usize) -> String {
-        self.iter()
-            .take(width / 2)
-            .map(|x| format!("{:>02X}", x))
-            .collect()
+        // A byte takes 3 columns of the hexdump and 1 column of the gutter
+        let count = width.saturating_sub(2) / 4;
+        let (count, ellipsis) = if count < self.len() {
+            (width.saturating_sub(3) / 4, "…")
+        } else {
+            (count, "")
+        };
+        let bytes = &self[..count.min(self.len())];
+        if bytes.is_empty() {
+            return String::from(ellipsis);
+        }
+
+        let hex: Vec<_> = bytes.iter().map(|x| format!("{:02X}", x)).collect();
+        let ascii: String = bytes
+            .iter()
+            .map(|&x| {
+                if x.is_ascii_graphic() || x == b' ' {
+                    char::from(x)
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        format!("{} |{}|{}", hex.join(" "), ascii, ellipsis)
     }
 }
 
+/// The first line is shown, and truncated to `width` characters with `…`.
+#[cfg(feature = "std")]
 impl FragmentDisplay for &str {
     fn display(&self, width: usize) -> String {
-        self.lines()
-            .next()
-            .unwrap_or_else(|| "")
-            .chars()
-            .take(width)
-            .collect()
+        let line = self.lines().next().unwrap_or("");
+        if line.chars().nth(width).is_none() {
+            return String::from(line);
+        }
+        let mut ret: String = line.chars().take(width.saturating_sub(1)).collect();
+        if width != 0 {
+            ret.push('…');
+        }
+        ret
+    }
+
+    /// The whole fragment is shown in a line, with control characters like `\n` escaped.
+    fn display_escaped(&self, width: usize) -> String {
+        // The escaped text is built until it exceeds `width`, so a large input is not copied
+        let mut escaped = String::new();
+        let mut len = 0;
+        for x in self.chars() {
+            if len > width {
+                break;
+            }
+            if x.is_control() {
+                let x = x.escape_default();
+                len += x.len();
+                escaped.extend(x);
+            } else {
+                len += 1;
+                escaped.push(x);
+            }
+        }
+        escaped.as_str().display(width)
     }
 }
 
@@ -65,8 +226,20 @@ impl FragmentDisplay for &str {
 pub trait Tracable: HasTracableInfo {
     fn inc_depth(self) -> Self;
     fn dec_depth(self) -> Self;
+    #[cfg(feature = "std")]
     fn format(&self) -> String;
+    #[cfg(feature = "std")]
     fn header(&self) -> String;
+
+    /// Offset of the input from the beginning of the whole input.
+    fn offset(&self) -> usize {
+        0
+    }
+
+    /// Bytes of the input.
+    fn fragment_bytes(&self) -> &[u8] {
+        &[]
+    }
 }
 
 /// Trait to indicate `TracableInfo` is provided.
@@ -76,54 +249,249 @@ pub trait HasTracableInfo {
 }
 
 /// Struct to have trace configuration.
-#[derive(Clone, Copy, Debug, PartialEq)]
+///
+/// This is `Clone` but not `Copy`, because the options are shared by `Arc` with `trace-core`.
+/// It is not `Copy` without `trace-core` too, so parsers are compiled regardless of the features.
+#[derive(Clone, Debug, PartialEq)]
 pub struct TracableInfo {
-    #[cfg(feature = "trace")]
+    #[cfg(feature = "trace-core")]
     pub depth: usize,
-    #[cfg(feature = "trace")]
+    #[cfg(feature = "trace-core")]
     pub forward: bool,
-    #[cfg(feature = "trace")]
+    #[cfg(feature = "trace-core")]
     pub backward: bool,
-    #[cfg(feature = "trace")]
+    #[cfg(feature = "trace-core")]
     pub custom: bool,
-    #[cfg(feature = "trace")]
+    #[cfg(feature = "trace-core")]
     pub color: bool,
-    #[cfg(feature = "trace")]
+    #[cfg(feature = "trace-core")]
     pub count_width: usize,
-    #[cfg(feature = "trace")]
+    #[cfg(feature = "trace-core")]
     pub parser_width: usize,
-    #[cfg(feature = "trace")]
+    #[cfg(feature = "trace-core")]
     pub fragment_width: usize,
-    #[cfg(feature = "trace")]
+    #[cfg(feature = "trace-core")]
     pub fold: u64,
+    #[cfg(feature = "trace-core")]
+    options: SharedOptions,
+    /// The ID by `session_id` is not in `options`, because each input of a batch may have an ID.
+    /// It is freed with the last input which has it.
+    #[cfg(feature = "trace-core")]
+    session_id: Option<std::sync::Arc<str>>,
+}
+
+/// `TracableOptions` shared by the copies of `TracableInfo`.
+///
+/// `None` is the default options, so `TracableInfo::new` doesn't allocate.
+/// The options are freed with the last `TracableInfo` which refers to them.
+#[cfg(feature = "trace-core")]
+#[derive(Clone, Default)]
+struct SharedOptions(Option<std::sync::Arc<TracableOptions>>);
+
+#[cfg(feature = "trace-core")]
+impl core::ops::Deref for SharedOptions {
+    type Target = TracableOptions;
+
+    fn deref(&self) -> &TracableOptions {
+        self.0.as_deref().unwrap_or(&DEFAULT_OPTIONS)
+    }
+}
+
+#[cfg(feature = "trace-core")]
+impl PartialEq for SharedOptions {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
 }
 
+#[cfg(feature = "trace-core")]
+impl core::fmt::Debug for SharedOptions {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+/// Struct to have trace configuration which is referred from `TracableInfo`.
+///
+/// `TracableInfo` is cloned into every input, so additional settings are shared here
+/// to keep the input type small.
+#[cfg(feature = "trace-core")]
+#[derive(Clone, Debug, PartialEq)]
+struct TracableOptions {
+    capture: bool,
+    time_budget: Option<Duration>,
+    timing: bool,
+    watchdog: Option<usize>,
+    bookmarks: Vec<(usize, String)>,
+    max_depth: Option<usize>,
+    depth_limit: Option<usize>,
+    failure_snippet: Option<usize>,
+    invocation_budgets: Vec<(String, usize)>,
+    include: Option<Vec<String>>,
+    exclude: Vec<String>,
+    disabled_groups: Vec<String>,
+    ndjson: Option<std::path::PathBuf>,
+    ndjson_filter: Option<EventFilter>,
+    on_forward: Option<EventHook>,
+    on_backward: Option<EventHook>,
+    failures_only: bool,
+    trace_range: Option<std::ops::Range<usize>>,
+    watches: Vec<String>,
+    watch_predicates: Vec<FragmentPredicate>,
+    breakpoints: Vec<String>,
+    break_on_watch: bool,
+    fragment_format: Option<FragmentFormatter>,
+    line_format: Option<LineFormatter>,
+    fold_summaries: Vec<(String, FoldSummaryHook)>,
+    sampling: Option<usize>,
+    ring_buffer: Option<usize>,
+    color_scheme: ColorScheme,
+    escalation: Option<(usize, usize)>,
+    output: TraceOutput,
+    output_file: Option<std::path::PathBuf>,
+    flush: TraceFlush,
+    thread_tag: bool,
+    session_tag: bool,
+    output_width: usize,
+    parser_colors: bool,
+    location_style: LocationStyle,
+    escape_control: bool,
+    show_consumed: bool,
+    show_matched: bool,
+    tree_style: bool,
+    collapse_repeats: bool,
+    snapshot: bool,
+    summary: bool,
+}
+
+#[cfg(feature = "trace-core")]
+type EventFilter = Hook<fn(&TraceEvent) -> bool>;
+
+#[cfg(feature = "trace-core")]
+type EventHook = Hook<fn(&TraceEvent)>;
+
+#[cfg(feature = "trace-core")]
+type FragmentPredicate = Hook<fn(&[u8]) -> bool>;
+
+#[cfg(feature = "trace-core")]
+type FragmentFormatter = Hook<fn(&[u8], usize) -> String>;
+
+#[cfg(feature = "trace-core")]
+type LineFormatter = Hook<fn(&TraceLine) -> String>;
+
+#[cfg(feature = "trace-core")]
+type FoldSummaryHook = Hook<fn(&FoldSummary) -> String>;
+
+/// Function pointer in `TracableOptions`.
+///
+/// This is compared by address. Functions may be merged or duplicated by the compiler,
+/// but it only affects whether the options are equal.
+#[cfg(feature = "trace-core")]
+#[derive(Clone, Copy, Debug)]
+struct Hook<F>(F);
+
+#[cfg(feature = "trace-core")]
+macro_rules! impl_hook_eq {
+    ($($t:ty),*) => {
+        $(
+            impl PartialEq for Hook<$t> {
+                fn eq(&self, other: &Self) -> bool {
+                    self.0 as usize == other.0 as usize
+                }
+            }
+        )*
+    };
+}
+
+#[cfg(feature = "trace-core")]
+impl_hook_eq!(
+    fn(&TraceEvent) -> bool,
+    fn(&TraceEvent),
+    fn(&[u8]) -> bool,
+    fn(&[u8], usize) -> String,
+    fn(&TraceLine) -> String,
+    fn(&FoldSummary) -> String
+);
+
+#[cfg(feature = "trace-core")]
+static DEFAULT_OPTIONS: TracableOptions = TracableOptions {
+    capture: false,
+    time_budget: None,
+    timing: false,
+    watchdog: None,
+    bookmarks: Vec::new(),
+    max_depth: None,
+    depth_limit: None,
+    failure_snippet: None,
+    invocation_budgets: Vec::new(),
+    include: None,
+    exclude: Vec::new(),
+    disabled_groups: Vec::new(),
+    ndjson: None,
+    ndjson_filter: None,
+    on_forward: None,
+    on_backward: None,
+    failures_only: false,
+    trace_range: None,
+    watches: Vec::new(),
+    watch_predicates: Vec::new(),
+    breakpoints: Vec::new(),
+    break_on_watch: false,
+    fragment_format: None,
+    line_format: None,
+    fold_summaries: Vec::new(),
+    sampling: None,
+    ring_buffer: None,
+    color_scheme: ColorScheme::DEFAULT,
+    escalation: None,
+    output: TraceOutput::DEFAULT,
+    output_file: None,
+    flush: TraceFlush::Line,
+    thread_tag: false,
+    session_tag: false,
+    output_width: 40,
+    parser_colors: false,
+    location_style: LocationStyle::Offset,
+    escape_control: false,
+    show_consumed: false,
+    show_matched: false,
+    tree_style: false,
+    collapse_repeats: false,
+    snapshot: false,
+    summary: false,
+};
+
+#[allow(clippy::derivable_impls)]
 impl Default for TracableInfo {
     fn default() -> Self {
         TracableInfo {
-            #[cfg(feature = "trace")]
+            #[cfg(feature = "trace-core")]
             depth: 0,
-            #[cfg(feature = "trace")]
+            #[cfg(feature = "trace-core")]
             forward: true,
-            #[cfg(feature = "trace")]
+            #[cfg(feature = "trace-core")]
             backward: true,
-            #[cfg(feature = "trace")]
+            #[cfg(feature = "trace-core")]
             custom: true,
-            #[cfg(feature = "trace")]
-            color: true,
-            #[cfg(feature = "trace")]
+            #[cfg(feature = "trace-core")]
+            color: !cfg!(feature = "log") && cfg!(feature = "trace-color") && color_supported(),
+            #[cfg(feature = "trace-core")]
             count_width: 10,
-            #[cfg(feature = "trace")]
+            #[cfg(feature = "trace-core")]
             parser_width: 96,
-            #[cfg(feature = "trace")]
+            #[cfg(feature = "trace-core")]
             fragment_width: 96,
-            #[cfg(feature = "trace")]
+            #[cfg(feature = "trace-core")]
             fold: 0,
+            #[cfg(feature = "trace-core")]
+            options: SharedOptions::default(),
+            #[cfg(feature = "trace-core")]
+            session_id: None,
         }
     }
 }
 
-#[cfg(feature = "trace")]
+#[cfg(feature = "trace-core")]
 impl TracableInfo {
     pub fn new() -> Self {
         TracableInfo::default()
@@ -134,6 +502,17 @@ impl TracableInfo {
         self
     }
 
+    /// Modify the options. A builder chain modifies the options in place,
+    /// and they are cloned only if other `TracableInfo` shares them.
+    fn set_options(mut self, f: impl FnOnce(&mut TracableOptions)) -> Self {
+        let options = self
+            .options
+            .0
+            .get_or_insert_with(|| std::sync::Arc::new(DEFAULT_OPTIONS.clone()));
+        f(std::sync::Arc::make_mut(options));
+        self
+    }
+
     /// Set whether forward trace is displayed.
     pub fn forward(mut self, x: bool) -> Self {
         self.forward = x;
@@ -153,11 +532,20 @@ impl TracableInfo {
     }
 
     /// Set whether color is enabled.
+    ///
+    /// Color is enabled by default unless `NO_COLOR` environment variable is set or the output is not a terminal.
+    /// On Windows, the virtual terminal processing of the console is enabled,
+    /// and color is disabled if the console doesn't support it.
     pub fn color(mut self, x: bool) -> Self {
-        self.color = x;
+        self.color = x && virtual_terminal();
         self
     }
 
+    /// Set the colors used if `color` is enabled.
+    pub fn color_scheme(self, x: ColorScheme) -> Self {
+        self.set_options(|o| o.color_scheme = x)
+    }
+
     /// Set the width of forward/backward count.
     pub fn count_width(mut self, x: usize) -> Self {
         self.count_width = x;
@@ -171,6 +559,8 @@ impl TracableInfo {
     }
 
     /// Set the width of fragment.
+    ///
+    /// Longer fragments are truncated with `…`.
     pub fn fragment_width(mut self, x: usize) -> Self {
         self.fragment_width = x;
         self
@@ -188,7 +578,463 @@ impl TracableInfo {
         self
     }
 
-    fn folded(self, x: &str) -> bool {
+    /// Set whether trace events are captured for `captured_trace`.
+    pub fn capture(self, x: bool) -> Self {
+        self.set_options(|o| o.capture = x)
+    }
+
+    /// Set the wall-clock budget of the root parse.
+    ///
+    /// If the budget is exceeded, a timeout marker, the current parser stack and
+    /// the partial statistics are displayed, and the hook by `set_abort_hook` is called.
+    pub fn time_budget(self, x: Duration) -> Self {
+        self.set_options(|o| o.time_budget = Some(x))
+    }
+
+    /// Set whether elapsed time of each parser is displayed.
+    ///
+    /// The total/average time of each parser is displayed at the end of the root parse.
+    pub fn timing(self, x: bool) -> Self {
+        self.set_options(|o| o.timing = x)
+    }
+
+    /// Set the maximum count that the same parser is entered at the same offset.
+    ///
+    /// If the count is exceeded, a warning is displayed and the hook by `set_abort_hook` is called.
+    /// This detects infinite loops which don't consume input.
+    pub fn watchdog(self, x: usize) -> Self {
+        self.set_options(|o| o.watchdog = Some(x))
+    }
+
+    /// Set the maximum depth of displayed trace.
+    ///
+    /// Lines deeper than the depth are elided and summarized by a line like `… 42 nested events elided`.
+    pub fn max_depth(self, x: usize) -> Self {
+        self.set_options(|o| o.max_depth = Some(x))
+    }
+
+    /// Set the maximum nesting depth of the parsers.
+    ///
+    /// A parser entered deeper than the limit fails by `Err::Failure` with `ErrorKind::TooLarge` without running,
+    /// and a warning is displayed. This guards the stack against deeply nested inputs.
+    /// The error is made by `ParseError::from_error_kind`, so parsers whose error type doesn't implement `ParseError`
+    /// are not stopped. The depth is counted by the traced parsers, so parsers in `without_trace!`
+    /// or disabled by `set_enabled` are not counted.
+    pub fn depth_limit(self, x: usize) -> Self {
+        self.set_options(|o| o.depth_limit = Some(x))
+    }
+
+    /// Set whether the excerpt of the input is displayed if the root parse fails.
+    ///
+    /// The excerpt has `context` lines around the furthest offset at which a traced parser failed,
+    /// the marker under the offset, and the parser chain of the failure.
+    /// The whole input is copied at the start of the root parse.
+    pub fn failure_snippet(self, context: usize) -> Self {
+        self.set_options(|o| o.failure_snippet = Some(context))
+    }
+
+    /// Set the maximum invocation count of the parser in a parse.
+    ///
+    /// If the count is exceeded, a warning is displayed and the hook by `set_abort_hook` is called.
+    /// This detects exponential blowups of the specific parser deterministically.
+    pub fn invocation_budget(self, name: &str, x: usize) -> Self {
+        self.set_options(|o| {
+            o.invocation_budgets.retain(|y| y.0 != name);
+            o.invocation_budgets.push((String::from(name), x));
+        })
+    }
+
+    /// Add a named bookmark at the offset.
+    ///
+    /// A marker line is displayed when the parse reaches the offset at first,
+    /// and the bookmark is captured as `TraceEventKind::Bookmark`.
+    pub fn bookmark(self, offset: usize, name: &str) -> Self {
+        self.set_options(|o| {
+            o.bookmarks.push((offset, String::from(name)));
+            o.bookmarks.sort_by_key(|x| x.0);
+        })
+    }
+
+    /// Set the names of parsers which are displayed.
+    ///
+    /// The other parsers are not displayed, but they are still counted.
+    pub fn include(self, x: &[&str]) -> Self {
+        self.set_options(|o| o.include = Some(x.iter().map(|x| String::from(*x)).collect()))
+    }
+
+    /// Set the names of parsers which are not displayed.
+    ///
+    /// The parsers called from the excluded parsers are displayed. Use `fold` to hide them.
+    pub fn exclude(self, x: &[&str]) -> Self {
+        self.set_options(|o| o.exclude = x.iter().map(|x| String::from(*x)).collect())
+    }
+
+    /// Set whether the parsers of the group given by `#[tracable_parser(group = "...")]` are displayed.
+    ///
+    /// All groups are enabled by default. The parsers of disabled groups are still counted like `exclude`.
+    pub fn enable_group(self, name: &str, x: bool) -> Self {
+        self.set_options(|o| {
+            o.disabled_groups.retain(|y| y != name);
+            if !x {
+                o.disabled_groups.push(String::from(name));
+            }
+        })
+    }
+
+    /// Set the path of NDJSON output.
+    ///
+    /// All trace events are written to the file as JSON lines in addition to the text output,
+    /// regardless of the text output settings like `forward` or `include`.
+    pub fn ndjson<P: AsRef<std::path::Path>>(self, path: P) -> Self {
+        let path = path.as_ref().to_path_buf();
+        self.set_options(|o| o.ndjson = Some(path))
+    }
+
+    /// Set the filter of NDJSON output. The events which the filter returns `false` are not written.
+    pub fn ndjson_filter(self, x: fn(&TraceEvent) -> bool) -> Self {
+        self.set_options(|o| o.ndjson_filter = Some(Hook(x)))
+    }
+
+    /// Set the hook called with the event whenever a parser is entered.
+    ///
+    /// The hook is called even if the forward trace is disabled,
+    /// so the events can be fed into metrics instead of being displayed.
+    ///
+    /// ```
+    /// # use nom_tracable::{TraceEvent, TracableInfo};
+    /// fn count(x: &TraceEvent) {
+    ///     println!("{} is called", x.parser);
+    /// }
+    ///
+    /// let info = TracableInfo::new().forward(false).backward(false).on_forward(count);
+    /// ```
+    pub fn on_forward(self, x: fn(&TraceEvent)) -> Self {
+        self.set_options(|o| o.on_forward = Some(Hook(x)))
+    }
+
+    /// Set the hook called with the `Ok`/`Err` event whenever a parser returns.
+    ///
+    /// The hook is called even if the backward trace is disabled.
+    pub fn on_backward(self, x: fn(&TraceEvent)) -> Self {
+        self.set_options(|o| o.on_backward = Some(Hook(x)))
+    }
+
+    /// Set whether only failed parsers and their ancestors are displayed.
+    pub fn failures_only(self, x: bool) -> Self {
+        self.set_options(|o| o.failures_only = x)
+    }
+
+    /// Set the offset range of displayed trace.
+    ///
+    /// A parser is displayed only if the input offset at the entry is in the range.
+    pub fn trace_range(self, x: std::ops::Range<usize>) -> Self {
+        self.set_options(|o| o.trace_range = Some(x))
+    }
+
+    /// Add a watchpoint on the fragment content.
+    ///
+    /// A marker line is displayed whenever a parser is entered with a fragment starting with `x`.
+    pub fn watch(self, x: &str) -> Self {
+        self.set_options(|o| o.watches.push(String::from(x)))
+    }
+
+    /// Add a watchpoint by the predicate on the fragment bytes.
+    pub fn watch_with(self, x: fn(&[u8]) -> bool) -> Self {
+        self.set_options(|o| o.watch_predicates.push(Hook(x)))
+    }
+
+    /// Add a breakpoint on the parser.
+    ///
+    /// The parse is paused whenever the parser is entered, and the debugger commands are read
+    /// from stdin or the source by `set_debugger_input`: step into/over, continue,
+    /// print the remaining input, and print the parser stack.
+    pub fn breakpoint(self, name: &str) -> Self {
+        self.set_options(|o| o.breakpoints.push(String::from(name)))
+    }
+
+    /// Set whether the parse is paused by the debugger when a watchpoint hits.
+    pub fn break_on_watch(self, x: bool) -> Self {
+        self.set_options(|o| o.break_on_watch = x)
+    }
+
+    /// Set the formatter of the fragment column instead of `FragmentDisplay`.
+    ///
+    /// The formatter is called with the fragment bytes and `fragment_width`,
+    /// so the column can show decoded values or redact secrets.
+    pub fn fragment_format(self, x: fn(&[u8], usize) -> String) -> Self {
+        self.set_options(|o| o.fragment_format = Some(Hook(x)))
+    }
+
+    /// Set the formatter of the forward/backward/custom trace lines instead of the default layout.
+    ///
+    /// The formatter is called with the fields of each displayed line, so the columns can be dropped or reordered.
+    /// The header line is not displayed, and color is disabled.
+    ///
+    /// ```
+    /// # use nom_tracable::{TraceLine, TracableInfo};
+    /// fn offset_first(x: &TraceLine) -> String {
+    ///     format!("{:>6} {}{} {}", x.offset, "  ".repeat(x.depth), x.parser, x.suffix)
+    /// }
+    ///
+    /// let info = TracableInfo::new().line_format(offset_first);
+    /// ```
+    pub fn line_format(self, x: fn(&TraceLine) -> String) -> Self {
+        self.set_options(|o| o.line_format = Some(Hook(x)))
+    }
+
+    /// Set the hook which makes the summary line of the folded parser.
+    ///
+    /// The default summary is `folded N nested calls`.
+    pub fn fold_summary(self, name: &str, x: fn(&FoldSummary) -> String) -> Self {
+        self.set_options(|o| {
+            o.fold_summaries.retain(|y| y.0 != name);
+            o.fold_summaries.push((String::from(name), Hook(x)));
+        })
+    }
+
+    /// Set the sampling interval of root parses.
+    ///
+    /// Only every `x`th root parse of the thread is displayed. This overrides `set_sampling`.
+    pub fn sampling(self, x: usize) -> Self {
+        self.set_options(|o| o.sampling = Some(x))
+    }
+
+    /// Set the capacity of the ring buffer of trace lines.
+    ///
+    /// The last `x` lines are buffered, and displayed only if the root parser failed.
+    pub fn ring_buffer(self, x: usize) -> Self {
+        self.set_options(|o| o.ring_buffer = Some(x))
+    }
+
+    /// Enable the adaptive detail escalation.
+    ///
+    /// The input is divided into regions of `region` bytes. Trace lines show only the offset,
+    /// but a region which caused more than `threshold` failures gets elevated detail:
+    /// wider fragments, error kinds and durations.
+    pub fn escalation(self, threshold: usize, region: usize) -> Self {
+        self.set_options(|o| o.escalation = Some((threshold, region.max(1))))
+    }
+
+    /// Set the destination of trace lines.
+    ///
+    /// The default is `TraceOutput::Stdout`, or `TraceOutput::Stderr` if `stderr` feature is enabled.
+    /// Histograms and reports after the parse are written to the destination of the last root parse.
+    pub fn output(self, x: TraceOutput) -> Self {
+        self.set_options(|o| o.output = x)
+    }
+
+    /// Append trace lines to the file instead of stdout/stderr.
+    ///
+    /// The file is opened at the first root parse, and kept open while the following root parses
+    /// specify the same path. The output is buffered and flushed at the end of each root parse.
+    /// If the file can't be opened, the error is reported and `output` is used instead.
+    /// If `log` feature is enabled, trace lines are emitted through `log` crate, and only the reports are written to the file.
+    pub fn output_file<P: AsRef<std::path::Path>>(self, path: P) -> Self {
+        let path = path.as_ref().to_path_buf();
+        self.set_options(|o| o.output_file = Some(path))
+    }
+
+    /// Set when trace lines are written to the output.
+    ///
+    /// Each line is written by a single write at least, so it is not interleaved with other output.
+    pub fn flush(self, x: TraceFlush) -> Self {
+        self.set_options(|o| o.flush = x)
+    }
+
+    /// Set whether each trace line is tagged with the thread and the parse session like `[main#1]`.
+    ///
+    /// The thread is the thread name or ID, and the session is the count of root parses in the thread.
+    /// This is useful to distinguish traces of parallel parses.
+    pub fn thread_tag(self, x: bool) -> Self {
+        self.set_options(|o| o.thread_tag = x)
+    }
+
+    /// Set whether each trace line is tagged with the ID of the root parse like `[#42]`.
+    ///
+    /// The ID is incremented at each root parse in the process, so it is unique across threads.
+    /// With `thread_tag`, the ID replaces the session count of the thread like `[main#42]`.
+    pub fn session_tag(self, x: bool) -> Self {
+        self.set_options(|o| o.session_tag = x)
+    }
+
+    /// Set the ID of the root parse shown like `session_tag`, for example the name of the input file.
+    ///
+    /// The ID given here is kept by `snapshot` because it is deterministic.
+    /// The ID is freed with the inputs which have it, so a batch can give an ID to each input.
+    pub fn session_id(mut self, x: &str) -> Self {
+        self.session_id = Some(std::sync::Arc::from(x));
+        self
+    }
+
+    /// Set the width of the parsed value shown by `#[tracable_parser(show_output)]`.
+    ///
+    /// The default is 40. Longer values are truncated.
+    pub fn output_width(self, x: usize) -> Self {
+        self.set_options(|o| o.output_width = x)
+    }
+
+    /// Set whether each parser name is colored by `ColorScheme::parsers`.
+    ///
+    /// The color is chosen by the hash of the name, so the same parser has the same color in all lines.
+    pub fn parser_colors(self, x: bool) -> Self {
+        self.set_options(|o| o.parser_colors = x)
+    }
+
+    /// Set the style of the location column of trace lines and the header.
+    ///
+    /// The line and column are available if the input is `LocatedSpan`.
+    pub fn location_style(self, x: LocationStyle) -> Self {
+        self.set_options(|o| o.location_style = x)
+    }
+
+    /// Set whether control characters of fragments are escaped like `\n`.
+    ///
+    /// By default, only the first line of the fragment is shown.
+    /// If enabled, the fragment over lines is shown in a trace line with the escaped characters.
+    pub fn escape_control(self, x: bool) -> Self {
+        self.set_options(|o| o.escape_control = x)
+    }
+
+    /// Set whether the backward trace on success shows the consumed bytes like `consumed 3`.
+    ///
+    /// Parsers which succeed without consuming are highlighted by `ColorScheme::warning`.
+    pub fn show_consumed(self, x: bool) -> Self {
+        self.set_options(|o| o.show_consumed = x)
+    }
+
+    /// Set whether the backward trace on success shows the text matched by the parser
+    /// instead of the remaining input.
+    ///
+    /// The text is decoded as UTF-8, or shown as bytes if it is not valid.
+    pub fn show_matched(self, x: bool) -> Self {
+        self.set_options(|o| o.show_matched = x)
+    }
+
+    /// Set whether the nesting is indented by box-drawing guides like `│ ├─ └─` instead of spaces.
+    pub fn tree_style(self, x: bool) -> Self {
+        self.set_options(|o| o.tree_style = x)
+    }
+
+    /// Set whether consecutive sibling subtrees of the same shape are collapsed.
+    ///
+    /// The shape is the sequence of the parser names and the results, regardless of the input.
+    /// The first subtree is displayed, and the following same subtrees are summarized like `… repeated 412×`.
+    /// The subtrees are buffered until they complete to be compared.
+    pub fn collapse_repeats(self, x: bool) -> Self {
+        self.set_options(|o| o.collapse_repeats = x)
+    }
+
+    /// Set whether the trace is rendered in the deterministic form for snapshot tests.
+    ///
+    /// Colors, elapsed times, thread tags and the IDs by `session_tag` are omitted,
+    /// so the same parse renders the same trace at each run.
+    /// The forward/backward counts are reset at each root parse, so they are stable too.
+    /// The column widths are fixed by `count_width`, `parser_width` and `fragment_width`.
+    pub fn snapshot(self, x: bool) -> Self {
+        self.set_options(|o| o.snapshot = x)
+    }
+
+    /// Set whether the summary block is displayed at the end of each root parse.
+    ///
+    /// The summary has the total forward/backward traces, the failed parser calls, the max depth,
+    /// and the most called and the slowest parsers. The calls are counted without `trace-stats` feature too.
+    pub fn summary(self, x: bool) -> Self {
+        self.set_options(|o| o.summary = x)
+    }
+
+    /// Get the indentation of a line at `depth`.
+    ///
+    /// `guide` is the guide of the innermost level in `tree_style`.
+    fn indent(&self, depth: usize, guide: &str) -> String {
+        if !self.options.tree_style {
+            " ".repeat(depth)
+        } else if depth == 0 {
+            String::new()
+        } else {
+            format!("{}{}", TREE_INNER.repeat(depth - 1), guide)
+        }
+    }
+
+    fn detail(&self, offset: usize) -> Detail {
+        match self.options.escalation {
+            Some((threshold, region)) => {
+                let failures = crate::TRACABLE_STORAGE.with(|storage| {
+                    let storage = storage.borrow();
+                    storage
+                        .region_failures
+                        .get(&(offset / region))
+                        .copied()
+                        .unwrap_or(0)
+                });
+                if failures > threshold {
+                    Detail::Escalated
+                } else {
+                    Detail::Minimal
+                }
+            }
+            None => Detail::Normal,
+        }
+    }
+
+    fn out_of_range(&self, offset: usize) -> bool {
+        match &self.options.trace_range {
+            Some(x) => !x.contains(&offset),
+            None => false,
+        }
+    }
+
+    /// Whether color is enabled. Color is always disabled by `snapshot` and in `with_captured_trace`.
+    fn color_enabled(&self) -> bool {
+        cfg!(feature = "trace-color")
+            && self.color
+            && !self.options.snapshot
+            && self.options.line_format.is_none()
+            && !capturing_output()
+    }
+
+    /// Whether the events are captured. This requires `trace-capture` feature.
+    fn capturing(&self) -> bool {
+        cfg!(feature = "trace-capture") && self.options.capture
+    }
+
+    /// Whether the calls are counted. The summary counts them without `trace-stats`.
+    fn counted(&self) -> bool {
+        STATS || self.options.summary
+    }
+
+    /// Whether elapsed times are displayed.
+    fn timing_shown(&self) -> bool {
+        self.options.timing && !self.options.snapshot
+    }
+
+    /// Get the colors. All colors are empty if `color` is disabled.
+    fn colors(&self) -> ColorScheme {
+        if self.color_enabled() {
+            self.options.color_scheme
+        } else {
+            ColorScheme::NONE
+        }
+    }
+
+    fn filtered(&self, x: &str) -> bool {
+        let included = match &self.options.include {
+            Some(include) => include.iter().any(|y| y == x),
+            None => true,
+        };
+        !included || self.options.exclude.iter().any(|y| y == x) || self.group_disabled(x)
+    }
+
+    fn group_disabled(&self, x: &str) -> bool {
+        if self.options.disabled_groups.is_empty() {
+            return false;
+        }
+        crate::TRACABLE_STORAGE.with(|storage| match storage.borrow().groups.get(x) {
+            Some(group) => self.options.disabled_groups.iter().any(|y| y == group),
+            None => false,
+        })
+    }
+
+    fn folded(&self, x: &str) -> bool {
         let index =
             crate::TRACABLE_STORAGE.with(|storage| storage.borrow_mut().get_parser_index(x));
 
@@ -200,7 +1046,7 @@ impl TracableInfo {
     }
 }
 
-#[cfg(not(feature = "trace"))]
+#[cfg(not(feature = "trace-core"))]
 impl TracableInfo {
     pub fn new() -> Self {
         TracableInfo::default()
@@ -222,6 +1068,10 @@ impl TracableInfo {
         self
     }
 
+    pub fn color_scheme(self, _x: ColorScheme) -> Self {
+        self
+    }
+
     pub fn count_width(self, _x: usize) -> Self {
         self
     }
@@ -237,165 +1087,2192 @@ impl TracableInfo {
     pub fn fold(self, _x: &str) -> Self {
         self
     }
-}
 
-impl HasTracableInfo for TracableInfo {
-    fn get_tracable_info(&self) -> TracableInfo {
-        *self
+    pub fn include(self, _x: &[&str]) -> Self {
+        self
     }
 
-    fn set_tracable_info(self, info: TracableInfo) -> Self {
-        info
+    pub fn exclude(self, _x: &[&str]) -> Self {
+        self
     }
-}
 
-#[cfg(feature = "trace")]
-impl<T, U: HasTracableInfo> HasTracableInfo for nom_locate::LocatedSpan<T, U> {
-    fn get_tracable_info(&self) -> TracableInfo {
-        self.extra.get_tracable_info()
+    pub fn enable_group(self, _name: &str, _x: bool) -> Self {
+        self
     }
 
-    fn set_tracable_info(mut self, info: TracableInfo) -> Self {
-        self.extra = self.extra.set_tracable_info(info);
+    #[cfg(feature = "std")]
+    pub fn ndjson<P: AsRef<std::path::Path>>(self, _path: P) -> Self {
         self
     }
-}
 
-#[cfg(feature = "trace")]
-impl<T: FragmentDisplay + nom::AsBytes, U: HasTracableInfo> Tracable
-    for nom_locate::LocatedSpan<T, U>
-{
-    fn inc_depth(self) -> Self {
-        let info = self.get_tracable_info();
-        let info = info.depth(info.depth + 1);
-        self.set_tracable_info(info)
+    #[cfg(feature = "std")]
+    pub fn ndjson_filter(self, _x: fn(&TraceEvent) -> bool) -> Self {
+        self
     }
 
-    fn dec_depth(self) -> Self {
-        let info = self.get_tracable_info();
-        let info = info.depth(info.depth - 1);
-        self.set_tracable_info(info)
+    #[cfg(feature = "std")]
+    pub fn on_forward(self, _x: fn(&TraceEvent)) -> Self {
+        self
     }
 
-    fn format(&self) -> String {
-        let info = self.get_tracable_info();
-        let fragment = self.fragment().display(info.fragment_width);
-        format!("{:<8} : {}", self.location_offset(), fragment)
+    #[cfg(feature = "std")]
+    pub fn on_backward(self, _x: fn(&TraceEvent)) -> Self {
+        self
     }
 
-    fn header(&self) -> String {
-        format!("{:<8} : {}", "offset", "fragment")
+    pub fn failures_only(self, _x: bool) -> Self {
+        self
     }
-}
 
-#[derive(Debug, Default)]
-struct TracableStorage {
-    forward_count: usize,
-    backward_count: usize,
-    parser_indexes: HashMap<String, usize>,
-    parser_index_next: usize,
-    histogram: HashMap<String, usize>,
-    cumulative_histogram: HashMap<String, usize>,
-    cumulative_working: HashMap<(String, usize), usize>,
-}
+    pub fn trace_range(self, _x: core::ops::Range<usize>) -> Self {
+        self
+    }
 
-#[allow(dead_code)]
-impl TracableStorage {
-    fn new() -> Self {
-        TracableStorage::default()
+    pub fn watch(self, _x: &str) -> Self {
+        self
     }
 
-    fn init(&mut self) {
-        self.forward_count = 0;
-        self.backward_count = 0;
-        self.histogram.clear();
-        self.cumulative_histogram.clear();
-        self.cumulative_working.clear();
+    pub fn watch_with(self, _x: fn(&[u8]) -> bool) -> Self {
+        self
     }
 
-    fn get_forward_count(&self) -> usize {
-        self.forward_count
+    pub fn breakpoint(self, _name: &str) -> Self {
+        self
     }
 
-    fn get_backward_count(&self) -> usize {
-        self.backward_count
+    pub fn break_on_watch(self, _x: bool) -> Self {
+        self
     }
 
-    fn inc_forward_count(&mut self) {
-        self.forward_count += 1
+    #[cfg(feature = "std")]
+    pub fn fragment_format(self, _x: fn(&[u8], usize) -> String) -> Self {
+        self
     }
 
-    fn inc_backward_count(&mut self) {
-        self.backward_count += 1
+    #[cfg(feature = "std")]
+    pub fn line_format(self, _x: fn(&TraceLine) -> String) -> Self {
+        self
     }
 
-    fn inc_histogram(&mut self, key: &str) {
-        let next = if let Some(x) = self.histogram.get(key) {
-            x + 1
-        } else {
-            1
-        };
-        self.histogram.insert(String::from(key), next);
+    #[cfg(feature = "std")]
+    pub fn fold_summary(self, _name: &str, _x: fn(&FoldSummary) -> String) -> Self {
+        self
     }
 
-    fn inc_cumulative_histogram(&mut self, key: &str, cnt: usize) {
-        let next = if let Some(x) = self.cumulative_histogram.get(key) {
+    pub fn sampling(self, _x: usize) -> Self {
+        self
+    }
+
+    pub fn ring_buffer(self, _x: usize) -> Self {
+        self
+    }
+
+    pub fn escalation(self, _threshold: usize, _region: usize) -> Self {
+        self
+    }
+
+    pub fn output(self, _x: TraceOutput) -> Self {
+        self
+    }
+
+    #[cfg(feature = "std")]
+    pub fn output_file<P: AsRef<std::path::Path>>(self, _path: P) -> Self {
+        self
+    }
+
+    pub fn flush(self, _x: TraceFlush) -> Self {
+        self
+    }
+
+    pub fn thread_tag(self, _x: bool) -> Self {
+        self
+    }
+
+    pub fn session_tag(self, _x: bool) -> Self {
+        self
+    }
+
+    pub fn session_id(self, _x: &str) -> Self {
+        self
+    }
+
+    pub fn output_width(self, _x: usize) -> Self {
+        self
+    }
+
+    pub fn parser_colors(self, _x: bool) -> Self {
+        self
+    }
+
+    pub fn location_style(self, _x: LocationStyle) -> Self {
+        self
+    }
+
+    pub fn escape_control(self, _x: bool) -> Self {
+        self
+    }
+
+    pub fn show_consumed(self, _x: bool) -> Self {
+        self
+    }
+
+    pub fn show_matched(self, _x: bool) -> Self {
+        self
+    }
+
+    pub fn tree_style(self, _x: bool) -> Self {
+        self
+    }
+
+    pub fn collapse_repeats(self, _x: bool) -> Self {
+        self
+    }
+
+    pub fn snapshot(self, _x: bool) -> Self {
+        self
+    }
+
+    pub fn summary(self, _x: bool) -> Self {
+        self
+    }
+
+    pub fn capture(self, _x: bool) -> Self {
+        self
+    }
+
+    pub fn time_budget(self, _x: Duration) -> Self {
+        self
+    }
+
+    pub fn timing(self, _x: bool) -> Self {
+        self
+    }
+
+    pub fn watchdog(self, _x: usize) -> Self {
+        self
+    }
+
+    pub fn max_depth(self, _x: usize) -> Self {
+        self
+    }
+
+    pub fn depth_limit(self, _x: usize) -> Self {
+        self
+    }
+
+    pub fn failure_snippet(self, _context: usize) -> Self {
+        self
+    }
+
+    pub fn invocation_budget(self, _name: &str, _x: usize) -> Self {
+        self
+    }
+
+    pub fn bookmark(self, _offset: usize, _name: &str) -> Self {
+        self
+    }
+}
+
+impl HasTracableInfo for TracableInfo {
+    fn get_tracable_info(&self) -> TracableInfo {
+        self.clone()
+    }
+
+    fn set_tracable_info(self, info: TracableInfo) -> Self {
+        info
+    }
+}
+
+#[cfg(any(feature = "trace-core", feature = "bare"))]
+impl<T, U: HasTracableInfo> HasTracableInfo for nom_locate::LocatedSpan<T, U> {
+    fn get_tracable_info(&self) -> TracableInfo {
+        self.extra.get_tracable_info()
+    }
+
+    fn set_tracable_info(mut self, info: TracableInfo) -> Self {
+        self.extra = self.extra.set_tracable_info(info);
+        self
+    }
+}
+
+#[cfg(feature = "trace-core")]
+impl<T: FragmentDisplay + nom::AsBytes, U: HasTracableInfo> Tracable
+    for nom_locate::LocatedSpan<T, U>
+{
+    fn inc_depth(self) -> Self {
+        let mut info = self.get_tracable_info();
+        info.depth += 1;
+        self.set_tracable_info(info)
+    }
+
+    fn dec_depth(self) -> Self {
+        let mut info = self.get_tracable_info();
+        info.depth -= 1;
+        self.set_tracable_info(info)
+    }
+
+    fn format(&self) -> String {
+        let line_col = (self.location_line(), self.get_utf8_column());
+        format_fragment(
+            self.location_offset(),
+            Some(line_col),
+            self.fragment(),
+            &self.get_tracable_info(),
+        )
+    }
+
+    fn header(&self) -> String {
+        format_header(true, &self.get_tracable_info())
+    }
+
+    fn offset(&self) -> usize {
+        self.location_offset()
+    }
+
+    fn fragment_bytes(&self) -> &[u8] {
+        self.fragment().as_bytes()
+    }
+}
+
+/// Format the location and the fragment columns of the input.
+///
+/// `line_col` is the line and the column if the input has them.
+#[cfg(feature = "trace-core")]
+fn format_fragment<T: FragmentDisplay + nom::AsBytes>(
+    offset: usize,
+    line_col: Option<(u32, usize)>,
+    fragment: &T,
+    info: &TracableInfo,
+) -> String {
+    let fragment = match info.options.fragment_format {
+        Some(Hook(f)) => f(fragment.as_bytes(), info.fragment_width),
+        None if info.options.escape_control => fragment.display_escaped(info.fragment_width),
+        None => fragment.display(info.fragment_width),
+    };
+    let line_col = line_col.map(|(line, col)| format!("{}:{}", line, col));
+    let location = match (info.options.location_style, line_col) {
+        (LocationStyle::LineCol, Some(x)) => format!("{:<10}", x),
+        (LocationStyle::Both, Some(x)) => format!("{:<8} {:<10}", offset, x),
+        _ => format!("{:<8}", offset),
+    };
+    format!("{} : {}", location, fragment)
+}
+
+/// Format the bytes matched by the parser for `TracableInfo::show_matched`.
+#[cfg(feature = "trace-core")]
+fn format_matched(bytes: &[u8], info: &TracableInfo) -> String {
+    // The head may be cut in the middle of a character
+    let text = match std::str::from_utf8(bytes) {
+        Ok(x) => Some(x),
+        Err(e) if e.error_len().is_none() => std::str::from_utf8(&bytes[..e.valid_up_to()]).ok(),
+        Err(_) => None,
+    };
+    match (info.options.fragment_format, text) {
+        (Some(Hook(f)), _) => f(bytes, info.fragment_width),
+        (None, Some(x)) if info.options.escape_control => x.display_escaped(info.fragment_width),
+        (None, Some(x)) => x.display(info.fragment_width),
+        (None, None) => bytes.display(info.fragment_width),
+    }
+}
+
+/// Format the header of the location and the fragment columns.
+#[cfg(feature = "trace-core")]
+fn format_header(line_col: bool, info: &TracableInfo) -> String {
+    let location = match (info.options.location_style, line_col) {
+        (LocationStyle::LineCol, true) => format!("{:<10}", "line:col"),
+        (LocationStyle::Both, true) => format!("{:<8} {:<10}", "offset", "line:col"),
+        _ => format!("{:<8}", "offset"),
+    };
+    format!("{} : {}", location, "fragment")
+}
+
+/// Struct given to the hook by `TracableInfo::fold_summary`.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg(feature = "std")]
+pub struct FoldSummary<'a> {
+    pub parser: &'a str,
+    /// Offset of the input at the entry.
+    pub offset: usize,
+    /// Consumed length. This is zero if the parser failed.
+    pub consumed: usize,
+    /// The number of parser calls in the folded subtree.
+    pub calls: usize,
+    pub success: bool,
+}
+
+/// Struct given to the formatter by `TracableInfo::line_format`.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg(feature = "std")]
+pub struct TraceLine<'a> {
+    /// `Forward`, `Ok`, `Err` or `Custom`.
+    pub kind: TraceEventKind,
+    /// The forward count of a forward line.
+    pub forward: Option<usize>,
+    /// The backward count of a backward line.
+    pub backward: Option<usize>,
+    pub depth: usize,
+    pub parser: &'a str,
+    /// Offset of the input at the entry, or the remaining input of a successful parser.
+    pub offset: usize,
+    /// The fragment column. This is empty for a failure, and the message for a custom trace.
+    pub fragment: &'a str,
+    /// The annotations appended to the default layout like ` : consumed 1`, with the leading separators.
+    pub suffix: &'a str,
+    /// Elapsed time of a backward line.
+    pub elapsed: Option<Duration>,
+}
+
+/// Kind of captured trace event.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg(feature = "std")]
+pub enum TraceEventKind {
+    /// The parser is entered.
+    Forward,
+    /// The parser returned `Ok`.
+    Ok,
+    /// The parser returned `Err`.
+    Err,
+    /// A message by `custom_trace`.
+    Custom,
+    /// A bookmark by `TracableInfo::bookmark`.
+    Bookmark,
+}
+
+/// Struct to have a captured trace event.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg(feature = "std")]
+pub struct TraceEvent {
+    pub kind: TraceEventKind,
+    pub depth: usize,
+    /// The parser name, or the bookmark name.
+    pub parser: String,
+    /// Offset of the input at the event.
+    /// `Err` has the offset at the entry of the parser.
+    pub offset: usize,
+    /// Input formatted by `Tracable::format`, or the message of custom trace.
+    pub fragment: String,
+}
+
+/// Struct to have the captured trace events of a parse.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg(feature = "std")]
+pub struct Trace {
+    /// Header formatted by `Tracable::header`.
+    pub header: String,
+    /// The whole input of the root parser.
+    pub input: Vec<u8>,
+    pub events: Vec<TraceEvent>,
+    /// Traces of nested contexts by `nested_context`.
+    pub includes: Vec<IncludedTrace>,
+}
+
+/// Struct to have the captured trace of a nested context.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg(feature = "std")]
+pub struct IncludedTrace {
+    /// The source name given to `nested_context`.
+    pub source: String,
+    /// The number of events of the outer trace before the nested context.
+    pub position: usize,
+    pub trace: Trace,
+}
+
+#[cfg(feature = "std")]
+impl Trace {
+    /// Get the iterator of the captured events.
+    pub fn iter(&self) -> std::slice::Iter<'_, TraceEvent> {
+        self.events.iter()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> IntoIterator for &'a Trace {
+    type Item = &'a TraceEvent;
+    type IntoIter = std::slice::Iter<'a, TraceEvent>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.events.iter()
+    }
+}
+
+#[cfg(feature = "std")]
+impl IntoIterator for Trace {
+    type Item = TraceEvent;
+    type IntoIter = std::vec::IntoIter<TraceEvent>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.events.into_iter()
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug)]
+#[cfg(feature = "std")]
+struct Frame {
+    name: String,
+    offset: usize,
+    start: Instant,
+    /// Index of the forward event in the captured trace.
+    event: Option<usize>,
+    info: TracableInfo,
+    /// Head of the input bytes by `TracableInfo::show_matched`.
+    head: Vec<u8>,
+    // The span of `tracing` crate is exited by drop
+    #[cfg(feature = "tracing")]
+    span: TracingSpan,
+}
+
+/// Span of `tracing` crate entered by a frame.
+///
+/// This is not `EnteredSpan` because the frames of an async parser are moved between threads.
+/// `TaskContext` exits the spans before the thread is left, and enters them again at the next poll.
+#[cfg(feature = "tracing")]
+#[derive(Debug)]
+struct TracingSpan {
+    span: tracing::Span,
+    entered: core::cell::Cell<bool>,
+}
+
+#[cfg(feature = "tracing")]
+impl TracingSpan {
+    fn new(span: tracing::Span) -> Self {
+        let ret = TracingSpan {
+            span,
+            entered: core::cell::Cell::new(false),
+        };
+        ret.enter();
+        ret
+    }
+
+    fn enter(&self) {
+        if !self.entered.replace(true) {
+            self.span
+                .with_subscriber(|(id, dispatch)| dispatch.enter(id));
+        }
+    }
+
+    fn exit(&self) {
+        if self.entered.replace(false) {
+            self.span
+                .with_subscriber(|(id, dispatch)| dispatch.exit(id));
+        }
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Drop for TracingSpan {
+    fn drop(&mut self) {
+        self.exit();
+    }
+}
+
+/// Detail of trace lines by `TracableInfo::escalation`.
+#[cfg(feature = "trace-core")]
+#[derive(Clone, Copy, PartialEq)]
+enum Detail {
+    Normal,
+    Minimal,
+    Escalated,
+}
+
+/// Whether the statistics like `histogram` are collected. This requires `trace-stats` feature.
+#[cfg(feature = "std")]
+const STATS: bool = cfg!(feature = "trace-stats");
+
+/// Guides of `TracableInfo::tree_style` for the forward trace, the backward trace and the others.
+#[cfg(feature = "trace-core")]
+const TREE_ENTER: &str = "├─";
+#[cfg(feature = "trace-core")]
+const TREE_EXIT: &str = "└─";
+#[cfg(feature = "trace-core")]
+const TREE_INNER: &str = "│ ";
+
+/// The fragment width of escalated lines is multiplied by this.
+#[cfg(feature = "trace-core")]
+const ESCALATED_FRAGMENT_FACTOR: usize = 4;
+
+/// Format the input with the detail by `TracableInfo::escalation`.
+#[cfg(feature = "trace-core")]
+fn format_detail<T: Tracable>(input: T, detail: Detail) -> (T, String) {
+    match detail {
+        Detail::Normal => {
+            let fragment = input.format();
+            (input, fragment)
+        }
+        Detail::Minimal => {
+            let fragment = format!("{}", input.offset());
+            (input, fragment)
+        }
+        Detail::Escalated => {
+            let info = input.get_tracable_info();
+            let width = info
+                .fragment_width
+                .saturating_mul(ESCALATED_FRAGMENT_FACTOR);
+            let input = input.set_tracable_info(info.clone().fragment_width(width));
+            let fragment = input.format();
+            (input.set_tracable_info(info), fragment)
+        }
+    }
+}
+
+/// Bounded buffer of trace lines by `TracableInfo::ring_buffer`.
+#[allow(dead_code)]
+#[derive(Debug)]
+#[cfg(feature = "std")]
+struct RingBuffer {
+    capacity: usize,
+    lines: std::collections::VecDeque<(String, OutputLevel, String)>,
+    dropped: usize,
+}
+
+#[allow(dead_code)]
+#[cfg(feature = "std")]
+impl RingBuffer {
+    fn new(capacity: usize) -> Self {
+        RingBuffer {
+            capacity,
+            lines: std::collections::VecDeque::new(),
+            dropped: 0,
+        }
+    }
+
+    fn push(&mut self, target: &str, level: OutputLevel, line: String) {
+        if self.capacity == 0 {
+            self.dropped += 1;
+            return;
+        }
+        if self.lines.len() == self.capacity {
+            self.lines.pop_front();
+            self.dropped += 1;
+        }
+        self.lines.push_back((String::from(target), level, line));
+    }
+}
+
+/// Lines buffered by `Repeats`.
+#[cfg(feature = "std")]
+type BufferedLines = Vec<(String, OutputLevel, String)>;
+
+/// Collapser of repeated sibling subtrees by `TracableInfo::collapse_repeats`.
+///
+/// Each level has the child subtrees of a parser at the depth.
+/// The first child is displayed directly, and the following children are buffered
+/// until they complete to be compared with the previous one.
+#[allow(dead_code)]
+#[derive(Debug, Default)]
+#[cfg(feature = "std")]
+struct Repeats {
+    levels: Vec<RepeatLevel>,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Default)]
+#[cfg(feature = "std")]
+struct RepeatLevel {
+    /// Shape of the last displayed child.
+    last: Option<Vec<String>>,
+    /// The number of children collapsed into `last`.
+    repeated: usize,
+    /// Shape of the child in progress.
+    shape: Vec<String>,
+    /// Lines of the child in progress if it is buffered.
+    lines: Option<BufferedLines>,
+}
+
+#[allow(dead_code)]
+#[cfg(feature = "std")]
+impl Repeats {
+    /// Begin a child subtree at `depth`.
+    fn enter(&mut self, depth: usize, name: &str) {
+        if self.levels.len() <= depth {
+            self.levels.resize_with(depth + 1, Default::default);
+        }
+        let level = &mut self.levels[depth];
+        level.shape.clear();
+        level.lines = level.last.as_ref().map(|_| Vec::new());
+        let entry = format!("-> {}", name);
+        for level in &mut self.levels[..=depth] {
+            level.shape.push(entry.clone());
+        }
+    }
+
+    /// Take the levels of the children of the parser at `depth` to flush them before the exit line.
+    fn close_children(&mut self, depth: usize) -> Option<usize> {
+        let repeated = self.levels.get(depth + 1).map(|x| x.repeated);
+        self.levels.truncate(depth + 1);
+        repeated.filter(|x| *x > 0)
+    }
+
+    /// Complete the child subtree at `depth`, and return the lines to be displayed
+    /// with the number of preceding repeats to be summarized.
+    fn exit(&mut self, depth: usize, entry: String) -> Option<(usize, BufferedLines)> {
+        for level in &mut self.levels[..=depth] {
+            level.shape.push(entry.clone());
+        }
+        let level = &mut self.levels[depth];
+        let shape = std::mem::take(&mut level.shape);
+        let lines = match level.lines.take() {
+            Some(x) => x,
+            None => {
+                level.last = Some(shape);
+                return None;
+            }
+        };
+        if level.last.as_ref() == Some(&shape) {
+            level.repeated += 1;
+            return None;
+        }
+        let repeated = std::mem::take(&mut level.repeated);
+        level.last = Some(shape);
+        Some((repeated, lines))
+    }
+
+    /// Buffer the line if a child in progress is buffered.
+    fn push(&mut self, target: &str, level: OutputLevel, line: &std::fmt::Arguments) -> bool {
+        match self.levels.iter_mut().rev().find_map(|x| x.lines.as_mut()) {
+            Some(lines) => {
+                lines.push((String::from(target), level, line.to_string()));
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Default)]
+#[cfg(feature = "std")]
+struct TracableStorage {
+    forward_count: usize,
+    backward_count: usize,
+    parser_indexes: HashMap<String, usize>,
+    parser_index_next: usize,
+    /// Groups of parsers by `#[tracable_parser(group = "...")]`.
+    groups: HashMap<String, &'static str>,
+    histogram: HashMap<String, usize>,
+    cumulative_histogram: HashMap<String, usize>,
+    cumulative_working: HashMap<(String, usize), usize>,
+    trace: Trace,
+    stack: Vec<Frame>,
+    start: Option<Instant>,
+    timed_out: bool,
+    abort_hook: Option<fn(&str)>,
+    /// The source of the debugger commands by `set_debugger_input`.
+    debugger_input: Option<fn() -> Option<String>>,
+    /// The depth where the debugger pauses next by stepping.
+    step: Option<usize>,
+    thread_default: Option<TracableInfo>,
+    timing: HashMap<String, (usize, Duration)>,
+    results: HashMap<String, (usize, usize)>,
+    /// Results by `#[tracable_parser(memoize)]`: the end offset and the output.
+    memo: HashMap<(String, usize), (usize, Box<dyn core::any::Any + Send>)>,
+    /// Whether the last result is taken from `memo`.
+    memo_hit: bool,
+    /// Calls and time spent in the failed invocations of each parser.
+    wasted: HashMap<String, (usize, Duration)>,
+    max_depth: usize,
+    max_depth_chain: Vec<String>,
+    visits: HashMap<(String, usize), usize>,
+    bookmark_next: usize,
+    left_recursions: Vec<String>,
+    cancellation_token: Option<CancellationToken>,
+    cancellation: Option<Cancellation>,
+    elided: usize,
+    ndjson: Option<std::io::BufWriter<std::fs::File>>,
+    pending: Vec<(usize, String, String)>,
+    sampling: Option<usize>,
+    root_count: usize,
+    sampled_out: bool,
+    ring: Option<RingBuffer>,
+    warnings: Vec<Warning>,
+    warning_subscriber: Option<fn(&Warning)>,
+    nested: usize,
+    /// The outer storage suspended by a root parse in the middle of the outer parse.
+    /// This is resumed at the end of the nested root parse.
+    outer: Option<Box<TracableStorage>>,
+    /// The input and the furthest failure by `TracableInfo::failure_snippet`.
+    #[cfg(feature = "trace-core")]
+    snippet: Option<snippet::FailureSnippet>,
+    region_failures: HashMap<usize, usize>,
+    suppressed: usize,
+    suppressed_calls: usize,
+    /// The parsers entered in the root parse which was not traced by `set_enabled`.
+    /// The children follow the root even if the switch is changed in the middle of the parse.
+    untraced: usize,
+    output: TraceOutput,
+    flush: TraceFlush,
+    lines: String,
+    /// Whether a root parse is running.
+    parsing: bool,
+    /// Tag of trace lines by `TracableInfo::thread_tag`, `session_tag` and `session_id`.
+    tag: Option<String>,
+    session: usize,
+    /// Whether this is the storage of an async task by `task_context`.
+    task: bool,
+    repeats: Option<Repeats>,
+    /// Calls and failures from the caller to the callee.
+    call_edges: HashMap<(String, String), (usize, usize)>,
+    /// Statistics over the root parses by `cumulative_stats`.
+    totals: cumulative::Totals,
+}
+
+#[allow(dead_code)]
+#[cfg(feature = "std")]
+impl TracableStorage {
+    fn new() -> Self {
+        TracableStorage::default()
+    }
+
+    /// Replace the storage by a new one for the nested context, and return the outer storage.
+    /// Thread settings are inherited.
+    fn suspend(&mut self) -> TracableStorage {
+        let inner = TracableStorage {
+            parser_indexes: self.parser_indexes.clone(),
+            parser_index_next: self.parser_index_next,
+            groups: self.groups.clone(),
+            abort_hook: self.abort_hook,
+            debugger_input: self.debugger_input,
+            thread_default: self.thread_default.clone(),
+            cancellation_token: self.cancellation_token.clone(),
+            sampling: self.sampling,
+            root_count: self.root_count,
+            sampled_out: self.sampled_out,
+            warning_subscriber: self.warning_subscriber,
+            output: self.output,
+            flush: self.flush,
+            tag: self.tag.clone(),
+            session: self.session,
+            ndjson: self.ndjson.take(),
+            totals: std::mem::take(&mut self.totals),
+            nested: self.nested + 1,
+            untraced: self.untraced,
+            ..Default::default()
+        };
+        std::mem::replace(self, inner)
+    }
+
+    /// Create the storage of an async task. Thread settings are inherited.
+    fn fork_task(&self) -> TracableStorage {
+        TracableStorage {
+            parser_indexes: self.parser_indexes.clone(),
+            parser_index_next: self.parser_index_next,
+            groups: self.groups.clone(),
+            abort_hook: self.abort_hook,
+            debugger_input: self.debugger_input,
+            thread_default: self.thread_default.clone(),
+            cancellation_token: self.cancellation_token.clone(),
+            sampling: self.sampling,
+            root_count: self.root_count,
+            warning_subscriber: self.warning_subscriber,
+            output: self.output,
+            flush: self.flush,
+            session: self.session,
+            totals: self.totals.clone(),
+            task: true,
+            untraced: self.untraced,
+            ..Default::default()
+        }
+    }
+
+    /// Restore the outer storage, and return the storage of the nested context.
+    fn resume(&mut self, outer: TracableStorage) -> TracableStorage {
+        let mut inner = std::mem::replace(self, outer);
+        self.parser_indexes = inner.parser_indexes.clone();
+        self.parser_index_next = inner.parser_index_next;
+        self.groups = inner.groups.clone();
+        self.ndjson = inner.ndjson.take();
+        self.totals = std::mem::take(&mut inner.totals);
+        self.session = inner.session;
+        inner
+    }
+
+    fn init(&mut self) {
+        self.forward_count = 0;
+        self.backward_count = 0;
+        self.histogram.clear();
+        self.cumulative_histogram.clear();
+        self.cumulative_working.clear();
+        self.trace = Trace::default();
+        self.stack.clear();
+        self.start = Some(Instant::now());
+        self.timed_out = false;
+        self.timing.clear();
+        self.results.clear();
+        self.wasted.clear();
+        self.memo.clear();
+        #[cfg(feature = "trace-core")]
+        {
+            self.snippet = None;
+        }
+        self.step = None;
+        self.max_depth = 0;
+        self.max_depth_chain.clear();
+        self.visits.clear();
+        self.bookmark_next = 0;
+        self.left_recursions.clear();
+        self.cancellation = None;
+        self.elided = 0;
+        // The NDJSON output is shared with the outer context
+        if self.nested == 0 {
+            self.ndjson = None;
+        }
+        self.pending.clear();
+        self.ring = None;
+        self.warnings.clear();
+        self.region_failures.clear();
+        self.call_edges.clear();
+    }
+
+    fn get_forward_count(&self) -> usize {
+        self.forward_count
+    }
+
+    fn get_backward_count(&self) -> usize {
+        self.backward_count
+    }
+
+    fn inc_forward_count(&mut self) {
+        self.forward_count += 1
+    }
+
+    fn inc_backward_count(&mut self) {
+        self.backward_count += 1
+    }
+
+    fn inc_histogram(&mut self, key: &str) {
+        let next = if let Some(x) = self.histogram.get(key) {
+            x + 1
+        } else {
+            1
+        };
+        self.histogram.insert(String::from(key), next);
+    }
+
+    fn inc_cumulative_histogram(&mut self, key: &str, cnt: usize) {
+        let next = if let Some(x) = self.cumulative_histogram.get(key) {
             x + cnt
         } else {
-            cnt
+            cnt
+        };
+        self.cumulative_histogram.insert(String::from(key), next);
+    }
+
+    fn inc_results(&mut self, key: &str, success: bool) {
+        let entry = self.results.entry(String::from(key)).or_default();
+        if success {
+            entry.0 += 1;
+        } else {
+            entry.1 += 1;
+        }
+    }
+
+    fn inc_visits(&mut self, key: &str, offset: usize) -> usize {
+        let entry = self.visits.entry((String::from(key), offset)).or_insert(0);
+        *entry += 1;
+        *entry
+    }
+
+    fn add_cumulative(&mut self, key: &str, depth: usize) {
+        self.cumulative_working
+            .insert((String::from(key), depth), 0);
+    }
+
+    fn inc_cumulative(&mut self) {
+        for val in self.cumulative_working.values_mut() {
+            *val += 1;
+        }
+    }
+
+    fn del_cumulative(&mut self, key: &str, depth: usize) {
+        self.cumulative_working.remove(&(key.to_string(), depth));
+    }
+
+    fn get_cumulative(&mut self, key: &str, depth: usize) -> Option<&usize> {
+        self.cumulative_working.get(&(key.to_string(), depth))
+    }
+
+    /// Enter the spans of `tracing` crate when the storage is installed to the thread.
+    fn enter_spans(&self) {
+        #[cfg(feature = "tracing")]
+        for x in &self.stack {
+            x.span.enter();
+        }
+    }
+
+    /// Exit the spans of `tracing` crate before the storage leaves the thread.
+    fn exit_spans(&self) {
+        #[cfg(feature = "tracing")]
+        for x in self.stack.iter().rev() {
+            x.span.exit();
+        }
+    }
+
+    fn push_event(&mut self, event: TraceEvent) {
+        self.trace.events.push(event);
+    }
+
+    fn push_frame(&mut self, name: &str, offset: usize, event: Option<usize>, info: TracableInfo) {
+        if let (true, Some(caller)) = (STATS, self.stack.last()) {
+            let key = (caller.name.clone(), String::from(name));
+            self.call_edges.entry(key).or_default().0 += 1;
+        }
+        self.stack.push(Frame {
+            name: String::from(name),
+            offset,
+            start: Instant::now(),
+            event,
+            info,
+            head: Vec::new(),
+            #[cfg(feature = "tracing")]
+            span: TracingSpan::new(tracing::trace_span!(
+                "parser",
+                parser = name,
+                depth = self.stack.len()
+            )),
+        });
+    }
+
+    fn update_max_depth(&mut self) {
+        if self.stack.len() > self.max_depth {
+            self.max_depth = self.stack.len();
+            self.max_depth_chain = self.stack.iter().map(|x| x.name.clone()).collect();
+        }
+    }
+
+    /// Count the root parse, and decide whether it is displayed.
+    fn sample(&mut self, sampling: Option<usize>) {
+        // Nested contexts follow the outer context
+        if self.nested > 0 {
+            return;
+        }
+        let interval = sampling.or(self.sampling).unwrap_or(1).max(1);
+        self.sampled_out = !self.root_count.is_multiple_of(interval);
+        self.root_count += 1;
+    }
+
+    /// Get the cycle if the last frame re-enters a parser on the stack at the same offset.
+    /// Each parser is reported once per parse.
+    fn left_recursion(&mut self) -> Option<String> {
+        let (last, rest) = self.stack.split_last()?;
+        let start = rest
+            .iter()
+            .position(|x| x.name == last.name && x.offset == last.offset)?;
+        if self.left_recursions.contains(&last.name) {
+            return None;
+        }
+        self.left_recursions.push(last.name.clone());
+        Some(
+            self.stack[start..]
+                .iter()
+                .map(|x| x.name.as_str())
+                .collect::<Vec<_>>()
+                .join(" -> "),
+        )
+    }
+
+    fn pop_frame(&mut self) -> Option<Frame> {
+        self.stack.pop()
+    }
+
+    fn add_timing(&mut self, key: &str, elapsed: Duration) {
+        let entry = self
+            .timing
+            .entry(String::from(key))
+            .or_insert((0, Duration::default()));
+        entry.0 += 1;
+        entry.1 += elapsed;
+    }
+
+    fn get_parser_index(&mut self, key: &str) -> usize {
+        if let Some(x) = self.parser_indexes.get(key) {
+            *x
+        } else {
+            let new_index = self.parser_index_next;
+            self.parser_index_next += 1;
+            self.parser_indexes.insert(String::from(key), new_index);
+            new_index
+        }
+    }
+}
+
+#[cfg(feature = "trace-core")]
+thread_local!(
+    static TRACABLE_STORAGE: core::cell::RefCell<crate::TracableStorage> = {
+        core::cell::RefCell::new(crate::TracableStorage::new())
+    }
+);
+
+/// Show histogram of parser call count.
+///
+/// The counts of success/failure of each parser are shown with the call count.
+///
+/// The statistics information to generate histogram is reset at each parser call.
+/// Therefore `histogram` should be called before next parser call.
+/// The information is thread independent because it is stored at thread local storage.
+///
+/// ```
+/// # use nom::character::complete::*;
+/// # use nom::IResult;
+/// # use nom_locate::LocatedSpan;
+/// # use nom_tracable::{histogram, tracable_parser, TracableInfo};
+/// #
+/// # type Span<'a> = LocatedSpan<&'a str, TracableInfo>;
+/// #
+/// # #[tracable_parser]
+/// # pub fn term(s: Span) -> IResult<Span, String> {
+/// #     let (s, x) = char('1')(s)?;
+/// #     Ok((s, x.to_string()))
+/// # }
+/// #
+/// let ret = term(LocatedSpan::new_extra("1", TracableInfo::new()));
+/// histogram(); // Show histogram of "1" parsing
+///
+/// let ret = term(LocatedSpan::new_extra("11", TracableInfo::new()));
+/// histogram(); // Show histogram of "11" parsing
+/// ```
+pub fn histogram() {
+    histogram_internal();
+}
+
+#[cfg(feature = "trace-core")]
+fn histogram_internal() {
+    crate::TRACABLE_STORAGE.with(|storage| {
+        let storage = storage.borrow();
+        show_histogram("histogram", &storage.histogram, Some(&storage.results));
+    });
+}
+
+#[cfg(not(feature = "trace-core"))]
+fn histogram_internal() {}
+
+/// Show cumulative histogram of parser call count.
+///
+/// The call count includes the counts of children parsers.
+///
+/// The statistics information to generate histogram is reset at each parser call.
+/// Therefore `cumulative_histogram` should be called before next parser call.
+/// The information is thread independent because it is stored at thread local storage.
+///
+/// ```
+/// # use nom::character::complete::*;
+/// # use nom::IResult;
+/// # use nom_locate::LocatedSpan;
+/// # use nom_tracable::{cumulative_histogram, tracable_parser, TracableInfo};
+/// #
+/// # type Span<'a> = LocatedSpan<&'a str, TracableInfo>;
+/// #
+/// # #[tracable_parser]
+/// # pub fn term(s: Span) -> IResult<Span, String> {
+/// #     let (s, x) = char('1')(s)?;
+/// #     Ok((s, x.to_string()))
+/// # }
+/// #
+/// let ret = term(LocatedSpan::new_extra("1", TracableInfo::new()));
+/// cumulative_histogram(); // Show cumulative histogram of "1" parsing
+///
+/// let ret = term(LocatedSpan::new_extra("11", TracableInfo::new()));
+/// cumulative_histogram(); // Show cumulative histogram of "11" parsing
+/// ```
+pub fn cumulative_histogram() {
+    cumulative_histogram_internal();
+}
+
+#[cfg(feature = "trace-core")]
+fn cumulative_histogram_internal() {
+    crate::TRACABLE_STORAGE.with(|storage| {
+        let storage = storage.borrow();
+        show_histogram("cumulative histogram", &storage.cumulative_histogram, None);
+    });
+}
+
+#[cfg(not(feature = "trace-core"))]
+fn cumulative_histogram_internal() {}
+
+/// Get the trace events captured by `TracableInfo::capture`.
+///
+/// The captured events are reset at each parser call like histogram.
+///
+/// ```
+/// # use nom::character::complete::*;
+/// # use nom::IResult;
+/// # use nom_locate::LocatedSpan;
+/// # use nom_tracable::{captured_trace, tracable_parser, TracableInfo};
+/// #
+/// # type Span<'a> = LocatedSpan<&'a str, TracableInfo>;
+/// #
+/// # #[tracable_parser]
+/// # pub fn term(s: Span) -> IResult<Span, String> {
+/// #     let (s, x) = char('1')(s)?;
+/// #     Ok((s, x.to_string()))
+/// # }
+/// #
+/// let ret = term(LocatedSpan::new_extra("1", TracableInfo::new().capture(true)));
+/// let trace = captured_trace();
+/// let mut html = Vec::new();
+/// trace.write_html(&mut html).unwrap();
+/// ```
+#[cfg(feature = "std")]
+pub fn captured_trace() -> Trace {
+    captured_trace_internal()
+}
+
+#[cfg(feature = "trace-core")]
+fn captured_trace_internal() -> Trace {
+    crate::TRACABLE_STORAGE.with(|storage| storage.borrow().trace.clone())
+}
+
+#[cfg(all(feature = "std", not(feature = "trace-core")))]
+fn captured_trace_internal() -> Trace {
+    Trace::default()
+}
+
+/// Take the captured trace of the last parse without copy.
+///
+/// The captured trace is cleared, so the following `captured_trace` returns an empty trace.
+#[cfg(feature = "std")]
+pub fn take_captured_trace() -> Trace {
+    take_captured_trace_internal()
+}
+
+#[cfg(feature = "trace-core")]
+fn take_captured_trace_internal() -> Trace {
+    crate::TRACABLE_STORAGE.with(|storage| std::mem::take(&mut storage.borrow_mut().trace))
+}
+
+#[cfg(all(feature = "std", not(feature = "trace-core")))]
+fn take_captured_trace_internal() -> Trace {
+    Trace::default()
+}
+
+/// Get the ID of the current parser invocation.
+///
+/// This is intended to be called inside the parser body, so values produced by the parser
+/// can be tagged with the invocation which created them.
+/// The ID is the index of the forward event in `Trace::events` of the captured trace.
+/// In nested contexts by `nested_context`, the ID is the index in the trace of the nested context.
+/// `None` is returned if `TracableInfo::capture` is disabled or no parser is running.
+///
+/// ```
+/// # use nom::character::complete::*;
+/// # use nom::IResult;
+/// # use nom_locate::LocatedSpan;
+/// # use nom_tracable::{captured_trace, current_event_id, tracable_parser, TracableInfo};
+/// #
+/// # type Span<'a> = LocatedSpan<&'a str, TracableInfo>;
+/// #
+/// #[tracable_parser]
+/// pub fn term(s: Span) -> IResult<Span, Option<usize>> {
+///     let (s, _) = char('1')(s)?;
+///     Ok((s, current_event_id()))
+/// }
+///
+/// let (_, id) = term(LocatedSpan::new_extra("1", TracableInfo::new().capture(true))).unwrap();
+/// # #[cfg(feature = "trace")]
+/// assert_eq!(captured_trace().events[id.unwrap()].parser, "term");
+/// ```
+#[cfg(feature = "std")]
+pub fn current_event_id() -> Option<usize> {
+    current_event_id_internal()
+}
+
+#[cfg(feature = "trace-core")]
+fn current_event_id_internal() -> Option<usize> {
+    crate::TRACABLE_STORAGE.with(|storage| storage.borrow().stack.last()?.event)
+}
+
+#[cfg(all(feature = "std", not(feature = "trace-core")))]
+fn current_event_id_internal() -> Option<usize> {
+    None
+}
+
+/// Process the captured events of the last parse incrementally without copy.
+///
+/// Parsers must not be called in the closure because the storage is borrowed.
+///
+/// ```
+/// # use nom::character::complete::*;
+/// # use nom::IResult;
+/// # use nom_locate::LocatedSpan;
+/// # use nom_tracable::{tracable_parser, with_captured_events, TraceEventKind, TracableInfo};
+/// #
+/// # type Span<'a> = LocatedSpan<&'a str, TracableInfo>;
+/// #
+/// # #[tracable_parser]
+/// # pub fn term(s: Span) -> IResult<Span, String> {
+/// #     let (s, x) = char('1')(s)?;
+/// #     Ok((s, x.to_string()))
+/// # }
+/// #
+/// let ret = term(LocatedSpan::new_extra("1", TracableInfo::new().capture(true)));
+/// let failures = with_captured_events(|events| {
+///     events.filter(|x| x.kind == TraceEventKind::Err).count()
+/// });
+/// assert_eq!(failures, 0);
+/// ```
+#[cfg(feature = "std")]
+pub fn with_captured_events<R, F: FnOnce(std::slice::Iter<'_, TraceEvent>) -> R>(f: F) -> R {
+    with_captured_events_internal(f)
+}
+
+#[cfg(feature = "trace-core")]
+fn with_captured_events_internal<R, F: FnOnce(std::slice::Iter<'_, TraceEvent>) -> R>(f: F) -> R {
+    crate::TRACABLE_STORAGE.with(|storage| f(storage.borrow().trace.events.iter()))
+}
+
+#[cfg(all(feature = "std", not(feature = "trace-core")))]
+fn with_captured_events_internal<R, F: FnOnce(std::slice::Iter<'_, TraceEvent>) -> R>(f: F) -> R {
+    f([].iter())
+}
+
+/// Get the maximum number of nested parsers reached during the last parse.
+///
+/// The statistics information is reset at each parser call like histogram.
+#[cfg(feature = "std")]
+pub fn max_depth() -> usize {
+    max_depth_internal()
+}
+
+#[cfg(feature = "trace-core")]
+fn max_depth_internal() -> usize {
+    crate::TRACABLE_STORAGE.with(|storage| storage.borrow().max_depth)
+}
+
+#[cfg(all(feature = "std", not(feature = "trace-core")))]
+fn max_depth_internal() -> usize {
+    0
+}
+
+/// Get the parser chain at the maximum depth reached during the last parse.
+///
+/// The first element is the root parser.
+#[cfg(feature = "std")]
+pub fn max_depth_chain() -> Vec<String> {
+    max_depth_chain_internal()
+}
+
+#[cfg(feature = "trace-core")]
+fn max_depth_chain_internal() -> Vec<String> {
+    crate::TRACABLE_STORAGE.with(|storage| storage.borrow().max_depth_chain.clone())
+}
+
+#[cfg(all(feature = "std", not(feature = "trace-core")))]
+fn max_depth_chain_internal() -> Vec<String> {
+    Vec::new()
+}
+
+/// Set the hook called when a limit such as `TracableInfo::time_budget` is exceeded.
+///
+/// The argument of the hook is the diagnostic message.
+/// The hook can abort the parse by `panic!` or `std::process::exit`.
+/// After `panic!` is caught by `std::panic::catch_unwind`, the later parses are traced as usual.
+/// The hook is thread independent because it is stored at thread local storage.
+#[cfg(feature = "std")]
+pub fn set_abort_hook(hook: Option<fn(&str)>) {
+    set_abort_hook_internal(hook);
+}
+
+#[cfg(feature = "trace-core")]
+fn set_abort_hook_internal(hook: Option<fn(&str)>) {
+    crate::TRACABLE_STORAGE.with(|storage| {
+        storage.borrow_mut().abort_hook = hook;
+    });
+}
+
+#[cfg(all(feature = "std", not(feature = "trace-core")))]
+fn set_abort_hook_internal(_hook: Option<fn(&str)>) {}
+
+#[cfg(any(feature = "trace-core", feature = "bare"))]
+static ENABLED: core::sync::atomic::AtomicBool = core::sync::atomic::AtomicBool::new(true);
+
+/// Enable or disable the trace at runtime.
+///
+/// This is global over all threads, so binaries can ship with the `trace` feature
+/// and turn on the trace by a flag or a signal.
+/// The switch is checked at the root parse, and its children follow the root,
+/// so an in-progress parse is traced consistently even if the switch is changed in the middle.
+pub fn set_enabled(enabled: bool) {
+    set_enabled_internal(enabled);
+}
+
+#[cfg(any(feature = "trace-core", feature = "bare"))]
+fn set_enabled_internal(enabled: bool) {
+    ENABLED.store(enabled, core::sync::atomic::Ordering::Relaxed);
+}
+
+#[cfg(not(any(feature = "trace-core", feature = "bare")))]
+fn set_enabled_internal(_enabled: bool) {}
+
+/// Check whether the trace is enabled by the `trace`/`bare` feature and `set_enabled`.
+pub fn is_enabled() -> bool {
+    is_enabled_internal()
+}
+
+#[cfg(any(feature = "trace-core", feature = "bare"))]
+fn is_enabled_internal() -> bool {
+    ENABLED.load(core::sync::atomic::Ordering::Relaxed)
+}
+
+#[cfg(not(any(feature = "trace-core", feature = "bare")))]
+fn is_enabled_internal() -> bool {
+    false
+}
+
+/// Check whether the parser at depth 0 is not traced.
+///
+/// The switch is checked only if no parse is in progress,
+/// and the children and the nested parses follow the root.
+#[cfg(feature = "trace-core")]
+fn untraced_root() -> bool {
+    crate::TRACABLE_STORAGE.with(|storage| {
+        let storage = storage.borrow();
+        storage.untraced > 0 || (storage.stack.is_empty() && !is_enabled())
+    })
+}
+
+/// Set the sampling interval of root parses of the current thread.
+///
+/// Only every `x`th root parse is displayed. `None` displays all parses.
+#[cfg(feature = "std")]
+pub fn set_sampling(x: Option<usize>) {
+    set_sampling_internal(x);
+}
+
+#[cfg(feature = "trace-core")]
+fn set_sampling_internal(x: Option<usize>) {
+    crate::TRACABLE_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        storage.sampling = x;
+        storage.root_count = 0;
+    });
+}
+
+#[cfg(all(feature = "std", not(feature = "trace-core")))]
+fn set_sampling_internal(_x: Option<usize>) {}
+
+/// Run `f` in a nested trace context.
+///
+/// The current trace context is suspended, and root parses in `f` get their own banner,
+/// counters and statistics. The outer context is resumed after `f`.
+/// This is useful to parse included/imported files in the middle of the parse.
+/// The captured trace of the nested context is added to `Trace::includes` of the outer trace.
+///
+/// ```
+/// # use nom::character::complete::*;
+/// # use nom::IResult;
+/// # use nom_locate::LocatedSpan;
+/// # use nom_tracable::{nested_context, tracable_parser, TracableInfo};
+/// #
+/// # type Span<'a> = LocatedSpan<&'a str, TracableInfo>;
+/// #
+/// #[tracable_parser]
+/// pub fn term(s: Span) -> IResult<Span, String> {
+///     let (s, x) = char('1')(s)?;
+///     Ok((s, x.to_string()))
+/// }
+///
+/// #[tracable_parser]
+/// pub fn include(s: Span) -> IResult<Span, String> {
+///     let (s, _) = char('@')(s)?;
+///     let included = LocatedSpan::new_extra("1", TracableInfo::new());
+///     let (_, x) = nested_context("included.txt", || term(included)).unwrap();
+///     Ok((s, x))
+/// }
+///
+/// let ret = include(LocatedSpan::new_extra("@", TracableInfo::new()));
+/// assert_eq!("\"1\"", format!("{:?}", ret.unwrap().1));
+/// ```
+#[cfg(feature = "std")]
+pub fn nested_context<R, F: FnOnce() -> R>(source: &str, f: F) -> R {
+    nested_context_internal(source, f)
+}
+
+#[cfg(feature = "trace-core")]
+fn nested_context_internal<R, F: FnOnce() -> R>(source: &str, f: F) -> R {
+    // Resume the outer storage even if the closure panics
+    struct Resume(Option<TracableStorage>);
+
+    impl Drop for Resume {
+        fn drop(&mut self) {
+            if let Some(outer) = self.0.take() {
+                let _ = crate::TRACABLE_STORAGE.try_with(|storage| {
+                    if let Ok(mut storage) = storage.try_borrow_mut() {
+                        storage.resume(outer);
+                    }
+                });
+            }
+        }
+    }
+
+    let (outer, parent, position) = crate::TRACABLE_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let parent = storage
+            .stack
+            .last()
+            .map(|x| format!(" from {} at offset {}", x.name, x.offset))
+            .unwrap_or_default();
+        let position = storage.trace.events.len();
+        (storage.suspend(), parent, position)
+    });
+    let mut resume = Resume(Some(outer));
+    ndjson_include("include_start", source);
+
+    output_line(
+        source,
+        OutputLevel::Debug,
+        format_args!("\n=== include : {}{} ===", source, parent),
+    );
+
+    let ret = f();
+
+    output_line(
+        source,
+        OutputLevel::Debug,
+        format_args!("=== end of include : {} ===", source),
+    );
+    ndjson_include("include_end", source);
+
+    crate::TRACABLE_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let inner = storage.resume(resume.0.take().unwrap_or_default());
+        if !inner.trace.events.is_empty() {
+            storage.trace.includes.push(IncludedTrace {
+                source: String::from(source),
+                position,
+                trace: inner.trace,
+            });
+        }
+    });
+
+    ret
+}
+
+#[cfg(feature = "trace-core")]
+static SESSION_ID: core::sync::atomic::AtomicUsize = core::sync::atomic::AtomicUsize::new(0);
+
+/// Get the tag of trace lines by `TracableInfo::thread_tag`, `session_tag` and `session_id`.
+///
+/// `session` is the session count of the thread.
+#[cfg(feature = "trace-core")]
+fn line_tag(info: &TracableInfo, session: usize) -> Option<String> {
+    let options = &info.options;
+    let thread = (options.thread_tag && !options.snapshot).then(|| {
+        let thread = std::thread::current();
+        match thread.name() {
+            Some(x) => String::from(x),
+            None => format!("{:?}", thread.id()),
+        }
+    });
+    let id = match &info.session_id {
+        Some(x) => String::from(&**x),
+        None if options.session_tag && !options.snapshot => {
+            let id = SESSION_ID.fetch_add(1, core::sync::atomic::Ordering::Relaxed) + 1;
+            id.to_string()
+        }
+        None if thread.is_some() => session.to_string(),
+        None => return None,
+    };
+    Some(format!("[{}#{}] ", thread.unwrap_or_default(), id))
+}
+
+/// Suspend the outer storage if the root parse is started in the middle of the outer parse.
+///
+/// This is the case that a parser re-parses a fresh span like an embedded string literal.
+/// The nested session has its own counters and statistics like `nested_context`.
+#[cfg(feature = "trace-core")]
+fn begin_nested_session(name: &str) {
+    let parent = crate::TRACABLE_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let parent = storage
+            .stack
+            .last()
+            .map(|x| (storage.stack.len(), x.name.clone(), x.offset))?;
+        let outer = storage.suspend();
+        storage.outer = Some(Box::new(outer));
+        Some(parent)
+    });
+    if let Some((depth, parent, offset)) = parent {
+        output_line(
+            name,
+            OutputLevel::Debug,
+            format_args!(
+                "\n{}=== nested parse : {} from {} at offset {} ===",
+                " ".repeat(depth),
+                name,
+                parent,
+                offset
+            ),
+        );
+    }
+}
+
+/// Resume the outer storage suspended by `begin_nested_session`.
+///
+/// The captured trace of the nested session is added to `Trace::includes` of the outer trace.
+#[cfg(feature = "trace-core")]
+fn end_nested_session(name: &str) {
+    let outer = crate::TRACABLE_STORAGE.with(|storage| storage.borrow_mut().outer.take());
+    let outer = match outer {
+        Some(x) => *x,
+        None => return,
+    };
+    let depth = outer.stack.len();
+    output_line(
+        name,
+        OutputLevel::Debug,
+        format_args!(
+            "{}=== end of nested parse : {} ===",
+            " ".repeat(depth),
+            name
+        ),
+    );
+
+    crate::TRACABLE_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let inner = storage.resume(outer);
+        if !inner.trace.events.is_empty() {
+            let position = storage.trace.events.len();
+            storage.trace.includes.push(IncludedTrace {
+                source: String::from(name),
+                position,
+                trace: inner.trace,
+            });
+        }
+    });
+}
+
+/// Give the type of the input to the closure of the parser body.
+/// This is inserted by `#[tracable_parser]`.
+#[doc(hidden)]
+#[cfg(any(feature = "trace-core", feature = "bare"))]
+pub fn parser_body<I, O, F: FnOnce(I) -> O>(_input: &I, body: F) -> F {
+    body
+}
+
+/// Guard to restore the storage if the parser unwinds by `panic!` like the hook by `set_abort_hook`.
+/// This is inserted by `#[tracable_parser]`.
+#[doc(hidden)]
+#[cfg(feature = "trace-core")]
+pub struct UnwindGuard {
+    nested: usize,
+    stack: usize,
+    untraced: usize,
+}
+
+/// Create the guard at the parser entry.
+/// This is inserted by `#[tracable_parser]`.
+#[doc(hidden)]
+#[cfg(feature = "trace-core")]
+pub fn unwind_guard() -> UnwindGuard {
+    crate::TRACABLE_STORAGE.with(|storage| {
+        let storage = storage.borrow();
+        UnwindGuard {
+            nested: storage.nested,
+            stack: storage.stack.len(),
+            untraced: storage.untraced,
+        }
+    })
+}
+
+#[cfg(feature = "trace-core")]
+impl Drop for UnwindGuard {
+    fn drop(&mut self) {
+        if !std::thread::panicking() {
+            return;
+        }
+        let _ = crate::TRACABLE_STORAGE.try_with(|storage| {
+            let mut storage = match storage.try_borrow_mut() {
+                Ok(x) => x,
+                Err(_) => return,
+            };
+            // The nested sessions started after the entry are discarded
+            while storage.nested > self.nested {
+                match storage.outer.take() {
+                    Some(outer) => {
+                        storage.resume(*outer);
+                    }
+                    None => break,
+                }
+            }
+            // The frames left by the unwound parsers make the later root parses nested
+            storage.stack.truncate(self.stack);
+            storage.untraced = self.untraced;
+            if storage.stack.is_empty() {
+                storage.parsing = false;
+                storage.repeats = None;
+                storage.step = None;
+            }
+        });
+    }
+}
+
+#[cfg(all(feature = "std", not(feature = "trace-core")))]
+fn nested_context_internal<R, F: FnOnce() -> R>(_source: &str, f: F) -> R {
+    f()
+}
+
+/// Set the default `TracableInfo` of the current thread.
+///
+/// The default is used by `new_span`, so spans can be created far from the configuration site.
+///
+/// ```
+/// # use nom::character::complete::*;
+/// # use nom::IResult;
+/// # use nom_locate::LocatedSpan;
+/// # use nom_tracable::{new_span, set_thread_default, tracable_parser, TracableInfo};
+/// #
+/// # type Span<'a> = LocatedSpan<&'a str, TracableInfo>;
+/// #
+/// # #[tracable_parser]
+/// # pub fn term(s: Span) -> IResult<Span, String> {
+/// #     let (s, x) = char('1')(s)?;
+/// #     Ok((s, x.to_string()))
+/// # }
+/// #
+/// set_thread_default(TracableInfo::new().color(false));
+///
+/// // The span has `TracableInfo::new().color(false)`
+/// let ret = term(new_span("1"));
+/// ```
+#[cfg(feature = "std")]
+pub fn set_thread_default(info: TracableInfo) {
+    set_thread_default_internal(info);
+}
+
+#[cfg(feature = "trace-core")]
+fn set_thread_default_internal(info: TracableInfo) {
+    crate::TRACABLE_STORAGE.with(|storage| {
+        storage.borrow_mut().thread_default = Some(info);
+    });
+}
+
+#[cfg(all(feature = "std", not(feature = "trace-core")))]
+fn set_thread_default_internal(_info: TracableInfo) {}
+
+/// Get the default `TracableInfo` of the current thread.
+///
+/// If `set_thread_default` is not called, this returns `TracableInfo::new()`.
+#[cfg(feature = "std")]
+pub fn thread_default() -> TracableInfo {
+    thread_default_internal()
+}
+
+#[cfg(feature = "trace-core")]
+fn thread_default_internal() -> TracableInfo {
+    crate::TRACABLE_STORAGE
+        .with(|storage| storage.borrow().thread_default.clone())
+        .unwrap_or_default()
+}
+
+#[cfg(all(feature = "std", not(feature = "trace-core")))]
+fn thread_default_internal() -> TracableInfo {
+    TracableInfo::new()
+}
+
+/// Create `LocatedSpan` with the default `TracableInfo` of the current thread.
+#[cfg(feature = "std")]
+pub fn new_span<T>(fragment: T) -> nom_locate::LocatedSpan<T, TracableInfo> {
+    nom_locate::LocatedSpan::new_extra(fragment, thread_default())
+}
+
+/// Split the input at `count`, and return `(prefix, suffix)`.
+///
+/// Both of them keep the offset and `TracableInfo` of the input,
+/// so the pieces can be handed to sub-parsers without desynchronizing the trace.
+///
+/// # Panics
+///
+/// Panics if `count` is larger than the length of the input,
+/// or is not on a character boundary of `&str` input.
+/// Check the length by `nom::InputLength::input_len` before splitting the input from a parser.
+///
+/// ```
+/// # use nom::character::complete::*;
+/// # use nom::IResult;
+/// # use nom_locate::LocatedSpan;
+/// # use nom_tracable::{rejoin, split_at, tracable_parser, TracableInfo};
+/// #
+/// # type Span<'a> = LocatedSpan<&'a str, TracableInfo>;
+/// #
+/// # #[tracable_parser]
+/// # pub fn term(s: Span) -> IResult<Span, String> {
+/// #     let (s, x) = char('1')(s)?;
+/// #     Ok((s, x.to_string()))
+/// # }
+/// #
+/// let (piece, rest) = split_at(LocatedSpan::new_extra("1+1", TracableInfo::new()), 1);
+/// let (piece, _) = term(piece).unwrap();
+///
+/// // Continue with the trace state after the sub-parser
+/// let rest = rejoin(rest, &piece);
+/// assert_eq!(rest.location_offset(), 1);
+/// ```
+pub fn split_at<T: nom::InputTake>(input: T, count: usize) -> (T, T) {
+    let (suffix, prefix) = input.take_split(count);
+    (prefix, suffix)
+}
+
+/// Take over `TracableInfo` of `inner` to `outer`.
+///
+/// This should be used when the result of a sub-parser applied to a piece by `split_at` is re-joined.
+#[cfg(feature = "trace-core")]
+pub fn rejoin<T: HasTracableInfo>(outer: T, inner: &T) -> T {
+    outer.set_tracable_info(inner.get_tracable_info())
+}
+
+#[cfg(not(feature = "trace-core"))]
+pub fn rejoin<T>(outer: T, _inner: &T) -> T {
+    outer
+}
+
+#[allow(dead_code)]
+#[cfg(feature = "std")]
+fn show_histogram(
+    title: &str,
+    map: &HashMap<String, usize>,
+    results: Option<&HashMap<String, (usize, usize)>>,
+) {
+    let mut result = Vec::new();
+    let mut max_parser_len = "parser".len();
+    let mut max_count = 0;
+    let mut max_count_len = "count".len();
+    for (p, c) in map {
+        result.push((p, c));
+        max_parser_len = max_parser_len.max(p.len());
+        max_count = max_count.max(*c);
+        max_count_len = max_count_len.max(format!("{}", c).len());
+    }
+
+    result.sort_by(|a, b| b.1.partial_cmp(a.1).unwrap());
+
+    let bar_length = 50;
+
+    let result_width = "success".len().max(max_count_len);
+    let count_width = if results.is_some() { max_count_len } else { 0 };
+    let result_column = |p: &str| {
+        if let Some(results) = results {
+            let (success, failure) = results.get(p).copied().unwrap_or_default();
+            format!(
+                " | {:>width$} | {:>width$}",
+                success,
+                failure,
+                width = result_width
+            )
+        } else {
+            String::new()
+        }
+    };
+
+    let mut lock = writer();
+
+    writeln!(
+        lock,
+        "\n{:<parser$} | {:<bar$} | {:<count$}{}",
+        "parser",
+        title,
+        "count",
+        if results.is_some() {
+            format!(
+                " | {:>width$} | {:>width$}",
+                "success",
+                "failure",
+                width = result_width
+            )
+        } else {
+            String::new()
+        },
+        parser = max_parser_len,
+        bar = bar_length,
+        count = count_width,
+    )
+    .unwrap();
+
+    writeln!(
+        lock,
+        "{:<parser$} | {:<bar$} | {}{}",
+        "-".repeat(max_parser_len),
+        "-".repeat(bar_length),
+        "-".repeat(max_count_len),
+        if results.is_some() {
+            format!(
+                " | {} | {}",
+                "-".repeat(result_width),
+                "-".repeat(result_width)
+            )
+        } else {
+            String::new()
+        },
+        parser = max_parser_len,
+        bar = bar_length,
+    )
+    .unwrap();
+
+    for (p, c) in &result {
+        let bar = *c * bar_length / max_count;
+        if bar > 0 {
+            writeln!(
+                lock,
+                "{:<parser$} | {}{} | {:<count$}{}",
+                p,
+                ".".repeat(bar),
+                " ".repeat(bar_length - bar),
+                c,
+                result_column(p),
+                parser = max_parser_len,
+                count = count_width,
+            )
+            .unwrap();
+        }
+    }
+    writeln!(lock).unwrap()
+}
+
+#[allow(dead_code)]
+#[cfg(feature = "std")]
+fn show_timing(map: &HashMap<String, (usize, Duration)>) {
+    let mut result: Vec<_> = map.iter().collect();
+    result.sort_by_key(|x| std::cmp::Reverse((x.1).1));
+
+    let max_parser_len = map
+        .keys()
+        .map(|x| x.len())
+        .max()
+        .unwrap_or(0)
+        .max("parser".len());
+
+    let mut lock = writer();
+
+    writeln!(
+        lock,
+        "\n{:<parser$} | {:>10} | {:>14} | {:>14}",
+        "parser",
+        "count",
+        "total",
+        "average",
+        parser = max_parser_len,
+    )
+    .unwrap();
+
+    writeln!(
+        lock,
+        "{} | {} | {} | {}",
+        "-".repeat(max_parser_len),
+        "-".repeat(10),
+        "-".repeat(14),
+        "-".repeat(14),
+    )
+    .unwrap();
+
+    for (p, (c, t)) in &result {
+        writeln!(
+            lock,
+            "{:<parser$} | {:>10} | {:>14} | {:>14}",
+            p,
+            c,
+            format!("{:?}", t),
+            format!("{:?}", *t / *c as u32),
+            parser = max_parser_len,
+        )
+        .unwrap();
+    }
+    writeln!(lock).unwrap()
+}
+
+/// Check whether the output supports colors.
+///
+/// Colors are disabled if `NO_COLOR` environment variable is set, or the output is not a terminal.
+#[cfg(feature = "trace-core")]
+fn color_supported() -> bool {
+    use std::io::IsTerminal;
+
+    static SUPPORTED: std::sync::OnceLock<bool> = std::sync::OnceLock::new();
+    *SUPPORTED.get_or_init(|| {
+        let no_color = std::env::var_os("NO_COLOR").is_some_and(|x| !x.is_empty());
+        let terminal = if cfg!(feature = "stderr") {
+            std::io::stderr().is_terminal()
+        } else {
+            std::io::stdout().is_terminal()
         };
-        self.cumulative_histogram.insert(String::from(key), next);
+        !no_color && terminal && virtual_terminal()
+    })
+}
+
+/// Enable the virtual terminal processing of the Windows console to interpret ANSI escape sequences.
+///
+/// `false` is returned if the console doesn't support it.
+/// If the output is not a console, the escape sequences are written as is.
+#[cfg(all(feature = "trace-core", windows))]
+fn virtual_terminal() -> bool {
+    type Handle = *mut std::ffi::c_void;
+    const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+    const STD_ERROR_HANDLE: u32 = -12i32 as u32;
+    const ENABLE_VIRTUAL_TERMINAL_PROCESSING: u32 = 0x0004;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetStdHandle(std_handle: u32) -> Handle;
+        fn GetConsoleMode(handle: Handle, mode: *mut u32) -> i32;
+        fn SetConsoleMode(handle: Handle, mode: u32) -> i32;
+    }
+
+    static ENABLED: std::sync::OnceLock<bool> = std::sync::OnceLock::new();
+    *ENABLED.get_or_init(|| {
+        let std_handle = if cfg!(feature = "stderr") {
+            STD_ERROR_HANDLE
+        } else {
+            STD_OUTPUT_HANDLE
+        };
+        // SAFETY: the handle is given by `GetStdHandle`, and `mode` is a valid pointer
+        unsafe {
+            let handle = GetStdHandle(std_handle);
+            let mut mode = 0;
+            if GetConsoleMode(handle, &mut mode) == 0 {
+                return true;
+            }
+            mode & ENABLE_VIRTUAL_TERMINAL_PROCESSING != 0
+                || SetConsoleMode(handle, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING) != 0
+        }
+    })
+}
+
+#[cfg(all(feature = "trace-core", not(windows)))]
+fn virtual_terminal() -> bool {
+    true
+}
+
+/// Destination of trace lines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraceOutput {
+    Stdout,
+    Stderr,
+}
+
+impl TraceOutput {
+    const DEFAULT: TraceOutput = if cfg!(feature = "stderr") {
+        TraceOutput::Stderr
+    } else {
+        TraceOutput::Stdout
+    };
+}
+
+impl Default for TraceOutput {
+    fn default() -> Self {
+        TraceOutput::DEFAULT
+    }
+}
+
+/// Style of the location column by `TracableInfo::location_style`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LocationStyle {
+    /// Byte offset from the beginning of the input.
+    #[default]
+    Offset,
+    /// Line and column like `3:14`. Inputs without line information show the offset.
+    LineCol,
+    /// Both of the offset and the line and column.
+    Both,
+}
+
+/// Timing to write trace lines to the output by `TracableInfo::flush`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TraceFlush {
+    /// Each line is written at once.
+    #[default]
+    Line,
+    /// The lines are buffered, and written at the end of the root parse.
+    /// The lines are lost if the parse panics.
+    Parse,
+}
+
+#[cfg(feature = "trace-core")]
+type OutputFile = Option<(std::path::PathBuf, std::io::BufWriter<std::fs::File>)>;
+
+// This is separated from `TRACABLE_STORAGE` because the writer is used while the storage is borrowed.
+#[cfg(feature = "trace-core")]
+thread_local!(
+    static OUTPUT_FILE: core::cell::RefCell<OutputFile> = const { core::cell::RefCell::new(None) }
+);
+
+/// Open the file by `TracableInfo::output_file` at the start of the root parse.
+#[cfg(feature = "trace-core")]
+fn open_output_file(info: &TracableInfo) {
+    open_output_path(info.options.output_file.as_ref());
+}
+
+#[cfg(feature = "trace-core")]
+fn open_output_path(path: Option<&std::path::PathBuf>) {
+    let opened = OUTPUT_FILE.with(|file| file.borrow().as_ref().map(|x| x.0.clone()));
+    if opened.as_ref() == path {
+        return;
     }
 
-    fn add_cumulative(&mut self, key: &str, depth: usize) {
-        self.cumulative_working
-            .insert((String::from(key), depth), 0);
+    let file = path.and_then(|path| {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path);
+        match file {
+            Ok(x) => Some((path.clone(), std::io::BufWriter::new(x))),
+            Err(x) => {
+                let _ = writeln!(
+                    std::io::stderr(),
+                    "!!! output_file : {} can't be opened : {}",
+                    path.display(),
+                    x
+                );
+                None
+            }
+        }
+    });
+    let closed = OUTPUT_FILE.with(|x| x.replace(file));
+    if let Some((_, mut w)) = closed {
+        let _ = w.flush();
     }
+}
 
-    fn inc_cumulative(&mut self) {
-        for val in self.cumulative_working.values_mut() {
-            *val = *val + 1;
+/// Writer to the file by `TracableInfo::output_file`.
+///
+/// The file is flushed when the writer is dropped outside of parses.
+#[cfg(feature = "trace-core")]
+struct FileWriter;
+
+#[cfg(feature = "trace-core")]
+impl Write for FileWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        OUTPUT_FILE.with(|file| match file.borrow_mut().as_mut() {
+            Some((_, w)) => w.write(buf),
+            None => Ok(buf.len()),
+        })
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        OUTPUT_FILE.with(|file| match file.borrow_mut().as_mut() {
+            Some((_, w)) => w.flush(),
+            None => Ok(()),
+        })
+    }
+}
+
+#[cfg(feature = "trace-core")]
+impl Drop for FileWriter {
+    fn drop(&mut self) {
+        let running = crate::TRACABLE_STORAGE.with(|storage| {
+            storage
+                .try_borrow()
+                .map(|x| !x.stack.is_empty())
+                .unwrap_or(true)
+        });
+        if !running {
+            let _ = self.flush();
         }
     }
+}
 
-    fn del_cumulative(&mut self, key: &str, depth: usize) {
-        self.cumulative_working.remove(&(key.to_string(), depth));
+#[cfg(feature = "trace-core")]
+static SINK: std::sync::Mutex<Option<fn(&str)>> = std::sync::Mutex::new(None);
+
+// Whether `SINK` is set, so `writer` doesn't lock `SINK` for each line without the sink
+#[cfg(feature = "trace-core")]
+static SINK_SET: core::sync::atomic::AtomicBool = core::sync::atomic::AtomicBool::new(false);
+
+/// Set the sink shared by all threads.
+///
+/// If the sink is set, trace lines and reports are passed to the sink instead of the output.
+/// The sink is called with a chunk of complete lines: a line, a parse by `TraceFlush::Parse`, or a report.
+/// The calls are serialized, so concurrent traces are not interleaved in a chunk.
+///
+/// With the `bare` feature, this is the only output, and the sink is called with each line.
+///
+/// On `wasm32-unknown-unknown`, stdout/stderr are discarded, so `wasm` feature routes the output to the browser console.
+pub fn set_sink(sink: Option<fn(&str)>) {
+    set_sink_internal(sink);
+}
+
+#[cfg(feature = "trace-core")]
+fn set_sink_internal(sink: Option<fn(&str)>) {
+    let mut lock = SINK.lock().unwrap_or_else(|x| x.into_inner());
+    *lock = sink;
+    SINK_SET.store(sink.is_some(), core::sync::atomic::Ordering::Release);
+}
+
+#[cfg(all(feature = "bare", not(feature = "trace-core")))]
+fn set_sink_internal(sink: Option<fn(&str)>) {
+    bare::set_sink(sink);
+}
+
+#[cfg(not(any(feature = "trace-core", feature = "bare")))]
+fn set_sink_internal(_sink: Option<fn(&str)>) {}
+
+/// Writer to the sink by `set_sink`. The written text is passed to the sink at drop.
+#[cfg(feature = "trace-core")]
+struct SinkWriter {
+    sink: fn(&str),
+    buf: Vec<u8>,
+}
+
+#[cfg(feature = "trace-core")]
+impl Write for SinkWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
     }
 
-    fn get_cumulative(&mut self, key: &str, depth: usize) -> Option<&usize> {
-        self.cumulative_working.get(&(key.to_string(), depth))
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
     }
+}
 
-    fn get_parser_index(&mut self, key: &str) -> usize {
-        if let Some(x) = self.parser_indexes.get(key) {
-            *x
-        } else {
-            let new_index = self.parser_index_next;
-            self.parser_index_next += 1;
-            self.parser_indexes.insert(String::from(key), new_index);
-            new_index
+#[cfg(feature = "trace-core")]
+impl Drop for SinkWriter {
+    fn drop(&mut self) {
+        if !self.buf.is_empty() {
+            // The lock serializes the calls of the sink
+            let _lock = SINK.lock().unwrap_or_else(|x| x.into_inner());
+            (self.sink)(&String::from_utf8_lossy(&self.buf));
         }
     }
 }
 
-#[cfg(feature = "trace")]
+// The output captured by `with_captured_trace`.
+#[cfg(feature = "trace-core")]
 thread_local!(
-    static TRACABLE_STORAGE: core::cell::RefCell<crate::TracableStorage> = {
-        core::cell::RefCell::new(crate::TracableStorage::new())
-    }
+    static CAPTURED_OUTPUT: core::cell::RefCell<Option<Vec<u8>>> = const { core::cell::RefCell::new(None) }
 );
 
-/// Show histogram of parser call count.
+#[cfg(feature = "trace-core")]
+fn capturing_output() -> bool {
+    CAPTURED_OUTPUT.with(|x| x.borrow().is_some())
+}
+
+/// Writer to the buffer of `with_captured_trace`.
+#[cfg(feature = "trace-core")]
+struct CaptureWriter;
+
+#[cfg(feature = "trace-core")]
+impl Write for CaptureWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        CAPTURED_OUTPUT.with(|x| {
+            if let Some(x) = x.borrow_mut().as_mut() {
+                x.extend_from_slice(buf);
+            }
+        });
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Run the closure, and get the result with the trace output of the current thread while running it.
 ///
-/// The statistics information to generate histogram is reset at each parser call.
-/// Therefore `histogram` should be called before next parser call.
-/// The information is thread independent because it is stored at thread local storage.
+/// The output is not passed to the sink, `output_file`, stdout or `log` crate, and color is disabled.
+/// This is useful for test assertions on the trace.
 ///
 /// ```
 /// # use nom::character::complete::*;
 /// # use nom::IResult;
 /// # use nom_locate::LocatedSpan;
-/// # use nom_tracable::{cumulative_histogram, tracable_parser, TracableInfo};
+/// # use nom_tracable::{tracable_parser, with_captured_trace, TracableInfo};
 /// #
 /// # type Span<'a> = LocatedSpan<&'a str, TracableInfo>;
 /// #
@@ -405,156 +3282,865 @@ thread_local!(
 /// #     Ok((s, x.to_string()))
 /// # }
 /// #
-/// # #[test]
-/// # fn test() {
-///     let ret = term(LocatedSpan::new_extra("1", TracableInfo::new()));
-///     histogram(); // Show histogram of "1" parsing
-///
-///     let ret = term(LocatedSpan::new_extra("11", TracableInfo::new()));
-///     histogram(); // Show histogram of "11" parsing
-/// # }
+/// let (ret, trace) = with_captured_trace(|| term(LocatedSpan::new_extra("1", TracableInfo::new())));
+/// assert!(ret.is_ok());
+/// # #[cfg(feature = "trace")]
+/// assert!(trace.contains("-> term"));
 /// ```
-pub fn histogram() {
-    histogram_internal();
+#[cfg(feature = "std")]
+pub fn with_captured_trace<R, F: FnOnce() -> R>(f: F) -> (R, String) {
+    with_captured_trace_internal(f)
 }
 
-#[cfg(feature = "trace")]
-fn histogram_internal() {
+#[cfg(feature = "trace-core")]
+fn with_captured_trace_internal<R, F: FnOnce() -> R>(f: F) -> (R, String) {
+    // Restore the outer capture even if the closure panics
+    struct Restore(Option<Option<Vec<u8>>>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            if let Some(x) = self.0.take() {
+                CAPTURED_OUTPUT.with(|output| output.replace(x));
+            }
+        }
+    }
+
+    let mut restore = Restore(Some(CAPTURED_OUTPUT.with(|x| x.replace(Some(Vec::new())))));
+    let ret = f();
+    let captured = CAPTURED_OUTPUT.with(|x| x.replace(restore.0.take().unwrap_or_default()));
+    let captured = String::from_utf8_lossy(&captured.unwrap_or_default()).into_owned();
+    (ret, captured)
+}
+
+#[cfg(all(feature = "std", not(feature = "trace-core")))]
+fn with_captured_trace_internal<R, F: FnOnce() -> R>(f: F) -> (R, String) {
+    (f(), String::new())
+}
+
+#[cfg(feature = "trace-core")]
+fn writer() -> Box<dyn Write> {
+    if capturing_output() {
+        return Box::new(CaptureWriter);
+    }
+    let sink = if SINK_SET.load(core::sync::atomic::Ordering::Acquire) {
+        *SINK.lock().unwrap_or_else(|x| x.into_inner())
+    } else {
+        None
+    };
+    if let Some(sink) = sink {
+        return Box::new(SinkWriter {
+            sink,
+            buf: Vec::new(),
+        });
+    }
+    if OUTPUT_FILE.with(|file| file.borrow().is_some()) {
+        return Box::new(FileWriter);
+    }
+    let output = crate::TRACABLE_STORAGE.with(|storage| storage.borrow().output);
+    #[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+    return Box::new(console::ConsoleWriter::new(output));
+    #[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+    match output {
+        TraceOutput::Stdout => Box::new(std::io::stdout().lock()),
+        TraceOutput::Stderr => Box::new(std::io::stderr().lock()),
+    }
+}
+
+#[cfg(all(feature = "std", not(feature = "trace-core")))]
+fn writer() -> Box<dyn Write> {
+    match TraceOutput::DEFAULT {
+        TraceOutput::Stdout => Box::new(std::io::stdout().lock()),
+        TraceOutput::Stderr => Box::new(std::io::stderr().lock()),
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug)]
+#[cfg(feature = "std")]
+enum OutputLevel {
+    Header,
+    Warn,
+    Debug,
+    Trace,
+}
+
+/// Output a trace line.
+///
+/// The line is discarded if the root parse is not sampled by `TracableInfo::sampling`,
+/// and buffered if `TracableInfo::ring_buffer` is enabled.
+#[cfg(feature = "trace-core")]
+fn output_line(target: &str, level: OutputLevel, line: std::fmt::Arguments) {
+    let collapsed =
+        crate::TRACABLE_STORAGE.with(|storage| match (&mut storage.borrow_mut().repeats, level) {
+            (_, OutputLevel::Warn) | (None, _) => false,
+            (Some(repeats), _) => repeats.push(target, level, &line),
+        });
+    if !collapsed {
+        output_uncollapsed_line(target, level, line);
+    }
+}
+
+/// Output a trace line which is not buffered by `TracableInfo::collapse_repeats`.
+#[cfg(feature = "trace-core")]
+fn output_uncollapsed_line(target: &str, level: OutputLevel, line: std::fmt::Arguments) {
+    let buffered = crate::TRACABLE_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        if storage.sampled_out {
+            return true;
+        }
+        match (&mut storage.ring, level) {
+            (_, OutputLevel::Warn) | (None, _) => false,
+            (Some(ring), _) => {
+                ring.push(target, level, line.to_string());
+                true
+            }
+        }
+    });
+    if !buffered {
+        emit_line(target, level, line);
+    }
+}
+
+/// Emit a trace line to stdout/stderr.
+/// If `log` feature is enabled, the line is emitted through `log` crate with the parser name as the target.
+#[cfg(all(feature = "trace-core", not(feature = "log")))]
+fn emit_line(_target: &str, level: OutputLevel, line: std::fmt::Arguments) {
+    use std::fmt::Write as _;
+
+    let lines = crate::TRACABLE_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let storage = &mut *storage;
+        let buffered = storage.flush == TraceFlush::Parse && storage.parsing;
+        if let OutputLevel::Header = level {
+            storage.lines.push('\n');
+        }
+        let tag = storage.tag.as_deref().unwrap_or("");
+        writeln!(storage.lines, "{}{}", tag, line).unwrap();
+        if buffered {
+            None
+        } else {
+            Some(std::mem::take(&mut storage.lines))
+        }
+    });
+    if let Some(lines) = lines {
+        write_lines(&lines);
+    }
+}
+
+/// Write the lines to the output by a single write.
+#[cfg(feature = "trace-core")]
+fn write_lines(lines: &str) {
+    let mut lock = writer();
+    lock.write_all(lines.as_bytes()).unwrap();
+}
+
+/// Write the lines buffered by `TraceFlush::Parse`.
+#[cfg(feature = "trace-core")]
+fn flush_lines() {
+    let lines =
+        crate::TRACABLE_STORAGE.with(|storage| std::mem::take(&mut storage.borrow_mut().lines));
+    if !lines.is_empty() {
+        write_lines(&lines);
+    }
+}
+
+#[cfg(all(feature = "trace-core", feature = "log"))]
+fn emit_line(target: &str, level: OutputLevel, line: std::fmt::Arguments) {
+    let tag = crate::TRACABLE_STORAGE.with(|storage| storage.borrow().tag.clone());
+    let tag = tag.as_deref().unwrap_or("");
+    if capturing_output() {
+        write_lines(&format!("{}{}\n", tag, line));
+        return;
+    }
+    match level {
+        OutputLevel::Warn => log::warn!(target: target, "{}{}", tag, line),
+        OutputLevel::Header | OutputLevel::Debug => {
+            log::debug!(target: target, "{}{}", tag, line)
+        }
+        OutputLevel::Trace => log::trace!(target: target, "{}{}", tag, line),
+    }
+}
+
+#[cfg(feature = "trace-core")]
+fn sampled_out() -> bool {
+    crate::TRACABLE_STORAGE.with(|storage| storage.borrow().sampled_out)
+}
+
+/// Column of elapsed time which is appended to forward/backward count.
+#[cfg(feature = "trace-core")]
+fn timing_column(info: &TracableInfo, x: &str) -> String {
+    if info.timing_shown() {
+        format!(" : {:>12}", x)
+    } else {
+        String::new()
+    }
+}
+
+/// Record the event to the captured trace and the NDJSON output, and call the hooks.
+#[cfg(feature = "trace-core")]
+fn record_event<F: FnOnce() -> TraceEvent>(info: &TracableInfo, f: F) {
+    let hooked = info.options.on_forward.is_some() || info.options.on_backward.is_some();
+    if !info.capturing() && info.options.ndjson.is_none() && !hooked {
+        return;
+    }
+    let event = f();
+
+    // The hooks are called without the borrow of the storage, so they can use the other functions
+    let hook = match event.kind {
+        TraceEventKind::Forward => info.options.on_forward,
+        TraceEventKind::Ok | TraceEventKind::Err => info.options.on_backward,
+        TraceEventKind::Custom | TraceEventKind::Bookmark => None,
+    };
+    if let Some(Hook(hook)) = hook {
+        hook(&event);
+    }
+    if !info.capturing() && info.options.ndjson.is_none() {
+        return;
+    }
+
     crate::TRACABLE_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let written = match info.options.ndjson_filter {
+            Some(Hook(filter)) => filter(&event),
+            None => true,
+        };
+        if let (true, Some(w)) = (written, &mut storage.ndjson) {
+            let _ = writeln!(w, "{}", event.to_json());
+        }
+        if info.capturing() {
+            storage.push_event(event);
+        }
+    });
+}
+
+/// Open the NDJSON output at the start of the root parse.
+#[cfg(feature = "trace-core")]
+fn open_ndjson(name: &str, info: &TracableInfo) {
+    let path = match &info.options.ndjson {
+        Some(x) => x,
+        None => return,
+    };
+    let shared = crate::TRACABLE_STORAGE.with(|storage| {
         let storage = storage.borrow();
-        show_histogram("histogram", &storage.histogram);
+        storage.nested > 0 && storage.ndjson.is_some()
     });
+    if shared {
+        return;
+    }
+    match std::fs::File::create(path) {
+        Ok(file) => crate::TRACABLE_STORAGE.with(|storage| {
+            storage.borrow_mut().ndjson = Some(std::io::BufWriter::new(file));
+        }),
+        Err(err) => output_line(
+            name,
+            OutputLevel::Warn,
+            format_args!("!!! ndjson : failed to create {} : {}", path.display(), err),
+        ),
+    }
 }
 
-#[cfg(not(feature = "trace"))]
-fn histogram_internal() {}
+/// Close the NDJSON output at the end of the root parse.
+#[cfg(feature = "trace-core")]
+fn close_ndjson() {
+    let ndjson = crate::TRACABLE_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        if storage.nested > 0 {
+            if let Some(w) = &mut storage.ndjson {
+                let _ = w.flush();
+            }
+            None
+        } else {
+            storage.ndjson.take()
+        }
+    });
+    if let Some(mut w) = ndjson {
+        let _ = w.flush();
+    }
+}
 
-/// Show cumulative histogram of parser call count.
+/// Write a line of the nested context to the NDJSON output.
+#[cfg(feature = "trace-core")]
+fn ndjson_include(kind: &str, source: &str) {
+    crate::TRACABLE_STORAGE.with(|storage| {
+        if let Some(w) = &mut storage.borrow_mut().ndjson {
+            let _ = writeln!(
+                w,
+                "{{\"kind\":\"{}\",\"source\":{}}}",
+                kind,
+                ndjson::escape(source)
+            );
+        }
+    });
+}
+
+/// Check whether the line at the depth is elided by `TracableInfo::max_depth`.
 ///
-/// The call count includes the counts of children parsers.
+/// The elided lines are summarized before the next line at the shallower depth.
+#[cfg(feature = "trace-core")]
+fn depth_elided(name: &str, info: &TracableInfo, depth: usize) -> bool {
+    match info.options.max_depth {
+        Some(max) if depth > max => {
+            crate::TRACABLE_STORAGE.with(|storage| storage.borrow_mut().elided += 1);
+            true
+        }
+        Some(_) => {
+            flush_elided(name, info);
+            false
+        }
+        None => false,
+    }
+}
+
+/// Display the forward lines which are held by `TracableInfo::failures_only`.
+#[cfg(feature = "trace-core")]
+fn flush_pending() {
+    let pending =
+        crate::TRACABLE_STORAGE.with(|storage| std::mem::take(&mut storage.borrow_mut().pending));
+    for (_, name, line) in pending {
+        output_line(&name, OutputLevel::Trace, format_args!("{}", line));
+    }
+}
+
+#[cfg(feature = "trace-core")]
+fn suppressed() -> bool {
+    crate::TRACABLE_STORAGE.with(|storage| storage.borrow().suppressed > 0)
+}
+
+/// Guard of the region by `without_trace!`.
 ///
-/// The statistics information to generate histogram is reset at each parser call.
-/// Therefore `cumulative_histogram` should be called before next parser call.
-/// The information is thread independent because it is stored at thread local storage.
+/// The trace is suppressed until the guard is dropped.
+#[cfg(feature = "std")]
+pub struct TraceSuppression {
+    _private: (),
+}
+
+/// Suppress the trace until the returned guard is dropped. This is used by `without_trace!`.
+#[cfg(feature = "std")]
+pub fn suppress_trace() -> TraceSuppression {
+    suppress_trace_internal();
+    TraceSuppression { _private: () }
+}
+
+#[cfg(feature = "trace-core")]
+fn suppress_trace_internal() {
+    crate::TRACABLE_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        if storage.suppressed == 0 {
+            storage.suppressed_calls = 0;
+        }
+        storage.suppressed += 1;
+    });
+}
+
+#[cfg(all(feature = "std", not(feature = "trace-core")))]
+fn suppress_trace_internal() {}
+
+#[cfg(feature = "std")]
+impl Drop for TraceSuppression {
+    fn drop(&mut self) {
+        resume_trace_internal();
+    }
+}
+
+/// Display a line which notes the suppressed calls at the end of the outermost region.
+#[cfg(feature = "trace-core")]
+fn resume_trace_internal() {
+    let ended = crate::TRACABLE_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        storage.suppressed = storage.suppressed.saturating_sub(1);
+        if storage.suppressed > 0 || storage.suppressed_calls == 0 {
+            return None;
+        }
+        let frame = storage.stack.last()?;
+        Some((
+            frame.name.clone(),
+            frame.offset,
+            frame.info.clone(),
+            storage.suppressed_calls,
+        ))
+    });
+    let (name, offset, info, calls) = match ended {
+        Some(x) => x,
+        None => return,
+    };
+
+    let depth = info.depth + 1;
+    if info.filtered(&name) || info.out_of_range(offset) || depth_elided(&name, &info, depth) {
+        return;
+    }
+
+    let forward_backword = format!(
+        "{:<count_width$} {:<count_width$}",
+        "",
+        "",
+        count_width = info.count_width
+    );
+    let forward_backword = forward_backword + &timing_column(&info, "");
+
+    let colors = info.colors();
+
+    output_line(
+        &name,
+        OutputLevel::Trace,
+        format_args!(
+            "{} : {}{}… {} calls suppressed by without_trace{}",
+            forward_backword,
+            colors.summary,
+            info.indent(depth, TREE_INNER),
+            calls,
+            colors.reset
+        ),
+    );
+}
+
+#[cfg(all(feature = "std", not(feature = "trace-core")))]
+fn resume_trace_internal() {}
+
+/// Suppress the trace in the block.
+///
+/// The parsers called in the block are not traced, and a line which notes the number of
+/// the suppressed calls is displayed instead.
+/// The block is not a closure, so `?` and `return` work as usual.
 ///
 /// ```
 /// # use nom::character::complete::*;
 /// # use nom::IResult;
 /// # use nom_locate::LocatedSpan;
-/// # use nom_tracable::{cumulative_histogram, tracable_parser, TracableInfo};
+/// # use nom_tracable::{tracable_parser, without_trace, TracableInfo};
 /// #
 /// # type Span<'a> = LocatedSpan<&'a str, TracableInfo>;
 /// #
-/// # #[tracable_parser]
-/// # pub fn term(s: Span) -> IResult<Span, String> {
-/// #     let (s, x) = char('1')(s)?;
-/// #     Ok((s, x.to_string()))
-/// # }
-/// #
-/// # #[test]
-/// # fn test() {
-///     let ret = term(LocatedSpan::new_extra("1", TracableInfo::new()));
-///     cumulative_histogram(); // Show cumulative histogram of "1" parsing
+/// #[tracable_parser]
+/// pub fn term(s: Span) -> IResult<Span, String> {
+///     let (s, x) = char('1')(s)?;
+///     Ok((s, x.to_string()))
+/// }
 ///
-///     let ret = term(LocatedSpan::new_extra("11", TracableInfo::new()));
-///     cumulative_histogram(); // Show cumulative histogram of "11" parsing
-/// # }
+/// #[tracable_parser]
+/// pub fn terms(s: Span) -> IResult<Span, Vec<String>> {
+///     without_trace! {
+///         nom::multi::many1(term)(s)
+///     }
+/// }
+///
+/// let ret = terms(LocatedSpan::new_extra("111", TracableInfo::new()));
+/// assert_eq!(ret.unwrap().1.len(), 3);
 /// ```
-pub fn cumulative_histogram() {
-    cumulative_histogram_internal();
+#[macro_export]
+#[cfg(feature = "std")]
+macro_rules! without_trace {
+    ($($body:tt)*) => {{
+        let _suppression = $crate::suppress_trace();
+        $($body)*
+    }};
+}
+
+/// Display the summary line of the folded parser.
+#[cfg(feature = "trace-core")]
+fn fold_summary_trace(summary: &FoldSummary, info: &TracableInfo) {
+    let depth = info.depth + 1;
+    let elided = match info.options.max_depth {
+        Some(max) => depth > max,
+        None => false,
+    };
+    if info.filtered(summary.parser) || info.out_of_range(summary.offset) || elided {
+        return;
+    }
+
+    let message = match info
+        .options
+        .fold_summaries
+        .iter()
+        .find(|x| x.0 == summary.parser)
+    {
+        Some((_, Hook(hook))) => hook(summary),
+        None => format!("folded {} nested calls", summary.calls),
+    };
+
+    let forward_backword = format!(
+        "{:<count_width$} {:<count_width$}",
+        "",
+        "",
+        count_width = info.count_width
+    );
+    let forward_backword = forward_backword + &timing_column(info, "");
+
+    let colors = info.colors();
+    let (color, reset) = (colors.summary, colors.reset);
+
+    output_line(
+        summary.parser,
+        OutputLevel::Trace,
+        format_args!(
+            "{} : {}{}… {}{}",
+            forward_backword,
+            color,
+            info.indent(depth, TREE_INNER),
+            message,
+            reset
+        ),
+    );
+}
+
+/// Display the lines buffered by `TracableInfo::ring_buffer` if the root parser failed.
+#[cfg(feature = "trace-core")]
+fn flush_ring(name: &str, failed: bool) {
+    let ring = crate::TRACABLE_STORAGE.with(|storage| storage.borrow_mut().ring.take());
+    let ring = match ring {
+        Some(x) if failed => x,
+        _ => return,
+    };
+
+    if ring.dropped > 0 {
+        emit_line(
+            name,
+            OutputLevel::Debug,
+            format_args!("… {} earlier lines dropped", ring.dropped),
+        );
+    }
+    for (target, level, line) in ring.lines {
+        emit_line(&target, level, format_args!("{}", line));
+    }
+}
+
+/// Display the summary of the elided lines.
+#[cfg(feature = "trace-core")]
+fn flush_elided(name: &str, info: &TracableInfo) {
+    let elided =
+        crate::TRACABLE_STORAGE.with(|storage| std::mem::take(&mut storage.borrow_mut().elided));
+    if elided == 0 {
+        return;
+    }
+    let depth = info.options.max_depth.unwrap_or_default() + 1;
+
+    let forward_backword = format!(
+        "{:<count_width$} {:<count_width$}",
+        "",
+        "",
+        count_width = info.count_width
+    );
+    let forward_backword = forward_backword + &timing_column(info, "");
+
+    let colors = info.colors();
+    let (color, reset) = (colors.summary, colors.reset);
+
+    output_line(
+        name,
+        OutputLevel::Trace,
+        format_args!(
+            "{} : {}{}… {} nested events elided{}",
+            forward_backword,
+            color,
+            info.indent(depth, TREE_INNER),
+            elided,
+            reset
+        ),
+    );
+}
+
+/// Begin a child subtree for `TracableInfo::collapse_repeats`.
+#[cfg(feature = "trace-core")]
+fn repeats_enter(name: &str, depth: usize) {
+    crate::TRACABLE_STORAGE.with(|storage| {
+        if let Some(repeats) = &mut storage.borrow_mut().repeats {
+            repeats.enter(depth, name);
+        }
+    });
+}
+
+/// Summarize the collapsed children before the backward trace of the parser.
+#[cfg(feature = "trace-core")]
+fn repeats_close(name: &str, info: &TracableInfo, depth: usize) {
+    let repeated = crate::TRACABLE_STORAGE.with(|storage| {
+        storage
+            .borrow_mut()
+            .repeats
+            .as_mut()
+            .and_then(|x| x.close_children(depth))
+    });
+    if let Some(repeated) = repeated {
+        repeated_trace(name, info, depth + 1, repeated);
+    }
+}
+
+/// Complete a child subtree, and display it unless it is the same as the previous sibling.
+#[cfg(feature = "trace-core")]
+fn repeats_exit<T, U, V>(name: &str, info: &TracableInfo, depth: usize, input: &IResult<T, U, V>) {
+    let entry = match input {
+        Ok(_) => format!("<- {}", name),
+        Err(_) => format!("<x {}", name),
+    };
+    let ret = crate::TRACABLE_STORAGE.with(|storage| {
+        storage
+            .borrow_mut()
+            .repeats
+            .as_mut()
+            .and_then(|x| x.exit(depth, entry))
+    });
+    if let Some((repeated, lines)) = ret {
+        if repeated > 0 {
+            repeated_trace(name, info, depth, repeated);
+        }
+        for (target, level, line) in lines {
+            output_line(&target, level, format_args!("{}", line));
+        }
+    }
 }
 
-#[cfg(feature = "trace")]
-fn cumulative_histogram_internal() {
-    crate::TRACABLE_STORAGE.with(|storage| {
-        let storage = storage.borrow();
-        show_histogram("cumulative histogram", &storage.cumulative_histogram);
-    });
+/// Display the summary line of the collapsed subtrees.
+#[cfg(feature = "trace-core")]
+fn repeated_trace(name: &str, info: &TracableInfo, depth: usize, repeated: usize) {
+    let forward_backword = format!(
+        "{:<count_width$} {:<count_width$}",
+        "",
+        "",
+        count_width = info.count_width
+    );
+    let forward_backword = forward_backword + &timing_column(info, "");
+
+    let colors = info.colors();
+    let (color, reset) = (colors.summary, colors.reset);
+
+    output_line(
+        name,
+        OutputLevel::Trace,
+        format_args!(
+            "{} : {}{}… repeated {}×{}",
+            forward_backword,
+            color,
+            info.indent(depth, TREE_INNER),
+            repeated,
+            reset
+        ),
+    );
 }
 
-#[cfg(not(feature = "trace"))]
-fn cumulative_histogram_internal() {}
+/// Display the marker line if the fragment matches watchpoints, and return whether it matches.
+#[cfg(feature = "trace-core")]
+fn watch_trace<T: Tracable>(input: &T, name: &str, info: &TracableInfo) -> bool {
+    let fragment = input.fragment_bytes();
 
-#[allow(dead_code)]
-fn show_histogram(title: &str, map: &HashMap<String, usize>) {
-    let mut result = Vec::new();
-    let mut max_parser_len = "parser".len();
-    let mut max_count = 0;
-    let mut max_count_len = "count".len();
-    for (p, c) in map {
-        result.push((p, c));
-        max_parser_len = max_parser_len.max(p.len());
-        max_count = max_count.max(*c);
-        max_count_len = max_count_len.max(format!("{}", c).len());
+    let watch = info
+        .options
+        .watches
+        .iter()
+        .find(|x| fragment.starts_with(x.as_bytes()))
+        .map(|x| format!("{:?}", x))
+        .or_else(|| {
+            info.options
+                .watch_predicates
+                .iter()
+                .any(|Hook(x)| x(fragment))
+                .then(|| String::from("predicate"))
+        });
+
+    if let Some(watch) = &watch {
+        let colors = info.colors();
+        let (color, reset) = (colors.watch, colors.reset);
+
+        output_line(
+            name,
+            OutputLevel::Debug,
+            format_args!(
+                "{}*** watch : {} is entered by {} at offset {}{}",
+                color,
+                watch,
+                name,
+                input.offset(),
+                reset
+            ),
+        );
     }
+    watch.is_some()
+}
 
-    result.sort_by(|a, b| b.1.partial_cmp(a.1).unwrap());
+/// Display the bookmarks which are reached at first.
+#[cfg(feature = "trace-core")]
+fn bookmark_trace<T: Tracable>(input: &T, info: &TracableInfo) {
+    let offset = input.offset();
+    let bookmarks = &info.options.bookmarks;
 
-    let bar_length = 50;
+    loop {
+        let next = crate::TRACABLE_STORAGE.with(|storage| {
+            let mut storage = storage.borrow_mut();
+            match bookmarks.get(storage.bookmark_next) {
+                Some(x) if x.0 <= offset => {
+                    storage.bookmark_next += 1;
+                    Some(x)
+                }
+                _ => None,
+            }
+        });
+        let (bookmark_offset, name) = match next {
+            Some(x) => x,
+            None => break,
+        };
 
-    let mut lock = if cfg!(feature = "stderr") {
-        Box::new(std::io::stderr().lock()) as Box<dyn Write>
-    } else {
-        Box::new(std::io::stdout().lock()) as Box<dyn Write>
-    };
+        record_event(info, || TraceEvent {
+            kind: TraceEventKind::Bookmark,
+            depth: info.depth,
+            parser: name.clone(),
+            offset: *bookmark_offset,
+            fragment: String::new(),
+        });
 
-    writeln!(
-        lock,
-        "\n{:<parser$} | {:<bar$} | {}",
-        "parser",
-        title,
-        "count",
-        parser = max_parser_len,
-        bar = bar_length,
-    )
-    .unwrap();
+        let colors = info.colors();
+        let (color, reset) = (colors.bookmark, colors.reset);
 
-    writeln!(
-        lock,
-        "{:<parser$} | {:<bar$} | {}",
-        "-".repeat(max_parser_len),
-        "-".repeat(bar_length),
-        "-".repeat(max_count_len),
-        parser = max_parser_len,
-        bar = bar_length,
-    )
-    .unwrap();
+        output_line(
+            name,
+            OutputLevel::Debug,
+            format_args!(
+                "{}=== bookmark : {} (offset {}) ==={}",
+                color, name, bookmark_offset, reset
+            ),
+        );
+    }
+}
 
-    for (p, c) in &result {
-        let bar = *c * bar_length / max_count;
-        if bar > 0 {
-            writeln!(
-                lock,
-                "{:<parser$} | {}{} | {}",
-                p,
-                ".".repeat(bar),
-                " ".repeat(bar_length - bar),
-                c,
-                parser = max_parser_len,
-            )
-            .unwrap();
+/// Function to register the group of the parser.
+/// This is inserted by `#[tracable_parser(group = "...")]`.
+#[doc(hidden)]
+#[cfg(feature = "trace-core")]
+pub fn register_group(name: &str, group: &'static str) {
+    crate::TRACABLE_STORAGE.with(|storage| {
+        if !storage.borrow().groups.contains_key(name) {
+            storage
+                .borrow_mut()
+                .groups
+                .insert(String::from(name), group);
         }
+    });
+}
+
+/// Function to check whether the parser is entered deeper than `TracableInfo::depth_limit`.
+/// This is inserted by `#[tracable_parser]` after the forward trace.
+#[doc(hidden)]
+#[cfg(feature = "trace-core")]
+pub fn depth_limit_exceeded<T: Tracable>(input: &T) -> bool {
+    let info = input.get_tracable_info();
+    info.options.depth_limit.is_some_and(|x| info.depth > x)
+}
+
+/// Display the warning of `TracableInfo::depth_limit` when the parser is stopped.
+#[cfg(feature = "trace-core")]
+pub(crate) fn report_depth_limit<T: Tracable>(input: &T, name: &str) {
+    let info = input.get_tracable_info();
+    report_warning(
+        WarningKind::DepthLimit,
+        name,
+        input.offset(),
+        &info,
+        &format!(
+            "depth limit : {} is entered at depth {} deeper than {}",
+            name,
+            info.depth,
+            info.options.depth_limit.unwrap_or_default()
+        ),
+    );
+}
+
+/// Function to run the parser body with the memoized result.
+/// This is inserted by `#[tracable_parser(memoize)]`.
+///
+/// The successful results are cached by the parser name and the entry offset until the next root parse,
+/// and the errors are not cached.
+#[doc(hidden)]
+#[cfg(feature = "trace-core")]
+pub fn memoize<T, U, V, F>(input: T, name: &str, body: F) -> IResult<T, U, V>
+where
+    T: Tracable + nom::Slice<core::ops::RangeFrom<usize>>,
+    U: Clone + Send + 'static,
+    F: FnOnce() -> IResult<T, U, V>,
+{
+    let start = input.offset();
+    let key = (String::from(name), start);
+    let hit = crate::TRACABLE_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let hit = storage
+            .memo
+            .get(&key)
+            .and_then(|(end, x)| Some((*end, x.downcast_ref::<U>()?.clone())));
+        storage.memo_hit = hit.is_some();
+        hit
+    });
+    if let Some((end, x)) = hit {
+        return Ok((input.slice(end - start..), x));
+    }
+
+    let ret = body();
+    if let Ok((s, x)) = &ret {
+        let end = s.offset();
+        crate::TRACABLE_STORAGE.with(|storage| {
+            storage
+                .borrow_mut()
+                .memo
+                .insert(key, (end, Box::new(x.clone())));
+        });
     }
-    writeln!(lock, "").unwrap()
+    ret
 }
 
 /// Function to display forward trace.
 /// This is inserted by `#[tracable_parser]`.
-#[cfg(feature = "trace")]
+#[cfg(feature = "trace-core")]
 pub fn forward_trace<T: Tracable>(input: T, name: &str) -> (TracableInfo, T) {
+    forward_trace_args(input, name, &[])
+}
+
+/// Function to display forward trace with the other arguments of the parser.
+/// This is inserted by `#[tracable_parser(show_args)]`.
+#[cfg(feature = "trace-core")]
+pub fn forward_trace_args<T: Tracable>(
+    mut input: T,
+    name: &str,
+    args: &[(&str, &dyn std::fmt::Debug)],
+) -> (TracableInfo, T) {
     let info = input.get_tracable_info();
     let depth = info.depth;
 
-    let mut lock = if cfg!(feature = "stderr") {
-        Box::new(std::io::stderr().lock()) as Box<dyn Write>
-    } else {
-        Box::new(std::io::stdout().lock()) as Box<dyn Write>
-    };
+    // The depth is not increased, so the children are counted to follow the root
+    if depth == 0 && untraced_root() {
+        crate::TRACABLE_STORAGE.with(|storage| storage.borrow_mut().untraced += 1);
+        return (info, input);
+    }
+
+    if suppressed() {
+        crate::TRACABLE_STORAGE.with(|storage| storage.borrow_mut().suppressed_calls += 1);
+        return (info, input);
+    }
 
     if depth == 0 {
+        begin_nested_session(name);
         crate::TRACABLE_STORAGE.with(|storage| {
             storage.borrow_mut().init();
+            storage.borrow_mut().output = info.options.output;
+            storage.borrow_mut().flush = info.options.flush;
+            storage.borrow_mut().parsing = true;
+            storage.borrow_mut().session += 1;
+            if STATS {
+                storage.borrow_mut().totals.inc_parses();
+            }
+            let session = storage.borrow().session;
+            storage.borrow_mut().tag = line_tag(&info, session);
+            storage.borrow_mut().sample(info.options.sampling);
+            storage.borrow_mut().ring = info.options.ring_buffer.map(RingBuffer::new);
+            storage.borrow_mut().repeats = info.options.collapse_repeats.then(Repeats::default);
+            if info.options.failure_snippet.is_some() {
+                storage.borrow_mut().snippet = Some(snippet::FailureSnippet::new(
+                    input.fragment_bytes(),
+                    input.offset(),
+                ));
+            }
+            if info.capturing() {
+                storage.borrow_mut().trace.header = input.header();
+                storage.borrow_mut().trace.input = input.fragment_bytes().to_vec();
+            }
         });
+        open_output_file(&info);
+        if !sampled_out() {
+            open_ndjson(name, &info);
+        }
         let forward_backword = if info.forward & info.backward {
             format!(
                 "{:<count_width$} {:<count_width$}",
@@ -576,19 +4162,32 @@ pub fn forward_trace<T: Tracable>(input: T, name: &str) -> (TracableInfo, T) {
             )
         };
 
-        let control_witdh = if info.color { 11 } else { 0 };
+        let forward_backword = forward_backword + &timing_column(&info, "time");
 
-        writeln!(
-            lock,
-            "\n{} : {:<parser_width$} : {}",
-            forward_backword,
-            "parser",
-            input.header(),
-            parser_width = info.parser_width - control_witdh,
-        )
-        .unwrap();
+        if info.options.line_format.is_none() {
+            output_line(
+                name,
+                OutputLevel::Header,
+                format_args!(
+                    "{} : {:<parser_width$} : {}",
+                    forward_backword,
+                    "parser",
+                    input.header(),
+                    parser_width = info.parser_width,
+                ),
+            );
+        }
+    }
+
+    repeats_enter(name, depth);
+
+    if !info.options.bookmarks.is_empty() {
+        bookmark_trace(&input, &info);
     }
 
+    let watched = (!info.options.watches.is_empty() || !info.options.watch_predicates.is_empty())
+        && watch_trace(&input, name, &info);
+
     if info.forward {
         let forward_count = crate::TRACABLE_STORAGE.with(|storage| {
             storage.borrow_mut().inc_forward_count();
@@ -609,37 +4208,192 @@ pub fn forward_trace<T: Tracable>(input: T, name: &str) -> (TracableInfo, T) {
                 count_width = info.count_width
             )
         };
+        let forward_backword = forward_backword + &timing_column(&info, "");
 
-        let color = if info.color { "\u{001b}[1;37m" } else { "" };
-        let reset = if info.color { "\u{001b}[0m" } else { "" };
+        let colors = info.colors();
         let folded = if info.folded(name) { "+" } else { " " };
 
-        writeln!(
-            lock,
-            "{} : {:<parser_width$} : {}",
-            forward_backword,
-            format!(
-                "{}{}-> {} {}{}",
-                color,
-                " ".repeat(depth),
-                name,
-                folded,
-                reset
-            ),
-            input.format(),
-            parser_width = info.parser_width,
-        )
-        .unwrap();
+        if !info.filtered(name)
+            && !info.out_of_range(input.offset())
+            && !depth_elided(name, &info, depth)
+        {
+            let detail = info.detail(input.offset());
+            let (ret, fragment) = format_detail(input, detail);
+            input = ret;
+            let args: Vec<_> = args
+                .iter()
+                .map(|(name, value)| format!("{} = {:?}", name, value))
+                .collect();
+            let args = if args.is_empty() {
+                String::new()
+            } else {
+                format!(" : {}", args.join(", "))
+            };
+            let line = match info.options.line_format {
+                Some(Hook(f)) => f(&TraceLine {
+                    kind: TraceEventKind::Forward,
+                    forward: Some(forward_count),
+                    backward: None,
+                    depth,
+                    parser: name,
+                    offset: input.offset(),
+                    fragment: &fragment,
+                    suffix: &args,
+                    elapsed: None,
+                }),
+                None => format!(
+                    "{} : {} : {}{}",
+                    forward_backword,
+                    colors.pad_parser(
+                        colors.forward,
+                        (
+                            &format!("{}-> ", info.indent(depth, TREE_ENTER)),
+                            name,
+                            &format!(" {}", folded)
+                        ),
+                        info.parser_width,
+                        info.options.parser_colors,
+                    ),
+                    fragment,
+                    args,
+                ),
+            };
+            if info.options.failures_only {
+                crate::TRACABLE_STORAGE.with(|storage| {
+                    storage
+                        .borrow_mut()
+                        .pending
+                        .push((depth, String::from(name), line));
+                });
+            } else {
+                output_line(name, OutputLevel::Trace, format_args!("{}", line));
+            }
+        }
     }
 
+    record_event(&info, || TraceEvent {
+        kind: TraceEventKind::Forward,
+        depth,
+        parser: String::from(name),
+        offset: input.offset(),
+        fragment: input.format(),
+    });
+
     crate::TRACABLE_STORAGE.with(|storage| {
-        storage.borrow_mut().inc_histogram(name);
+        // The invocation budgets are checked by the histogram too
+        if info.counted() || !info.options.invocation_budgets.is_empty() {
+            storage.borrow_mut().inc_histogram(name);
+        }
+        if STATS {
+            storage.borrow_mut().totals.inc_forward(name);
+        }
         storage.borrow_mut().add_cumulative(name, depth);
         storage.borrow_mut().inc_cumulative();
+        let event = info
+            .capturing()
+            .then(|| storage.borrow().trace.events.len() - 1);
+        storage
+            .borrow_mut()
+            .push_frame(name, input.offset(), event, info.clone());
+        if info.counted() {
+            storage.borrow_mut().update_max_depth();
+        }
+        if info.options.show_matched {
+            // A character takes 4 bytes at most, so this is enough to display `fragment_width`
+            let bytes = input.fragment_bytes();
+            let len = bytes.len().min(info.fragment_width.saturating_mul(4));
+            if let Some(frame) = storage.borrow_mut().stack.last_mut() {
+                frame.head = bytes[..len].to_vec();
+            }
+        }
     });
 
+    debugger::debug_break(&input, name, &info, watched);
+
+    let cycle = crate::TRACABLE_STORAGE.with(|storage| storage.borrow_mut().left_recursion());
+    if let Some(cycle) = cycle {
+        report_warning(
+            WarningKind::LeftRecursion,
+            name,
+            input.offset(),
+            &info,
+            &format!("possible left recursion : {}", cycle),
+        );
+    }
+
+    cancel::check_cancellation(name, input.offset(), &info);
+
+    if let Some((_, limit)) = info.options.invocation_budgets.iter().find(|x| x.0 == name) {
+        let count =
+            crate::TRACABLE_STORAGE.with(|storage| storage.borrow().histogram.get(name).copied());
+        if count == Some(limit + 1) {
+            report_warning(
+                WarningKind::InvocationBudget,
+                name,
+                input.offset(),
+                &info,
+                &format!(
+                    "invocation budget : {} is called more than {} times",
+                    name, limit
+                ),
+            );
+        }
+    }
+
+    // The visits are counted for `hotspots` even if the watchdog is disabled
+    let offset = input.offset();
+    let visits = if STATS || info.options.watchdog.is_some() {
+        crate::TRACABLE_STORAGE.with(|storage| storage.borrow_mut().inc_visits(name, offset))
+    } else {
+        0
+    };
+    if let Some(limit) = info.options.watchdog {
+        if visits == limit + 1 {
+            report_warning(
+                WarningKind::NonProgress,
+                name,
+                offset,
+                &info,
+                &format!(
+                    "non-progress : {} is entered at offset {} more than {} times",
+                    name, offset, limit
+                ),
+            );
+        }
+    }
+
+    if let Some(budget) = info.options.time_budget {
+        let timed_out = crate::TRACABLE_STORAGE.with(|storage| {
+            let mut storage = storage.borrow_mut();
+            let elapsed = storage.start.map(|x| x.elapsed()).unwrap_or_default();
+            if !storage.timed_out && elapsed > budget {
+                storage.timed_out = true;
+                true
+            } else {
+                false
+            }
+        });
+        if timed_out {
+            report_warning(
+                WarningKind::TimeBudget,
+                name,
+                input.offset(),
+                &info,
+                &format!("timeout : time budget {:?} is exceeded", budget),
+            );
+            crate::TRACABLE_STORAGE.with(|storage| {
+                let storage = storage.borrow();
+                show_histogram(
+                    "partial histogram",
+                    &storage.histogram,
+                    Some(&storage.results),
+                );
+            });
+        }
+    }
+
     let input = if info.folded(name) {
-        let info = info.forward(false).backward(false).custom(false);
+        let info = info.clone().forward(false).backward(false).custom(false);
         input.set_tracable_info(info)
     } else {
         input
@@ -649,22 +4403,161 @@ pub fn forward_trace<T: Tracable>(input: T, name: &str) -> (TracableInfo, T) {
     (info, input)
 }
 
-/// Function to display backward trace.
-/// This is inserted by `#[tracable_parser]`.
-#[cfg(feature = "trace")]
+/// Function to display backward trace without the details of the error.
+#[cfg(feature = "trace-core")]
 pub fn backward_trace<T: Tracable, U, V>(
     input: IResult<T, U, V>,
     name: &str,
     info: TracableInfo,
+) -> IResult<T, U, V> {
+    backward_trace_detail(input, name, info, None)
+}
+
+/// Function to display backward trace with the details of the error by `detail`.
+/// This is inserted by `#[tracable_parser]`.
+#[cfg(feature = "trace-core")]
+pub fn backward_trace_detail<T: Tracable, U, V>(
+    input: IResult<T, U, V>,
+    name: &str,
+    info: TracableInfo,
+    detail: Option<ErrorDetail<V>>,
+) -> IResult<T, U, V> {
+    backward_trace_inner(input, name, info, detail, None)
+}
+
+/// Function to display backward trace with the parsed value on success.
+/// This is inserted by `#[tracable_parser(show_output)]`.
+#[cfg(feature = "trace-core")]
+pub fn backward_trace_output<T: Tracable, U: std::fmt::Debug, V>(
+    input: IResult<T, U, V>,
+    name: &str,
+    info: TracableInfo,
+    detail: Option<ErrorDetail<V>>,
+) -> IResult<T, U, V> {
+    backward_trace_inner(input, name, info, detail, Some(|x| format!("{:?}", x)))
+}
+
+#[cfg(feature = "trace-core")]
+fn backward_trace_inner<T: Tracable, U, V>(
+    input: IResult<T, U, V>,
+    name: &str,
+    info: TracableInfo,
+    detail: Option<ErrorDetail<V>>,
+    output: Option<fn(&U) -> String>,
 ) -> IResult<T, U, V> {
     let depth = info.depth;
+    let memoized =
+        crate::TRACABLE_STORAGE.with(|storage| std::mem::take(&mut storage.borrow_mut().memo_hit));
 
-    crate::TRACABLE_STORAGE.with(|storage| {
+    // The root parse was not traced by `set_enabled`
+    if depth == 0
+        && crate::TRACABLE_STORAGE.with(|storage| {
+            let mut storage = storage.borrow_mut();
+            if storage.untraced > 0 {
+                storage.untraced -= 1;
+                return true;
+            }
+            storage.stack.is_empty()
+        })
+    {
+        return input;
+    }
+
+    if suppressed() {
+        return input;
+    }
+
+    let (elapsed, entry_offset, head, cnt) = crate::TRACABLE_STORAGE.with(|storage| {
         let cnt = *storage.borrow_mut().get_cumulative(name, depth).unwrap();
-        storage.borrow_mut().inc_cumulative_histogram(name, cnt);
+        if STATS {
+            storage.borrow_mut().inc_cumulative_histogram(name, cnt);
+        }
+        if info.counted() {
+            storage.borrow_mut().inc_results(name, input.is_ok());
+        }
+        #[cfg(feature = "tracing")]
+        match &input {
+            Ok(_) => tracing::trace!(parser = name, result = "ok"),
+            Err(_) => tracing::trace!(parser = name, result = "err"),
+        }
+        let (elapsed, entry_offset, head) = storage
+            .borrow_mut()
+            .pop_frame()
+            .map(|x| (x.start.elapsed(), x.offset, x.head))
+            .unwrap_or_default();
+        if STATS {
+            storage
+                .borrow_mut()
+                .totals
+                .inc_backward(name, input.is_ok(), elapsed);
+        }
+        if input.is_err() {
+            let mut storage = storage.borrow_mut();
+            if STATS {
+                let wasted = storage.wasted.entry(String::from(name)).or_default();
+                wasted.0 += cnt;
+                wasted.1 += elapsed;
+            }
+            let storage = &mut *storage;
+            if let Some(snippet) = storage.snippet.as_mut() {
+                let stack = &storage.stack;
+                snippet.record(entry_offset, || {
+                    let chain = stack.iter().map(|x| x.name.clone());
+                    chain.chain(core::iter::once(String::from(name))).collect()
+                });
+            }
+            if let Some(caller) = storage.stack.last() {
+                let key = (caller.name.clone(), String::from(name));
+                if let Some(edge) = storage.call_edges.get_mut(&key) {
+                    edge.1 += 1;
+                }
+            }
+        }
+        // The slowest parsers of the summary are found by the timing
+        if info.options.timing || info.options.summary {
+            storage.borrow_mut().add_timing(name, elapsed);
+        }
+        if let (Some((_, region)), Err(_)) = (info.options.escalation, &input) {
+            *storage
+                .borrow_mut()
+                .region_failures
+                .entry(entry_offset / region)
+                .or_insert(0) += 1;
+        }
+        (elapsed, entry_offset, head, cnt)
+    });
+
+    repeats_close(name, &info, depth);
+
+    record_event(&info, || {
+        let (kind, offset, fragment) = match &input {
+            Ok((s, _)) => (TraceEventKind::Ok, s.offset(), s.format()),
+            Err(_) => (TraceEventKind::Err, entry_offset, String::new()),
+        };
+        TraceEvent {
+            kind,
+            depth,
+            parser: String::from(name),
+            offset,
+            fragment,
+        }
     });
 
-    if info.backward {
+    if info.folded(name) && info.backward {
+        let summary = FoldSummary {
+            parser: name,
+            offset: entry_offset,
+            consumed: match &input {
+                Ok((s, _)) => s.offset().saturating_sub(entry_offset),
+                Err(_) => 0,
+            },
+            calls: cnt.saturating_sub(1),
+            success: input.is_ok(),
+        };
+        fold_summary_trace(&summary, &info);
+    }
+
+    let ret = if info.backward {
         let backward_count = crate::TRACABLE_STORAGE.with(|storage| {
             storage.borrow_mut().inc_backward_count();
             storage.borrow().get_backward_count()
@@ -684,79 +4577,229 @@ pub fn backward_trace<T: Tracable, U, V>(
                 count_width = info.count_width
             )
         };
+        let forward_backword = forward_backword + &timing_column(&info, &format!("{:?}", elapsed));
 
-        let color_ok = if info.color { "\u{001b}[1;32m" } else { "" };
-        let color_err = if info.color { "\u{001b}[1;31m" } else { "" };
-        let reset = if info.color { "\u{001b}[0m" } else { "" };
+        let colors = info.colors();
         let folded = if info.folded(name) { "+" } else { " " };
-
-        let mut lock = if cfg!(feature = "stderr") {
-            Box::new(std::io::stderr().lock()) as Box<dyn Write>
-        } else {
-            Box::new(std::io::stdout().lock()) as Box<dyn Write>
-        };
+        let error_detail = detail;
+        let detail = info.detail(entry_offset);
+        // The duration is already displayed if `timing` is enabled
+        let duration =
+            if detail == Detail::Escalated && !info.options.timing && !info.options.snapshot {
+                format!(" : {:?}", elapsed)
+            } else {
+                String::new()
+            };
 
         match input {
+            x if info.filtered(name)
+                || info.out_of_range(entry_offset)
+                || depth_elided(name, &info, depth) =>
+            {
+                x
+            }
+            Ok((s, x)) if info.options.failures_only => {
+                crate::TRACABLE_STORAGE.with(|storage| {
+                    let mut storage = storage.borrow_mut();
+                    if storage.pending.last().map(|x| x.0) == Some(depth) {
+                        storage.pending.pop();
+                    }
+                });
+                Ok((s, x))
+            }
             Ok((s, x)) => {
-                writeln!(
-                    lock,
-                    "{} : {:<parser_width$} : {}",
-                    forward_backword,
-                    format!(
-                        "{}{}<- {} {}{}",
-                        color_ok,
-                        " ".repeat(depth),
-                        name,
-                        folded,
-                        reset
-                    ),
-                    s.format(),
-                    parser_width = info.parser_width,
-                )
-                .unwrap();
-
-                let s = if info.folded(name) {
-                    let info = s
-                        .get_tracable_info()
-                        .forward(info.forward)
-                        .backward(info.backward)
-                        .custom(info.custom);
-                    s.set_tracable_info(info)
+                // Parsers consuming nothing are highlighted because they may hang `many0`
+                let consumed = if info.options.show_consumed {
+                    let consumed = s.offset().saturating_sub(entry_offset);
+                    let color = if consumed == 0 { colors.warning } else { "" };
+                    format!(" : {}consumed {}{}", color, consumed, colors.reset)
                 } else {
-                    s
+                    String::new()
                 };
-
-                Ok((s.dec_depth(), x))
+                let (s, fragment) = format_detail(s, detail);
+                let fragment = if info.options.show_matched && detail != Detail::Minimal {
+                    let consumed = s.offset().saturating_sub(entry_offset);
+                    let location = fragment.split(" : ").next().unwrap_or("");
+                    let matched = format_matched(&head[..consumed.min(head.len())], &info);
+                    format!("{} : {}", location, matched)
+                } else {
+                    fragment
+                };
+                let value = match output {
+                    Some(f) => {
+                        let value: String = f(&x).chars().take(info.options.output_width).collect();
+                        format!(" : {}", value)
+                    }
+                    None => String::new(),
+                };
+                let memoized = if memoized {
+                    format!(" : {}memoized{}", colors.bookmark, colors.reset)
+                } else {
+                    String::new()
+                };
+                let line = match info.options.line_format {
+                    Some(Hook(f)) => f(&TraceLine {
+                        kind: TraceEventKind::Ok,
+                        forward: None,
+                        backward: Some(backward_count),
+                        depth,
+                        parser: name,
+                        offset: s.offset(),
+                        fragment: &fragment,
+                        suffix: &format!("{}{}{}{}", consumed, memoized, value, duration),
+                        elapsed: Some(elapsed),
+                    }),
+                    None => format!(
+                        "{} : {} : {}{}{}{}{}",
+                        forward_backword,
+                        colors.pad_parser(
+                            colors.ok,
+                            (
+                                &format!("{}<- ", info.indent(depth, TREE_EXIT)),
+                                name,
+                                &format!(" {}", folded)
+                            ),
+                            info.parser_width,
+                            info.options.parser_colors,
+                        ),
+                        fragment,
+                        consumed,
+                        memoized,
+                        value,
+                        duration,
+                    ),
+                };
+                output_line(name, OutputLevel::Trace, format_args!("{}", line));
+                Ok((s, x))
             }
             Err(x) => {
-                writeln!(
-                    lock,
-                    "{} : {:<parser_width$}",
-                    forward_backword,
-                    format!(
-                        "{}{}<- {} {}{}",
-                        color_err,
-                        " ".repeat(depth),
-                        name,
-                        folded,
-                        reset
+                if info.options.failures_only {
+                    flush_pending();
+                }
+                // `Err::Error` is usual in `alt`, so only the others are labeled by default
+                let (color, arrow, kind) = match &x {
+                    nom::Err::Error(_) if detail == Detail::Escalated => {
+                        (colors.err, "<-", String::from(" : error"))
+                    }
+                    nom::Err::Error(_) => (colors.err, "<-", String::new()),
+                    nom::Err::Failure(_) => (colors.failure, "<!", String::from(" : failure")),
+                    nom::Err::Incomplete(nom::Needed::Size(n)) => (
+                        colors.incomplete,
+                        "<?",
+                        format!(" : incomplete (needed {})", n),
                     ),
-                    parser_width = info.parser_width,
-                )
-                .unwrap();
+                    nom::Err::Incomplete(nom::Needed::Unknown) => {
+                        (colors.incomplete, "<?", String::from(" : incomplete"))
+                    }
+                };
+                let mut error = String::new();
+                if let (nom::Err::Error(e) | nom::Err::Failure(e), Some(f)) = (&x, error_detail) {
+                    error.push_str(" : ");
+                    let _ = f(e, &mut error);
+                }
+                let line = match info.options.line_format {
+                    Some(Hook(f)) => f(&TraceLine {
+                        kind: TraceEventKind::Err,
+                        forward: None,
+                        backward: Some(backward_count),
+                        depth,
+                        parser: name,
+                        offset: entry_offset,
+                        fragment: "",
+                        suffix: &format!("{}{}{}", kind, error, duration),
+                        elapsed: Some(elapsed),
+                    }),
+                    None => format!(
+                        "{} : {}{}{}{}",
+                        forward_backword,
+                        colors.pad_parser(
+                            color,
+                            (
+                                &format!("{}{} ", info.indent(depth, TREE_EXIT), arrow),
+                                name,
+                                &format!(" {}", folded),
+                            ),
+                            info.parser_width,
+                            info.options.parser_colors,
+                        ),
+                        kind,
+                        error,
+                        duration,
+                    ),
+                };
+                output_line(name, OutputLevel::Trace, format_args!("{}", line));
                 Err(x)
             }
         }
     } else {
         input
+    };
+
+    repeats_exit(name, &info, depth, &ret);
+
+    let ret = match ret {
+        Ok((s, x)) => {
+            let s = if info.folded(name) {
+                let info = s
+                    .get_tracable_info()
+                    .forward(info.forward)
+                    .backward(info.backward)
+                    .custom(info.custom);
+                s.set_tracable_info(info)
+            } else {
+                s
+            };
+            Ok((s.dec_depth(), x))
+        }
+        Err(x) => Err(x),
+    };
+
+    if depth == 0 {
+        crate::TRACABLE_STORAGE.with(|storage| storage.borrow_mut().repeats = None);
+        flush_elided(name, &info);
+        close_ndjson();
+        flush_ring(name, ret.is_err());
+        if let (Some(context), Err(_)) = (info.options.failure_snippet, &ret) {
+            snippet::show_failure_snippet(name, &info, context);
+        }
+        if info.options.summary {
+            summary::show_summary(name, &info);
+        }
+        crate::TRACABLE_STORAGE.with(|storage| storage.borrow_mut().parsing = false);
+        flush_lines();
+    }
+
+    if depth == 0 && info.timing_shown() && !sampled_out() {
+        crate::TRACABLE_STORAGE.with(|storage| {
+            show_timing(&storage.borrow().timing);
+        });
+    }
+
+    if depth == 0 {
+        end_nested_session(name);
+        let _ = FileWriter.flush();
     }
+
+    ret
 }
 
 /// Function to display custom trace.
-#[cfg(feature = "trace")]
+#[cfg(feature = "trace-core")]
 pub fn custom_trace<T: Tracable>(input: &T, name: &str, message: &str, color: &str) {
     let info = input.get_tracable_info();
 
+    if (info.depth == 0 && untraced_root()) || suppressed() {
+        return;
+    }
+
+    record_event(&info, || TraceEvent {
+        kind: TraceEventKind::Custom,
+        depth: info.depth,
+        parser: String::from(name),
+        offset: input.offset(),
+        fragment: String::from(message),
+    });
+
     if info.custom {
         let depth = info.depth;
         let forward_backword = format!(
@@ -765,24 +4808,38 @@ pub fn custom_trace<T: Tracable>(input: &T, name: &str, message: &str, color: &s
             "",
             count_width = info.count_width
         );
+        let forward_backword = forward_backword + &timing_column(&info, "");
 
-        let color = if info.color { color } else { "" };
-        let reset = if info.color { "\u{001b}[0m" } else { "" };
+        let colors = info.colors();
+        let color = if info.color_enabled() { color } else { "" };
 
-        let mut lock = if cfg!(feature = "stderr") {
-            Box::new(std::io::stderr().lock()) as Box<dyn Write>
-        } else {
-            Box::new(std::io::stdout().lock()) as Box<dyn Write>
-        };
+        if depth_elided(name, &info, depth) {
+            return;
+        }
 
-        writeln!(
-            lock,
-            "{} : {:<parser_width$} : {}",
-            forward_backword,
-            format!("{}{}   {}{}", color, " ".repeat(depth), name, reset),
-            message,
-            parser_width = info.parser_width,
-        )
-        .unwrap();
+        let line = match info.options.line_format {
+            Some(Hook(f)) => f(&TraceLine {
+                kind: TraceEventKind::Custom,
+                forward: None,
+                backward: None,
+                depth,
+                parser: name,
+                offset: input.offset(),
+                fragment: message,
+                suffix: "",
+                elapsed: None,
+            }),
+            None => format!(
+                "{} : {} : {}",
+                forward_backword,
+                colors.pad(
+                    color,
+                    &format!("{}   {}", info.indent(depth, TREE_INNER), name),
+                    info.parser_width
+                ),
+                message,
+            ),
+        };
+        output_line(name, OutputLevel::Debug, format_args!("{}", line));
     }
 }
@@ -0,0 +1,76 @@
+use crate::{IncludedTrace, Trace, TraceEvent, TraceEventKind};
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+pub(crate) fn escape(x: &str) -> String {
+    let mut ret = String::with_capacity(x.len() + 2);
+    ret.push('"');
+    for c in x.chars() {
+        match c {
+            '"' => ret.push_str("\\\""),
+            '\\' => ret.push_str("\\\\"),
+            '\n' => ret.push_str("\\n"),
+            '\r' => ret.push_str("\\r"),
+            '\t' => ret.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(ret, "\\u{:04x}", c as u32);
+            }
+            _ => ret.push(c),
+        }
+    }
+    ret.push('"');
+    ret
+}
+
+impl TraceEventKind {
+    /// Get the name of the kind used by the machine-readable formats.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TraceEventKind::Forward => "forward",
+            TraceEventKind::Ok => "ok",
+            TraceEventKind::Err => "err",
+            TraceEventKind::Custom => "custom",
+            TraceEventKind::Bookmark => "bookmark",
+        }
+    }
+}
+
+impl TraceEvent {
+    /// Format the event as a JSON object in a line.
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"kind\":\"{}\",\"depth\":{},\"parser\":{},\"offset\":{},\"fragment\":{}}}",
+            self.kind.as_str(),
+            self.depth,
+            escape(&self.parser),
+            self.offset,
+            escape(&self.fragment)
+        )
+    }
+}
+
+fn write_include<W: Write>(w: &mut W, include: &IncludedTrace) -> io::Result<()> {
+    let source = escape(&include.source);
+    writeln!(w, "{{\"kind\":\"include_start\",\"source\":{}}}", source)?;
+    include.trace.write_ndjson(w)?;
+    writeln!(w, "{{\"kind\":\"include_end\",\"source\":{}}}", source)
+}
+
+impl Trace {
+    /// Write the events as NDJSON (a JSON object per line).
+    ///
+    /// The events of nested contexts are enclosed by `include_start`/`include_end` lines.
+    pub fn write_ndjson<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let mut includes = self.includes.iter().peekable();
+        for (position, event) in self.events.iter().enumerate() {
+            while let Some(include) = includes.next_if(|x| x.position <= position) {
+                write_include(w, include)?;
+            }
+            writeln!(w, "{}", event.to_json())?;
+        }
+        for include in includes {
+            write_include(w, include)?;
+        }
+        Ok(())
+    }
+}
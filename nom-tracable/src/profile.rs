@@ -0,0 +1,272 @@
+use crate::{ndjson::escape, writer};
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::time::Duration;
+
+const PROFILE_HEADER: &str = "# nom-tracable profile v1";
+
+/// Version of the JSON schema by `Profile::to_json`.
+const JSON_VERSION: usize = 1;
+
+/// Struct to have the aggregated profile of a parser.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ProfileEntry {
+    pub count: usize,
+    /// Total elapsed time. This is zero if `TracableInfo::timing` is disabled.
+    pub total: Duration,
+}
+
+/// Struct to have the aggregated per-parser profile of a parse.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Profile {
+    pub parsers: BTreeMap<String, ProfileEntry>,
+}
+
+/// Struct to have a regression found by `Profile::compare`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Regression {
+    pub parser: String,
+    /// `"count"` or `"time"`.
+    pub metric: &'static str,
+    /// Value of the baseline. The time is nanoseconds.
+    pub baseline: u128,
+    /// Value of the current profile. The time is nanoseconds.
+    pub current: u128,
+}
+
+impl Profile {
+    /// Save the profile to the specified path.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = io::BufWriter::new(std::fs::File::create(path)?);
+        writeln!(file, "{}", PROFILE_HEADER)?;
+        for (name, entry) in &self.parsers {
+            writeln!(
+                file,
+                "{}\t{}\t{}",
+                name,
+                entry.count,
+                entry.total.as_nanos()
+            )?;
+        }
+        file.flush()
+    }
+
+    /// Load the profile from the specified path.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = io::BufReader::new(std::fs::File::open(path)?);
+        let invalid = |line: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid profile line: {}", line),
+            )
+        };
+
+        let mut ret = Profile::default();
+        for line in file.lines() {
+            let line = line?;
+            if line.starts_with('#') || line.is_empty() {
+                continue;
+            }
+            let mut columns = line.split('\t');
+            let name = columns.next().ok_or_else(|| invalid(&line))?;
+            let count = columns
+                .next()
+                .and_then(|x| x.parse().ok())
+                .ok_or_else(|| invalid(&line))?;
+            let total: u64 = columns
+                .next()
+                .and_then(|x| x.parse().ok())
+                .ok_or_else(|| invalid(&line))?;
+            ret.parsers.insert(
+                String::from(name),
+                ProfileEntry {
+                    count,
+                    total: Duration::from_nanos(total),
+                },
+            );
+        }
+        Ok(ret)
+    }
+
+    /// Format the profile as JSON.
+    ///
+    /// The schema is stable, and the version is incremented if it is changed:
+    ///
+    /// ```json
+    /// {
+    ///   "version": 1,
+    ///   "parsers": [
+    ///     { "name": "expr", "count": 3, "total_ns": 1200, "mean_ns": 400 }
+    ///   ]
+    /// }
+    /// ```
+    ///
+    /// The parsers are sorted by name. The times are zero if `TracableInfo::timing` is disabled.
+    pub fn to_json(&self) -> String {
+        let parsers: Vec<_> = self
+            .parsers
+            .iter()
+            .map(|(name, entry)| {
+                let total = entry.total.as_nanos();
+                let mean = match entry.count {
+                    0 => 0,
+                    x => total / x as u128,
+                };
+                format!(
+                    "{{\"name\":{},\"count\":{},\"total_ns\":{},\"mean_ns\":{}}}",
+                    escape(name),
+                    entry.count,
+                    total,
+                    mean
+                )
+            })
+            .collect();
+        format!(
+            "{{\"version\":{},\"parsers\":[{}]}}",
+            JSON_VERSION,
+            parsers.join(",")
+        )
+    }
+
+    /// Save the profile as JSON by `to_json` to the specified path.
+    pub fn save_json<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = io::BufWriter::new(std::fs::File::create(path)?);
+        writeln!(file, "{}", self.to_json())?;
+        file.flush()
+    }
+
+    /// Compare the profile against the baseline.
+    ///
+    /// The count or time which exceeds the baseline by more than `threshold` (e.g. `0.1` is 10%) is a regression.
+    /// The time is compared only if both profiles are measured by `TracableInfo::timing`.
+    pub fn compare(&self, baseline: &Profile, threshold: f64) -> Vec<Regression> {
+        let mut ret = Vec::new();
+        for (name, current) in &self.parsers {
+            let base = baseline.parsers.get(name).copied().unwrap_or_default();
+
+            let exceeded =
+                |base: u128, current: u128| current as f64 > base as f64 * (1.0 + threshold);
+
+            if exceeded(base.count as u128, current.count as u128) {
+                ret.push(Regression {
+                    parser: name.clone(),
+                    metric: "count",
+                    baseline: base.count as u128,
+                    current: current.count as u128,
+                });
+            }
+
+            let (base_time, current_time) = (base.total.as_nanos(), current.total.as_nanos());
+            if base_time != 0 && current_time != 0 && exceeded(base_time, current_time) {
+                ret.push(Regression {
+                    parser: name.clone(),
+                    metric: "time",
+                    baseline: base_time,
+                    current: current_time,
+                });
+            }
+        }
+        ret
+    }
+
+    /// Compare the profile against the baseline, and show the regression report.
+    pub fn show_comparison(&self, baseline: &Profile, threshold: f64) -> Vec<Regression> {
+        let regressions = self.compare(baseline, threshold);
+
+        let max_parser_len = regressions
+            .iter()
+            .map(|x| x.parser.len())
+            .max()
+            .unwrap_or(0)
+            .max("parser".len());
+
+        let mut lock = writer();
+
+        writeln!(
+            lock,
+            "\n{:<parser$} | {:<6} | {:>14} | {:>14} | change",
+            "parser",
+            "metric",
+            "baseline",
+            "current",
+            parser = max_parser_len,
+        )
+        .unwrap();
+
+        writeln!(
+            lock,
+            "{} | {} | {} | {} | {}",
+            "-".repeat(max_parser_len),
+            "-".repeat(6),
+            "-".repeat(14),
+            "-".repeat(14),
+            "-".repeat(8),
+        )
+        .unwrap();
+
+        for x in &regressions {
+            let (baseline, current) = if x.metric == "time" {
+                (
+                    format!("{:?}", Duration::from_nanos(x.baseline as u64)),
+                    format!("{:?}", Duration::from_nanos(x.current as u64)),
+                )
+            } else {
+                (format!("{}", x.baseline), format!("{}", x.current))
+            };
+            let change = if x.baseline == 0 {
+                String::from("new")
+            } else {
+                format!(
+                    "{:+.1}%",
+                    (x.current as f64 / x.baseline as f64 - 1.0) * 100.0
+                )
+            };
+            writeln!(
+                lock,
+                "{:<parser$} | {:<6} | {:>14} | {:>14} | {}",
+                x.parser,
+                x.metric,
+                baseline,
+                current,
+                change,
+                parser = max_parser_len,
+            )
+            .unwrap();
+        }
+        writeln!(lock).unwrap();
+
+        regressions
+    }
+}
+
+/// Get the aggregated per-parser profile of the last parse.
+///
+/// The statistics information is reset at each parser call like histogram.
+pub fn profile() -> Profile {
+    profile_internal()
+}
+
+#[cfg(feature = "trace-core")]
+fn profile_internal() -> Profile {
+    crate::TRACABLE_STORAGE.with(|storage| {
+        let storage = storage.borrow();
+        let mut ret = Profile::default();
+        for (name, count) in &storage.histogram {
+            let total = storage.timing.get(name).map(|x| x.1).unwrap_or_default();
+            ret.parsers.insert(
+                name.clone(),
+                ProfileEntry {
+                    count: *count,
+                    total,
+                },
+            );
+        }
+        ret
+    })
+}
+
+#[cfg(not(feature = "trace-core"))]
+fn profile_internal() -> Profile {
+    Profile::default()
+}
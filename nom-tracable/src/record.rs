@@ -0,0 +1,171 @@
+use crate::{golden::unescape, ndjson::escape, IncludedTrace, Trace, TraceEvent, TraceEventKind};
+use std::fmt::Write as _;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+const RECORD_HEADER: &str = "# nom-tracable trace v1";
+
+fn parse_kind(x: &str) -> Option<TraceEventKind> {
+    match x {
+        "forward" => Some(TraceEventKind::Forward),
+        "ok" => Some(TraceEventKind::Ok),
+        "err" => Some(TraceEventKind::Err),
+        "custom" => Some(TraceEventKind::Custom),
+        "bookmark" => Some(TraceEventKind::Bookmark),
+        _ => None,
+    }
+}
+
+fn write_trace<W: Write>(w: &mut W, trace: &Trace) -> io::Result<()> {
+    let input = trace.input.iter().fold(String::new(), |mut x, b| {
+        let _ = write!(x, "{:02x}", b);
+        x
+    });
+    writeln!(w, "header\t{}", escape(&trace.header))?;
+    writeln!(w, "input\t{}", input)?;
+
+    let mut includes = trace.includes.iter().peekable();
+    for (position, event) in trace.events.iter().enumerate() {
+        while let Some(include) = includes.next_if(|x| x.position <= position) {
+            write_include(w, include)?;
+        }
+        writeln!(
+            w,
+            "{}\t{}\t{}\t{}\t{}",
+            event.kind.as_str(),
+            event.depth,
+            escape(&event.parser),
+            event.offset,
+            escape(&event.fragment)
+        )?;
+    }
+    for include in includes {
+        write_include(w, include)?;
+    }
+    Ok(())
+}
+
+fn write_include<W: Write>(w: &mut W, include: &IncludedTrace) -> io::Result<()> {
+    writeln!(
+        w,
+        "include\t{}\t{}",
+        include.position,
+        escape(&include.source)
+    )?;
+    write_trace(w, &include.trace)?;
+    writeln!(w, "end")
+}
+
+impl Trace {
+    /// Save the captured trace to the specified path, so it can be loaded and rendered later by `Trace::load`.
+    ///
+    /// The file is a line per event, and keeps the header, the input and the nested contexts.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = io::BufWriter::new(std::fs::File::create(path)?);
+        writeln!(file, "{}", RECORD_HEADER)?;
+        write_trace(&mut file, self)?;
+        file.flush()
+    }
+
+    /// Load the trace saved by `Trace::save`.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = io::BufReader::new(std::fs::File::open(path)?);
+        let invalid = |line: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid trace line: {}", line),
+            )
+        };
+
+        // The traces of the nested contexts in progress with the positions and the sources
+        let mut stack = vec![(Trace::default(), 0, String::new())];
+        for line in file.lines() {
+            let line = line?;
+            if line.starts_with('#') || line.is_empty() {
+                continue;
+            }
+            let mut columns = line.split('\t');
+            let nested = stack.len() > 1;
+            let trace = match stack.last_mut() {
+                Some(x) => &mut x.0,
+                None => return Err(invalid(&line)),
+            };
+            match columns.next() {
+                Some("header") => {
+                    trace.header = columns
+                        .next()
+                        .and_then(unescape)
+                        .ok_or_else(|| invalid(&line))?;
+                }
+                Some("input") => {
+                    let input = columns.next().ok_or_else(|| invalid(&line))?;
+                    trace.input = (0..input.len())
+                        .step_by(2)
+                        .map(|i| {
+                            input
+                                .get(i..i + 2)
+                                .and_then(|x| u8::from_str_radix(x, 16).ok())
+                        })
+                        .collect::<Option<_>>()
+                        .ok_or_else(|| invalid(&line))?;
+                }
+                Some("include") => {
+                    let position = columns
+                        .next()
+                        .and_then(|x| x.parse().ok())
+                        .ok_or_else(|| invalid(&line))?;
+                    let source = columns
+                        .next()
+                        .and_then(unescape)
+                        .ok_or_else(|| invalid(&line))?;
+                    stack.push((Trace::default(), position, source));
+                }
+                Some("end") if nested => {
+                    let (trace, position, source) = stack.pop().unwrap();
+                    if let Some(x) = stack.last_mut() {
+                        x.0.includes.push(IncludedTrace {
+                            source,
+                            position,
+                            trace,
+                        });
+                    }
+                }
+                Some(kind) => {
+                    let kind = parse_kind(kind).ok_or_else(|| invalid(&line))?;
+                    let depth = columns
+                        .next()
+                        .and_then(|x| x.parse().ok())
+                        .ok_or_else(|| invalid(&line))?;
+                    let parser = columns
+                        .next()
+                        .and_then(unescape)
+                        .ok_or_else(|| invalid(&line))?;
+                    let offset = columns
+                        .next()
+                        .and_then(|x| x.parse().ok())
+                        .ok_or_else(|| invalid(&line))?;
+                    let fragment = columns
+                        .next()
+                        .and_then(unescape)
+                        .ok_or_else(|| invalid(&line))?;
+                    trace.events.push(TraceEvent {
+                        kind,
+                        depth,
+                        parser,
+                        offset,
+                        fragment,
+                    });
+                }
+                None => return Err(invalid(&line)),
+            }
+        }
+
+        match stack.pop() {
+            Some((trace, _, _)) if stack.is_empty() => Ok(trace),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "unterminated nested trace",
+            )),
+        }
+    }
+}
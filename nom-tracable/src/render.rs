@@ -0,0 +1,160 @@
+use crate::{ColorScheme, Trace, TraceEvent, TraceEventKind, TraceLine};
+
+/// Options of the text rendering of captured traces.
+#[derive(Clone, Debug)]
+pub struct RenderOptions {
+    pub color: bool,
+    pub color_scheme: ColorScheme,
+    pub count_width: usize,
+    pub parser_width: usize,
+    /// Parsers rendered by `render` and `render_page`. If `None`, all parsers are rendered.
+    pub include: Option<Vec<String>>,
+    /// Parsers not rendered by `render` and `render_page`.
+    pub exclude: Vec<String>,
+    /// The formatter of the lines like `TracableInfo::line_format`.
+    /// The index of the event is given as the forward or backward count.
+    pub line_format: Option<fn(&TraceLine) -> String>,
+}
+
+// `line_format` is compared by address like the hooks of `TracableInfo`
+impl PartialEq for RenderOptions {
+    fn eq(&self, other: &Self) -> bool {
+        self.color == other.color
+            && self.color_scheme == other.color_scheme
+            && self.count_width == other.count_width
+            && self.parser_width == other.parser_width
+            && self.include == other.include
+            && self.exclude == other.exclude
+            && self.line_format.map(|x| x as usize) == other.line_format.map(|x| x as usize)
+    }
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            color: false,
+            color_scheme: ColorScheme::DEFAULT,
+            count_width: 10,
+            parser_width: 96,
+            include: None,
+            exclude: Vec::new(),
+            line_format: None,
+        }
+    }
+}
+
+/// Render a line of the event like the live trace.
+/// The first column is the index of the event in the trace.
+pub fn render_event(index: usize, event: &TraceEvent, options: &RenderOptions) -> String {
+    let colors = if options.color {
+        options.color_scheme
+    } else {
+        ColorScheme::NONE
+    };
+    let (color, arrow) = match event.kind {
+        TraceEventKind::Forward => (colors.forward, "-> "),
+        TraceEventKind::Ok => (colors.ok, "<- "),
+        TraceEventKind::Err => (colors.err, "<- "),
+        TraceEventKind::Custom => (colors.custom, "   "),
+        TraceEventKind::Bookmark => (colors.bookmark, "=== "),
+    };
+    let parser = format!(
+        "{}{}{}{}{}",
+        color,
+        " ".repeat(event.depth),
+        arrow,
+        event.parser,
+        colors.reset
+    );
+    let control_width = color.len() + colors.reset.len();
+
+    let fragment = match event.kind {
+        TraceEventKind::Err => String::new(),
+        TraceEventKind::Bookmark => format!("offset {}", event.offset),
+        _ => event.fragment.clone(),
+    };
+
+    if let Some(f) = options.line_format {
+        let (forward, backward) = match event.kind {
+            TraceEventKind::Forward => (Some(index), None),
+            TraceEventKind::Ok | TraceEventKind::Err => (None, Some(index)),
+            TraceEventKind::Custom | TraceEventKind::Bookmark => (None, None),
+        };
+        return f(&TraceLine {
+            kind: event.kind,
+            forward,
+            backward,
+            depth: event.depth,
+            parser: &event.parser,
+            offset: event.offset,
+            fragment: &fragment,
+            suffix: "",
+            elapsed: None,
+        });
+    }
+
+    format!(
+        "{:<count_width$} : {:<parser_width$} : {}",
+        index,
+        parser,
+        fragment,
+        count_width = options.count_width,
+        parser_width = options.parser_width + control_width,
+    )
+}
+
+/// Render the events with the indexes from `start` which pass the filters of `options`.
+fn render_events(events: &[TraceEvent], start: usize, options: &RenderOptions) -> String {
+    let mut ret = String::new();
+    for (index, event) in events.iter().enumerate() {
+        let included = options
+            .include
+            .as_ref()
+            .is_none_or(|x| x.contains(&event.parser));
+        if included && !options.exclude.contains(&event.parser) {
+            ret.push_str(&render_event(start + index, event, options));
+            ret.push('\n');
+        }
+    }
+    ret
+}
+
+/// Render the events like the live trace.
+///
+/// This re-renders a trace recorded by `Trace::save` with other widths, filters or formats.
+/// Each line is terminated by `\n`.
+pub fn render(events: &[TraceEvent], options: &RenderOptions) -> String {
+    render_events(events, 0, options)
+}
+
+/// Render the page of the captured trace.
+///
+/// The page is the range of events `page * page_size..(page + 1) * page_size`,
+/// so viewers can render enormous traces on demand.
+/// Each line is terminated by `\n`.
+pub fn render_page(
+    trace: &Trace,
+    page: usize,
+    page_size: usize,
+    options: &RenderOptions,
+) -> String {
+    let start = page.saturating_mul(page_size).min(trace.events.len());
+    let end = start.saturating_add(page_size).min(trace.events.len());
+
+    render_events(&trace.events[start..end], start, options)
+}
+
+impl Trace {
+    /// Get the number of pages by `render_page`.
+    pub fn page_count(&self, page_size: usize) -> usize {
+        match page_size {
+            0 => 0,
+            _ => self.events.len().div_ceil(page_size),
+        }
+    }
+
+    /// Render the whole captured trace.
+    pub fn render(&self, options: &RenderOptions) -> String {
+        render_page(self, 0, self.events.len(), options)
+    }
+}
@@ -0,0 +1,102 @@
+#[cfg(feature = "trace-core")]
+use crate::{output_line, ColorScheme, OutputLevel, TracableInfo};
+
+/// Struct to have the furthest failure of the root parse for `TracableInfo::failure_snippet`.
+#[cfg(feature = "trace-core")]
+#[derive(Debug, Default)]
+pub(crate) struct FailureSnippet {
+    /// The whole input of the root parser and the offset of it.
+    pub(crate) input: Vec<u8>,
+    pub(crate) base: usize,
+    /// The furthest entry offset of the failed parsers, and the parser chain at it.
+    pub(crate) furthest: Option<(usize, Vec<String>)>,
+}
+
+#[cfg(feature = "trace-core")]
+impl FailureSnippet {
+    pub(crate) fn new(input: &[u8], base: usize) -> Self {
+        FailureSnippet {
+            input: input.to_vec(),
+            base,
+            furthest: None,
+        }
+    }
+
+    /// Record the failure of the parser. The first failure at the furthest offset is kept.
+    pub(crate) fn record<F: FnOnce() -> Vec<String>>(&mut self, offset: usize, chain: F) {
+        if self.furthest.as_ref().is_none_or(|x| x.0 < offset) {
+            self.furthest = Some((offset, chain()));
+        }
+    }
+
+    /// Format the excerpt of the input with the marker under the furthest failure.
+    fn format(&self, context: usize, colors: &ColorScheme) -> Vec<String> {
+        let (offset, chain) = match &self.furthest {
+            Some(x) => x,
+            None => return Vec::new(),
+        };
+        let position = offset.saturating_sub(self.base).min(self.input.len());
+        let text = String::from_utf8_lossy(&self.input);
+        let lines: Vec<_> = text.split('\n').collect();
+
+        // The position is in bytes, and the lossy conversion keeps the valid UTF-8 bytes
+        let before = String::from_utf8_lossy(&self.input[..position]);
+        let line = before.matches('\n').count();
+        let prefix = before.rsplit('\n').next().unwrap_or("");
+        let column = prefix.chars().count();
+        // Tabs are kept to align the marker
+        let indent: String = prefix
+            .chars()
+            .map(|x| if x == '\t' { '\t' } else { ' ' })
+            .collect();
+
+        let first = line.saturating_sub(context);
+        let last = (line + context).min(lines.len() - 1);
+        let number_width = (last + 1).to_string().len();
+
+        let mut ret = vec![format!(
+            "{}error{} : parse failed at line {}, column {} (offset {})",
+            colors.err,
+            colors.reset,
+            line + 1,
+            column + 1,
+            offset
+        )];
+        for (i, x) in lines.iter().enumerate().take(last + 1).skip(first) {
+            let x = x.strip_suffix('\r').unwrap_or(x);
+            ret.push(format!("{:>width$} | {}", i + 1, x, width = number_width));
+            if i == line {
+                ret.push(format!(
+                    "{:>width$} | {}{}^ {}{}",
+                    "",
+                    indent,
+                    colors.err,
+                    chain.join(" -> "),
+                    colors.reset,
+                    width = number_width
+                ));
+            }
+        }
+        ret
+    }
+}
+
+/// Show the excerpt of the input at the end of the failed root parse.
+#[cfg(feature = "trace-core")]
+pub(crate) fn show_failure_snippet(name: &str, info: &TracableInfo, context: usize) {
+    let lines = crate::TRACABLE_STORAGE.with(|storage| {
+        let storage = storage.borrow();
+        storage
+            .snippet
+            .as_ref()
+            .map(|x| x.format(context, &info.colors()))
+            .unwrap_or_default()
+    });
+    if lines.is_empty() {
+        return;
+    }
+    output_line(name, OutputLevel::Warn, format_args!(""));
+    for x in &lines {
+        output_line(name, OutputLevel::Warn, format_args!("{}", x));
+    }
+}
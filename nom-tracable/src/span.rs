@@ -0,0 +1,313 @@
+use crate::{HasTracableInfo, TracableInfo};
+use core::ops::{Deref, RangeFrom, RangeTo};
+use nom::error::{ErrorKind, ParseError};
+use nom::{
+    AsBytes, Compare, CompareResult, Err, ExtendInto, FindSubstring, FindToken, IResult, InputIter,
+    InputLength, InputTake, InputTakeAtPosition, Needed, Offset, ParseTo, Slice,
+};
+
+/// Lightweight input type which implements `Tracable` without `nom_locate`.
+///
+/// This has the fragment, the offset from the beginning of the whole input, and `TracableInfo`.
+///
+/// ```
+/// # use nom::character::complete::*;
+/// # use nom::IResult;
+/// # use nom_tracable::{tracable_parser, TracableInfo, TracableStr};
+/// #
+/// #[tracable_parser]
+/// pub fn term(s: TracableStr) -> IResult<TracableStr, String> {
+///     let (s, x) = char('1')(s)?;
+///     Ok((s, x.to_string()))
+/// }
+///
+/// let (s, x) = term(TracableStr::new("1+1", TracableInfo::new())).unwrap();
+/// assert_eq!(x, "1");
+/// assert_eq!(s.location_offset(), 1);
+/// assert_eq!(*s.fragment(), "+1");
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct TracableSpan<T> {
+    fragment: T,
+    offset: usize,
+    info: TracableInfo,
+}
+
+/// `TracableSpan` of `&str`.
+pub type TracableStr<'a> = TracableSpan<&'a str>;
+
+/// `TracableSpan` of `&[u8]`.
+pub type TracableBytes<'a> = TracableSpan<&'a [u8]>;
+
+impl<T> TracableSpan<T> {
+    /// Create a span of the whole input.
+    pub fn new(fragment: T, info: TracableInfo) -> Self {
+        TracableSpan {
+            fragment,
+            offset: 0,
+            info,
+        }
+    }
+
+    pub fn fragment(&self) -> &T {
+        &self.fragment
+    }
+
+    pub fn into_fragment(self) -> T {
+        self.fragment
+    }
+
+    /// Offset of the fragment from the beginning of the whole input.
+    pub fn location_offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl<T> Deref for TracableSpan<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.fragment
+    }
+}
+
+impl<T> HasTracableInfo for TracableSpan<T> {
+    fn get_tracable_info(&self) -> TracableInfo {
+        self.info.clone()
+    }
+
+    fn set_tracable_info(mut self, info: TracableInfo) -> Self {
+        self.info = info;
+        self
+    }
+}
+
+#[cfg(feature = "trace-core")]
+impl<T: crate::FragmentDisplay + AsBytes> crate::Tracable for TracableSpan<T> {
+    fn inc_depth(mut self) -> Self {
+        self.info.depth += 1;
+        self
+    }
+
+    fn dec_depth(mut self) -> Self {
+        self.info.depth -= 1;
+        self
+    }
+
+    fn format(&self) -> String {
+        crate::format_fragment(self.offset, None, &self.fragment, &self.info)
+    }
+
+    fn header(&self) -> String {
+        crate::format_header(false, &self.info)
+    }
+
+    fn offset(&self) -> usize {
+        self.offset
+    }
+
+    fn fragment_bytes(&self) -> &[u8] {
+        self.fragment.as_bytes()
+    }
+}
+
+#[cfg(all(feature = "bare", not(feature = "trace-core")))]
+impl<T: AsBytes> crate::Tracable for TracableSpan<T> {
+    fn inc_depth(self) -> Self {
+        self
+    }
+
+    fn dec_depth(self) -> Self {
+        self
+    }
+
+    #[cfg(feature = "std")]
+    fn format(&self) -> String {
+        format!("{:<8}", self.offset)
+    }
+
+    #[cfg(feature = "std")]
+    fn header(&self) -> String {
+        format!("{:<8}", "offset")
+    }
+
+    fn offset(&self) -> usize {
+        self.offset
+    }
+
+    fn fragment_bytes(&self) -> &[u8] {
+        self.fragment.as_bytes()
+    }
+}
+
+impl<T: AsBytes> AsBytes for TracableSpan<T> {
+    fn as_bytes(&self) -> &[u8] {
+        self.fragment.as_bytes()
+    }
+}
+
+impl<T: InputLength> InputLength for TracableSpan<T> {
+    fn input_len(&self) -> usize {
+        self.fragment.input_len()
+    }
+}
+
+impl<T> InputTake for TracableSpan<T>
+where
+    Self: Slice<RangeFrom<usize>> + Slice<RangeTo<usize>>,
+{
+    fn take(&self, count: usize) -> Self {
+        self.slice(..count)
+    }
+
+    fn take_split(&self, count: usize) -> (Self, Self) {
+        (self.slice(count..), self.slice(..count))
+    }
+}
+
+impl<T: InputIter> InputIter for TracableSpan<T> {
+    type Item = T::Item;
+    type Iter = T::Iter;
+    type IterElem = T::IterElem;
+
+    fn iter_indices(&self) -> Self::Iter {
+        self.fragment.iter_indices()
+    }
+
+    fn iter_elements(&self) -> Self::IterElem {
+        self.fragment.iter_elements()
+    }
+
+    fn position<P>(&self, predicate: P) -> Option<usize>
+    where
+        P: Fn(Self::Item) -> bool,
+    {
+        self.fragment.position(predicate)
+    }
+
+    fn slice_index(&self, count: usize) -> Result<usize, Needed> {
+        self.fragment.slice_index(count)
+    }
+}
+
+impl<T> InputTakeAtPosition for TracableSpan<T>
+where
+    T: InputIter + InputLength,
+    Self: Slice<RangeFrom<usize>> + Slice<RangeTo<usize>> + Clone,
+{
+    type Item = T::Item;
+
+    fn split_at_position<P, E: ParseError<Self>>(&self, predicate: P) -> IResult<Self, Self, E>
+    where
+        P: Fn(Self::Item) -> bool,
+    {
+        match self.fragment.position(predicate) {
+            Some(n) => Ok(self.take_split(n)),
+            None => Err(Err::Incomplete(Needed::new(1))),
+        }
+    }
+
+    fn split_at_position1<P, E: ParseError<Self>>(
+        &self,
+        predicate: P,
+        e: ErrorKind,
+    ) -> IResult<Self, Self, E>
+    where
+        P: Fn(Self::Item) -> bool,
+    {
+        match self.fragment.position(predicate) {
+            Some(0) => Err(Err::Error(E::from_error_kind(self.clone(), e))),
+            Some(n) => Ok(self.take_split(n)),
+            None => Err(Err::Incomplete(Needed::new(1))),
+        }
+    }
+
+    fn split_at_position_complete<P, E: ParseError<Self>>(
+        &self,
+        predicate: P,
+    ) -> IResult<Self, Self, E>
+    where
+        P: Fn(Self::Item) -> bool,
+    {
+        match self.split_at_position(predicate) {
+            Err(Err::Incomplete(_)) => Ok(self.take_split(self.input_len())),
+            x => x,
+        }
+    }
+
+    fn split_at_position1_complete<P, E: ParseError<Self>>(
+        &self,
+        predicate: P,
+        e: ErrorKind,
+    ) -> IResult<Self, Self, E>
+    where
+        P: Fn(Self::Item) -> bool,
+    {
+        match self.fragment.position(predicate) {
+            Some(0) => Err(Err::Error(E::from_error_kind(self.clone(), e))),
+            Some(n) => Ok(self.take_split(n)),
+            None if self.fragment.input_len() == 0 => {
+                Err(Err::Error(E::from_error_kind(self.clone(), e)))
+            }
+            None => Ok(self.take_split(self.input_len())),
+        }
+    }
+}
+
+impl<T: Slice<R> + Offset, R> Slice<R> for TracableSpan<T> {
+    fn slice(&self, range: R) -> Self {
+        let fragment = self.fragment.slice(range);
+        TracableSpan {
+            offset: self.offset + self.fragment.offset(&fragment),
+            fragment,
+            info: self.info.clone(),
+        }
+    }
+}
+
+impl<T: Compare<U>, U> Compare<U> for TracableSpan<T> {
+    fn compare(&self, t: U) -> CompareResult {
+        self.fragment.compare(t)
+    }
+
+    fn compare_no_case(&self, t: U) -> CompareResult {
+        self.fragment.compare_no_case(t)
+    }
+}
+
+impl<T: FindSubstring<U>, U> FindSubstring<U> for TracableSpan<T> {
+    fn find_substring(&self, substr: U) -> Option<usize> {
+        self.fragment.find_substring(substr)
+    }
+}
+
+impl<T: FindToken<U>, U> FindToken<U> for TracableSpan<T> {
+    fn find_token(&self, token: U) -> bool {
+        self.fragment.find_token(token)
+    }
+}
+
+impl<T: ParseTo<R>, R> ParseTo<R> for TracableSpan<T> {
+    fn parse_to(&self) -> Option<R> {
+        self.fragment.parse_to()
+    }
+}
+
+impl<T> Offset for TracableSpan<T> {
+    fn offset(&self, second: &Self) -> usize {
+        second.offset - self.offset
+    }
+}
+
+impl<T: ExtendInto> ExtendInto for TracableSpan<T> {
+    type Item = T::Item;
+    type Extender = T::Extender;
+
+    fn new_builder(&self) -> Self::Extender {
+        self.fragment.new_builder()
+    }
+
+    fn extend_into(&self, acc: &mut Self::Extender) {
+        self.fragment.extend_into(acc)
+    }
+}
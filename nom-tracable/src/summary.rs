@@ -0,0 +1,62 @@
+use crate::{output_line, OutputLevel, TracableInfo};
+
+/// The number of parsers listed in each ranking of the summary.
+const SUMMARY_RANKING: usize = 3;
+
+/// Display the summary block at the end of the root parse by `TracableInfo::summary`.
+pub(crate) fn show_summary(name: &str, info: &TracableInfo) {
+    let lines = crate::TRACABLE_STORAGE.with(|storage| {
+        let storage = storage.borrow();
+
+        let forward: usize = storage.histogram.values().sum();
+        let backward: usize = storage.results.values().map(|x| x.0 + x.1).sum();
+        let failed: usize = storage.results.values().map(|x| x.1).sum();
+
+        let mut called: Vec<_> = storage.histogram.iter().collect();
+        called.sort_by(|x, y| y.1.cmp(x.1).then_with(|| x.0.cmp(y.0)));
+        let called: Vec<_> = called
+            .iter()
+            .take(SUMMARY_RANKING)
+            .map(|(parser, count)| format!("{} ({})", parser, count))
+            .collect();
+
+        let mut lines = vec![
+            format!(
+                "forward {} : backward {} : failed {}",
+                forward, backward, failed
+            ),
+            format!(
+                "max depth {} : {}",
+                storage.max_depth,
+                storage.max_depth_chain.join(" -> ")
+            ),
+            format!("most called : {}", called.join(", ")),
+        ];
+
+        // The elapsed times are not deterministic
+        if !info.options.snapshot {
+            let mut slowest: Vec<_> = storage.timing.iter().collect();
+            slowest.sort_by(|x, y| y.1 .1.cmp(&x.1 .1).then_with(|| x.0.cmp(y.0)));
+            let slowest: Vec<_> = slowest
+                .iter()
+                .take(SUMMARY_RANKING)
+                .map(|(parser, (_, elapsed))| format!("{} ({:?})", parser, elapsed))
+                .collect();
+            lines.push(format!("slowest : {}", slowest.join(", ")));
+        }
+        lines
+    });
+
+    let colors = info.colors();
+    let (color, reset) = (colors.summary, colors.reset);
+
+    output_line(name, OutputLevel::Debug, format_args!(""));
+    output_line(
+        name,
+        OutputLevel::Debug,
+        format_args!("{}=== summary : {} ==={}", color, name, reset),
+    );
+    for x in &lines {
+        output_line(name, OutputLevel::Debug, format_args!("{}", x));
+    }
+}
@@ -0,0 +1,80 @@
+use crate::{open_output_path, FileWriter, TracableStorage, OUTPUT_FILE};
+use std::future::Future;
+use std::io::Write;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Future to carry the trace context of an async parser across executor threads.
+///
+/// The storage of the trace is owned by the future, and installed to the thread only while the future is polled.
+/// So a parse can be resumed on another thread after `.await`.
+/// Nested async parsers share the context of the outermost one.
+/// After the future completes, the statistics and the captured trace are available on the thread which completed it.
+///
+/// This is inserted by `#[tracable_parser]` for `async fn`.
+pub struct TaskContext<F> {
+    future: Pin<Box<F>>,
+    storage: Option<TracableStorage>,
+    nested: Option<bool>,
+    output_file: Option<PathBuf>,
+}
+
+/// Wrap the future of an async parser by `TaskContext`.
+pub fn task_context<F: Future>(future: F) -> TaskContext<F> {
+    TaskContext {
+        future: Box::pin(future),
+        storage: None,
+        nested: None,
+        output_file: None,
+    }
+}
+
+impl<F: Future> Future for TaskContext<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let this = self.get_mut();
+
+        // The outer context is installed while this is polled
+        let nested = *this
+            .nested
+            .get_or_insert_with(|| crate::TRACABLE_STORAGE.with(|storage| storage.borrow().task));
+        if nested {
+            return this.future.as_mut().poll(cx);
+        }
+
+        let task = match this.storage.take() {
+            Some(x) => x,
+            None => crate::TRACABLE_STORAGE.with(|storage| storage.borrow().fork_task()),
+        };
+        task.enter_spans();
+        let thread = crate::TRACABLE_STORAGE
+            .with(|storage| std::mem::replace(&mut *storage.borrow_mut(), task));
+        if this.output_file.is_some() {
+            open_output_path(this.output_file.as_ref());
+        }
+
+        let ret = this.future.as_mut().poll(cx);
+
+        let mut task = crate::TRACABLE_STORAGE
+            .with(|storage| std::mem::replace(&mut *storage.borrow_mut(), thread));
+        task.exit_spans();
+        this.output_file = OUTPUT_FILE.with(|file| file.borrow().as_ref().map(|x| x.0.clone()));
+        // The next poll may be on another thread
+        let _ = FileWriter.flush();
+
+        if ret.is_pending() {
+            this.storage = Some(task);
+        } else {
+            crate::TRACABLE_STORAGE.with(|storage| {
+                let mut storage = storage.borrow_mut();
+                if storage.stack.is_empty() {
+                    task.task = false;
+                    *storage = task;
+                }
+            });
+        }
+        ret
+    }
+}
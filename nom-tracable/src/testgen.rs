@@ -0,0 +1,74 @@
+use crate::{Trace, TraceEventKind};
+
+impl Trace {
+    /// Get the offset and the parser chain of the furthest failure.
+    ///
+    /// This returns `None` if the root parser succeeded.
+    pub fn failure_path(&self) -> Option<(usize, Vec<String>)> {
+        match self.events.last() {
+            Some(x) if x.kind == TraceEventKind::Err && x.depth == 0 => (),
+            _ => return None,
+        }
+
+        let mut stack = Vec::new();
+        let mut furthest: Option<(usize, Vec<String>)> = None;
+
+        for event in &self.events {
+            match event.kind {
+                TraceEventKind::Forward => stack.push(event.parser.clone()),
+                TraceEventKind::Ok => {
+                    stack.pop();
+                }
+                TraceEventKind::Err => {
+                    let further = match &furthest {
+                        Some((offset, _)) => event.offset > *offset,
+                        None => true,
+                    };
+                    if further {
+                        furthest = Some((event.offset, stack.clone()));
+                    }
+                    stack.pop();
+                }
+                TraceEventKind::Custom | TraceEventKind::Bookmark => (),
+            }
+        }
+
+        furthest
+    }
+
+    /// Generate a skeleton of `#[test]` which reproduces the failure of the trace.
+    ///
+    /// The generated test contains the input literal, the root parser and the expected failure.
+    /// This returns `None` if the root parser succeeded.
+    pub fn failure_test(&self, test_name: &str) -> Option<String> {
+        let (offset, chain) = self.failure_path()?;
+        let root = &self
+            .events
+            .iter()
+            .find(|x| x.kind == TraceEventKind::Forward)?
+            .parser;
+
+        let input = match std::str::from_utf8(&self.input) {
+            Ok(x) => format!("{:?}", x),
+            Err(_) => format!("&b\"{}\"[..]", self.input.escape_ascii()),
+        };
+
+        let mut ret = String::new();
+        ret.push_str("#[test]\n");
+        ret.push_str(&format!("fn {}() {{\n", test_name));
+        ret.push_str(&format!("    // failing path : {}\n", chain.join(" -> ")));
+        ret.push_str(&format!(
+            "    // expected failure : {} at offset {}\n",
+            chain.last().map_or("", |x| x.as_str()),
+            offset
+        ));
+        ret.push_str(&format!(
+            "    let input = LocatedSpan::new_extra({}, TracableInfo::new());\n",
+            input
+        ));
+        ret.push_str(&format!("    let ret = {}(input);\n", root));
+        ret.push_str("    assert!(ret.is_err());\n");
+        ret.push_str("}\n");
+        Some(ret)
+    }
+}
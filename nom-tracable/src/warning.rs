@@ -0,0 +1,148 @@
+use crate::ndjson::escape;
+
+/// Kind of `Warning`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WarningKind {
+    /// A parser re-enters itself at the same offset.
+    LeftRecursion,
+    /// A parser is entered at the same offset more than `TracableInfo::watchdog`.
+    NonProgress,
+    /// A parser is called more than `TracableInfo::invocation_budget`.
+    InvocationBudget,
+    /// The parse exceeds `TracableInfo::time_budget`.
+    TimeBudget,
+    /// The cancellation by `CancellationToken` is observed.
+    Cancelled,
+    /// A parser is entered deeper than `TracableInfo::depth_limit`.
+    DepthLimit,
+}
+
+impl WarningKind {
+    /// Get the name of the kind used by the machine-readable formats.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WarningKind::LeftRecursion => "left_recursion",
+            WarningKind::NonProgress => "non_progress",
+            WarningKind::InvocationBudget => "invocation_budget",
+            WarningKind::TimeBudget => "time_budget",
+            WarningKind::Cancelled => "cancelled",
+            WarningKind::DepthLimit => "depth_limit",
+        }
+    }
+}
+
+/// Struct to have a diagnostic generated by the tracer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Warning {
+    pub kind: WarningKind,
+    /// The parser which caused the warning.
+    pub parser: String,
+    pub offset: usize,
+    /// The parser chain when the warning was generated.
+    pub chain: Vec<String>,
+    /// Human-readable message which is displayed in the trace.
+    pub message: String,
+}
+
+impl Warning {
+    /// Format the warning as a JSON object in a line.
+    pub fn to_json(&self) -> String {
+        let chain: Vec<_> = self.chain.iter().map(|x| escape(x)).collect();
+        format!(
+            "{{\"kind\":\"{}\",\"parser\":{},\"offset\":{},\"chain\":[{}],\"message\":{}}}",
+            self.kind.as_str(),
+            escape(&self.parser),
+            self.offset,
+            chain.join(","),
+            escape(&self.message)
+        )
+    }
+}
+
+/// Set the subscriber called for each warning generated by the tracer.
+///
+/// The subscriber is called before the abort hook by `set_abort_hook`.
+/// The subscriber is thread independent because it is stored at thread local storage.
+pub fn set_warning_subscriber(subscriber: Option<fn(&Warning)>) {
+    set_warning_subscriber_internal(subscriber);
+}
+
+#[cfg(feature = "trace-core")]
+fn set_warning_subscriber_internal(subscriber: Option<fn(&Warning)>) {
+    crate::TRACABLE_STORAGE.with(|storage| {
+        storage.borrow_mut().warning_subscriber = subscriber;
+    });
+}
+
+#[cfg(not(feature = "trace-core"))]
+fn set_warning_subscriber_internal(_subscriber: Option<fn(&Warning)>) {}
+
+/// Get the warnings generated during the last parse.
+///
+/// The warnings are reset at each parser call like histogram.
+pub fn warnings() -> Vec<Warning> {
+    warnings_internal()
+}
+
+#[cfg(feature = "trace-core")]
+fn warnings_internal() -> Vec<Warning> {
+    crate::TRACABLE_STORAGE.with(|storage| storage.borrow().warnings.clone())
+}
+
+#[cfg(not(feature = "trace-core"))]
+fn warnings_internal() -> Vec<Warning> {
+    Vec::new()
+}
+
+/// Display the warning, notify it to the subscriber, and call the abort hook.
+#[cfg(feature = "trace-core")]
+pub(crate) fn report_warning(
+    kind: WarningKind,
+    name: &str,
+    offset: usize,
+    info: &crate::TracableInfo,
+    message: &str,
+) {
+    let colors = info.colors();
+    let (color, reset) = (colors.warning, colors.reset);
+
+    let warning = crate::TRACABLE_STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let warning = Warning {
+            kind,
+            parser: String::from(name),
+            offset,
+            chain: storage.stack.iter().map(|x| x.name.clone()).collect(),
+            message: String::from(message),
+        };
+        storage.warnings.push(warning.clone());
+        warning
+    });
+    let (subscriber, hook) = crate::TRACABLE_STORAGE.with(|storage| {
+        let storage = storage.borrow();
+        (storage.warning_subscriber, storage.abort_hook)
+    });
+
+    crate::output_line(
+        name,
+        crate::OutputLevel::Warn,
+        format_args!("{}!!! {}{}", color, message, reset),
+    );
+    crate::output_line(
+        name,
+        crate::OutputLevel::Warn,
+        format_args!(
+            "{}!!! stack : {}{}",
+            color,
+            warning.chain.join(" -> "),
+            reset
+        ),
+    );
+
+    if let Some(subscriber) = subscriber {
+        subscriber(&warning);
+    }
+    if let Some(hook) = hook {
+        hook(message);
+    }
+}
@@ -0,0 +1,56 @@
+// `set_sink` is global, so this is separated from the other tests running in parallel.
+#![cfg(all(feature = "bare", not(feature = "trace")))]
+
+use nom::character::complete::*;
+use nom::IResult;
+use nom_locate::LocatedSpan;
+use nom_tracable::{backward_trace, forward_trace, set_sink, TracableInfo};
+use std::sync::Mutex;
+
+type Span<'a> = LocatedSpan<&'a str, TracableInfo>;
+
+// The expansion of `#[tracable_parser]` in a crate which maps its `trace` feature to `bare`
+pub fn term(s: Span) -> IResult<Span, String> {
+    let (depth, s) = forward_trace(s, "term");
+    let ret = term_inner(s);
+    backward_trace(ret, "term", depth)
+}
+
+pub fn term_inner(s: Span) -> IResult<Span, String> {
+    let (depth, s) = forward_trace(s, "term_inner");
+    let ret = char('1')(s).map(|(s, x)| (s, x.to_string()));
+    backward_trace(ret, "term_inner", depth)
+}
+
+static LINES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+fn sink(x: &str) {
+    LINES.lock().unwrap().push(String::from(x));
+}
+
+#[test]
+fn test_bare() {
+    set_sink(Some(sink));
+    let ret = term(LocatedSpan::new_extra("1", TracableInfo::new()));
+    assert_eq!("\"1\"", format!("{:?}", ret.unwrap().1));
+    let _ = term(LocatedSpan::new_extra("2", TracableInfo::new()));
+    set_sink(None);
+
+    let lines = LINES.lock().unwrap();
+    let lines: Vec<_> = lines.iter().map(|x| x.trim_end()).collect();
+    assert_eq!(
+        lines,
+        vec![
+            "forward    backward   : parser : offset",
+            "1                     : -> term : 0",
+            "2                     :  -> term_inner : 0",
+            "           1          :  <- term_inner : 1",
+            "           2          : <- term : 1",
+            "forward    backward   : parser : offset",
+            "1                     : -> term : 0",
+            "2                     :  -> term_inner : 0",
+            "           1          :  <- term_inner : error",
+            "           2          : <- term : error",
+        ]
+    );
+}
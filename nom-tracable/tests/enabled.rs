@@ -0,0 +1,67 @@
+// `set_enabled` is global, so this is separated from the other tests running in parallel.
+#![cfg(feature = "std")]
+
+use nom::character::complete::*;
+use nom::IResult;
+use nom_locate::LocatedSpan;
+use nom_tracable::{
+    captured_trace, is_enabled, set_enabled, tracable_parser, with_captured_trace, TracableInfo,
+};
+
+type Span<'a> = LocatedSpan<&'a str, TracableInfo>;
+
+#[tracable_parser]
+pub fn term(s: Span) -> IResult<Span, String> {
+    term_inner(s)
+}
+
+#[tracable_parser]
+pub fn term_inner(s: Span) -> IResult<Span, String> {
+    let (s, x) = char('1')(s)?;
+    Ok((s, x.to_string()))
+}
+
+/// Parser switching on the trace in the middle of the parse
+#[tracable_parser]
+pub fn toggle(s: Span) -> IResult<Span, String> {
+    set_enabled(true);
+    term(s)
+}
+
+#[test]
+fn test_set_enabled() {
+    let info = TracableInfo::new().capture(true);
+
+    set_enabled(false);
+    assert!(!is_enabled());
+    let ret = term(LocatedSpan::new_extra("1", info.clone()));
+    assert_eq!("\"1\"", format!("{:?}", ret.unwrap().1));
+    assert!(captured_trace().events.is_empty());
+
+    set_enabled(true);
+    let ret = term(LocatedSpan::new_extra("1", info.clone()));
+    assert_eq!("\"1\"", format!("{:?}", ret.unwrap().1));
+
+    #[cfg(feature = "trace")]
+    {
+        assert!(is_enabled());
+        assert_eq!(captured_trace().events.len(), 4);
+    }
+
+    // The children follow the root, which was not traced
+    set_enabled(false);
+    let (ret, output) = with_captured_trace(|| toggle(LocatedSpan::new_extra("1", info.clone())));
+    assert_eq!("\"1\"", format!("{:?}", ret.unwrap().1));
+    assert!(output.is_empty());
+
+    let (ret, output) = with_captured_trace(|| toggle(LocatedSpan::new_extra("1", info)));
+    assert_eq!("\"1\"", format!("{:?}", ret.unwrap().1));
+
+    #[cfg(feature = "trace")]
+    {
+        assert!(output.contains("-> toggle"));
+        assert_eq!(captured_trace().events.len(), 6);
+    }
+    #[cfg(not(feature = "trace"))]
+    let _ = output;
+}
@@ -0,0 +1,64 @@
+// `set_sink` is global, so this is separated from the other tests running in parallel.
+
+use nom::character::complete::*;
+use nom::IResult;
+use nom_locate::LocatedSpan;
+use nom_tracable::{set_sink, tracable_parser, TracableInfo, TraceFlush};
+use std::sync::Mutex;
+
+type Span<'a> = LocatedSpan<&'a str, TracableInfo>;
+
+#[tracable_parser]
+pub fn term(s: Span) -> IResult<Span, String> {
+    term_inner(s)
+}
+
+#[tracable_parser]
+pub fn term_inner(s: Span) -> IResult<Span, String> {
+    let (s, x) = char('1')(s)?;
+    Ok((s, x.to_string()))
+}
+
+static CHUNKS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+fn sink(x: &str) {
+    CHUNKS.lock().unwrap().push(String::from(x));
+}
+
+#[test]
+fn test_sink() {
+    set_sink(Some(sink));
+    let threads: Vec<_> = ["a", "b"]
+        .iter()
+        .map(|name| {
+            std::thread::Builder::new()
+                .name(String::from(*name))
+                .spawn(|| {
+                    let info = TracableInfo::new()
+                        .thread_tag(true)
+                        .flush(TraceFlush::Parse);
+                    let ret = term(LocatedSpan::new_extra("1", info));
+                    assert!(ret.is_ok());
+                })
+                .unwrap()
+        })
+        .collect();
+    for x in threads {
+        x.join().unwrap();
+    }
+    set_sink(None);
+
+    #[cfg(all(feature = "trace", not(feature = "log")))]
+    {
+        let chunks = CHUNKS.lock().unwrap();
+        for name in ["a", "b"] {
+            let tag = format!("[{}#1] ", name);
+            let chunk: Vec<_> = chunks.iter().filter(|x| x.contains(&tag)).collect();
+            // The whole parse is passed at once
+            assert_eq!(chunk.len(), 1);
+            let lines: Vec<_> = chunk[0].lines().filter(|x| !x.is_empty()).collect();
+            assert_eq!(lines.len(), 5);
+            assert!(lines.iter().all(|x| x.starts_with(&tag)));
+        }
+    }
+}
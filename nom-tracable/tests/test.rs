@@ -1,3 +1,6 @@
+// The tests use the API which is available with `std`.
+#![cfg(feature = "std")]
+
 use nom::branch::*;
 use nom::character::complete::*;
 use nom::IResult;
@@ -40,6 +43,13 @@ pub fn term_inner(s: Span) -> IResult<Span, String> {
     Ok((s, x.to_string()))
 }
 
+#[tracable_parser]
+pub fn expr_eof(s: Span) -> IResult<Span, String> {
+    let (s, x) = expr(s)?;
+    let (s, _) = nom::combinator::eof(s)?;
+    Ok((s, x))
+}
+
 #[test]
 fn test() {
     let ret = expr(LocatedSpan::new_extra("1", TracableInfo::new()));
@@ -69,3 +79,2941 @@ fn test() {
     histogram();
     cumulative_histogram();
 }
+
+#[cfg(feature = "trace")]
+#[test]
+fn test_html() {
+    use nom_tracable::{captured_trace, TraceEventKind};
+
+    let ret = expr(LocatedSpan::new_extra(
+        "1+1",
+        TracableInfo::new()
+            .forward(false)
+            .backward(false)
+            .capture(true),
+    ));
+    assert!(ret.is_ok());
+
+    let trace = captured_trace();
+    assert_eq!(trace.events.first().unwrap().kind, TraceEventKind::Forward);
+    assert_eq!(trace.events.first().unwrap().parser, "expr");
+    assert_eq!(trace.events.last().unwrap().kind, TraceEventKind::Ok);
+
+    let mut html = Vec::new();
+    trace.write_html(&mut html).unwrap();
+    let html = String::from_utf8(html).unwrap();
+    assert!(html.contains("<span class=\"parser\">expr_plus</span>"));
+    assert_eq!(
+        html.matches("<details").count(),
+        html.matches("</details>").count()
+    );
+}
+
+#[cfg(feature = "trace")]
+#[test]
+fn test_time_budget() {
+    use nom_tracable::set_abort_hook;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    static ABORTED: AtomicBool = AtomicBool::new(false);
+
+    fn hook(message: &str) {
+        assert!(message.contains("timeout"));
+        ABORTED.store(true, Ordering::SeqCst);
+    }
+
+    set_abort_hook(Some(hook));
+    let ret = expr(LocatedSpan::new_extra(
+        "1+1",
+        TracableInfo::new().time_budget(Duration::from_nanos(1)),
+    ));
+    set_abort_hook(None);
+
+    assert!(ret.is_ok());
+    assert!(ABORTED.load(Ordering::SeqCst));
+}
+
+#[cfg(feature = "trace")]
+#[test]
+fn test_shared_options() {
+    // The clones share the options until one of them is modified
+    let base = TracableInfo::new().timing(true);
+    let watched = base.clone().watch("1");
+    assert_ne!(base, watched);
+    assert_eq!(base, TracableInfo::new().timing(true));
+    assert_eq!(watched, TracableInfo::new().timing(true).watch("1"));
+    assert_eq!(TracableInfo::new().timing(false), TracableInfo::new());
+}
+
+#[test]
+fn test_thread_default() {
+    use nom_tracable::{new_span, set_thread_default, thread_default};
+
+    let info = TracableInfo::new().color(false).parser_width(40);
+    set_thread_default(info.clone());
+    assert_eq!(thread_default(), info);
+
+    let span = new_span("1+1");
+    assert_eq!(span.extra, info);
+
+    let ret = expr(span);
+    assert_eq!("\"1+1\"", format!("{:?}", ret.unwrap().1));
+
+    std::thread::spawn(|| assert_eq!(thread_default(), TracableInfo::new()))
+        .join()
+        .unwrap();
+}
+
+// The non-empty lines of the captured trace with the column padding collapsed
+#[cfg(feature = "trace")]
+fn trace_lines(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter(|x| !x.trim().is_empty())
+        .map(|x| x.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect()
+}
+
+// The parser column of the captured trace like `-> expr` or `<- term`
+#[cfg(feature = "trace")]
+fn trace_arrows(output: &str) -> Vec<String> {
+    trace_lines(output)
+        .iter()
+        .filter_map(|x| {
+            x.split(" : ")
+                .find(|x| x.starts_with("-> ") || x.starts_with("<- "))
+                .map(String::from)
+        })
+        .collect()
+}
+
+// Run `f` with the abort hook which panics, and get the message of the abort
+#[cfg(feature = "trace")]
+fn abort_message<R>(f: impl FnOnce() -> R) -> String {
+    use nom_tracable::set_abort_hook;
+
+    fn hook(message: &str) {
+        panic!("{}", message);
+    }
+
+    set_abort_hook(Some(hook));
+    let ret = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f));
+    set_abort_hook(None);
+
+    match ret {
+        Ok(_) => panic!("the parse is not aborted"),
+        Err(x) => x.downcast_ref::<String>().unwrap().clone(),
+    }
+}
+
+#[test]
+fn test_timing() {
+    use nom_tracable::with_captured_trace;
+
+    let (ret, output) = with_captured_trace(|| {
+        expr(LocatedSpan::new_extra(
+            "1+1",
+            TracableInfo::new().timing(true).color(false),
+        ))
+    });
+    assert_eq!("\"1+1\"", format!("{:?}", ret.unwrap().1));
+
+    #[cfg(feature = "trace")]
+    {
+        let lines = trace_lines(&output);
+        assert_eq!(
+            lines[0],
+            "forward backward : time : parser : offset : fragment"
+        );
+
+        // The backward lines have the elapsed time, and the forward lines leave it blank
+        for x in &lines[1..] {
+            if x.contains(" <- ") {
+                assert!(x.split(" : ").nth(1).unwrap().ends_with('s'), "{}", x);
+            } else if x.contains(" -> ") {
+                assert!(x.contains(" : : -> "), "{}", x);
+            }
+        }
+
+        // The rows of the timing table are sorted by the total time, so compare them as a set
+        let table = lines
+            .iter()
+            .position(|x| x == "parser | count | total | average")
+            .unwrap();
+        let mut rows: Vec<_> = lines[table + 2..]
+            .iter()
+            .map(|x| {
+                let columns: Vec<_> = x.split(" | ").collect();
+                (columns[0], columns[1])
+            })
+            .collect();
+        rows.sort();
+        assert_eq!(
+            rows,
+            vec![
+                ("expr", "2"),
+                ("expr_minus", "1"),
+                ("expr_plus", "2"),
+                ("term", "4"),
+                ("term_inner", "4"),
+            ]
+        );
+    }
+    #[cfg(not(feature = "trace"))]
+    let _ = output;
+}
+
+#[test]
+fn test_split_at() {
+    use nom_tracable::{rejoin, split_at};
+
+    let input = LocatedSpan::new_extra("1+1-1", TracableInfo::new());
+    let (piece, rest) = split_at(input.clone(), 3);
+    assert_eq!(*piece.fragment(), "1+1");
+    assert_eq!(*rest.fragment(), "-1");
+    assert_eq!(rest.location_offset(), 3);
+
+    let (piece, x) = expr(piece).unwrap();
+    assert_eq!(x, "1+1");
+
+    let rest = rejoin(rest, &piece);
+    assert_eq!(rest.extra, input.extra);
+    assert_eq!(rest.location_offset(), 3);
+}
+
+#[test]
+#[should_panic]
+fn test_split_at_out_of_range() {
+    let input = LocatedSpan::new_extra("1+1", TracableInfo::new());
+    let _ = nom_tracable::split_at(input, 4);
+}
+
+#[cfg(feature = "trace")]
+#[test]
+fn test_failure_test() {
+    use nom_tracable::captured_trace;
+
+    let ret = expr_eof(LocatedSpan::new_extra(
+        "1+",
+        TracableInfo::new().capture(true),
+    ));
+    assert!(ret.is_err());
+
+    let trace = captured_trace();
+    let (offset, chain) = trace.failure_path().unwrap();
+    assert_eq!(offset, 2);
+    assert_eq!(chain.last().unwrap(), "term_inner");
+
+    let test = trace.failure_test("expr_fails").unwrap();
+    assert!(test.contains("fn expr_fails()"));
+    assert!(test.contains("LocatedSpan::new_extra(\"1+\", TracableInfo::new())"));
+    assert!(test.contains("let ret = expr_eof(input);"));
+    assert!(test.contains("term_inner at offset 2"));
+}
+
+#[cfg(feature = "trace")]
+#[test]
+fn test_profile() {
+    use nom_tracable::{profile, Profile};
+
+    let _ = expr(LocatedSpan::new_extra("1+1", TracableInfo::new()));
+    let baseline = profile();
+    assert_eq!(baseline.parsers["term"].count, 4);
+
+    let path = std::env::temp_dir().join(format!("nom-tracable-profile-{}", std::process::id()));
+    baseline.save(&path).unwrap();
+    let baseline = Profile::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let _ = expr(LocatedSpan::new_extra("1+1+1+1", TracableInfo::new()));
+    let regressions = profile().show_comparison(&baseline, 0.5);
+    assert!(regressions
+        .iter()
+        .any(|x| x.parser == "expr" && x.metric == "count"));
+    assert!(profile().compare(&profile(), 0.0).is_empty());
+}
+
+#[cfg(feature = "trace")]
+#[test]
+fn test_max_depth() {
+    use nom_tracable::{max_depth, max_depth_chain};
+
+    let _ = expr(LocatedSpan::new_extra("1+1", TracableInfo::new()));
+    assert_eq!(max_depth(), 6);
+    assert_eq!(
+        max_depth_chain(),
+        vec![
+            "expr",
+            "expr_plus",
+            "expr",
+            "expr_plus",
+            "term",
+            "term_inner"
+        ]
+    );
+
+    // depth is kept coherent without backward trace
+    let ret = expr(LocatedSpan::new_extra(
+        "1+1",
+        TracableInfo::new().backward(false),
+    ));
+    assert_eq!(ret.unwrap().0.extra.depth, 0);
+    assert_eq!(max_depth(), 6);
+}
+
+#[cfg(feature = "trace")]
+#[test]
+fn test_watchdog() {
+    let message = abort_message(|| {
+        expr(LocatedSpan::new_extra(
+            "1+1",
+            TracableInfo::new().watchdog(1),
+        ))
+    });
+    assert!(message.starts_with("non-progress : term is entered at offset 2"));
+}
+
+#[cfg(feature = "trace")]
+#[test]
+fn test_bookmark() {
+    use nom_tracable::{captured_trace, TraceEventKind};
+
+    let info = TracableInfo::new()
+        .capture(true)
+        .bookmark(4, "second half")
+        .bookmark(0, "start");
+    let _ = expr(LocatedSpan::new_extra("1+1+1+1", info));
+
+    let trace = captured_trace();
+    let bookmarks: Vec<_> = trace
+        .events
+        .iter()
+        .filter(|x| x.kind == TraceEventKind::Bookmark)
+        .map(|x| (x.offset, x.parser.as_str()))
+        .collect();
+    assert_eq!(bookmarks, vec![(0, "start"), (4, "second half")]);
+
+    let mut html = Vec::new();
+    trace.write_html(&mut html).unwrap();
+    let html = String::from_utf8(html).unwrap();
+    assert!(html.contains("<a href=\"#bookmark-1\">second half</a>"));
+    assert!(html.contains("id=\"bookmark-1\""));
+}
+
+#[tracable_parser]
+pub fn left_rec(s: Span) -> IResult<Span, String> {
+    alt((left_rec_plus, term))(s)
+}
+
+#[tracable_parser]
+pub fn left_rec_plus(s: Span) -> IResult<Span, String> {
+    let (s, x) = left_rec(s)?;
+    let (s, y) = char('+')(s)?;
+    let (s, z) = term(s)?;
+    Ok((s, format!("{}{}{}", x, y, z)))
+}
+
+#[cfg(feature = "trace")]
+#[test]
+fn test_left_recursion() {
+    let message = abort_message(|| {
+        left_rec(LocatedSpan::new_extra(
+            "1+1",
+            TracableInfo::new().forward(false).backward(false),
+        ))
+    });
+    assert_eq!(
+        message,
+        "possible left recursion : left_rec -> left_rec_plus -> left_rec"
+    );
+}
+
+#[cfg(feature = "trace")]
+#[test]
+fn test_parse_after_unwind() {
+    use nom_tracable::with_captured_trace;
+
+    abort_message(|| {
+        expr(LocatedSpan::new_extra(
+            "1+1",
+            TracableInfo::new().invocation_budget("term_inner", 0),
+        ))
+    });
+
+    let (ret, trace) =
+        with_captured_trace(|| expr(LocatedSpan::new_extra("1", TracableInfo::new())));
+    assert!(ret.is_ok());
+    assert!(!trace.contains("nested parse"));
+    assert!(trace.contains("-> expr"));
+}
+
+#[cfg(feature = "trace")]
+#[test]
+fn test_combinator_after_unwind() {
+    use nom_tracable::{tracable, with_captured_trace};
+
+    abort_message(|| {
+        tracable("combinator_term", term)(LocatedSpan::new_extra(
+            "1",
+            TracableInfo::new().invocation_budget("combinator_term", 0),
+        ))
+    });
+
+    let (ret, trace) = with_captured_trace(|| {
+        tracable("combinator_term", term)(LocatedSpan::new_extra("1", TracableInfo::new()))
+    });
+    assert!(ret.is_ok());
+    assert!(!trace.contains("nested parse"));
+    assert!(trace.contains("-> combinator_term"));
+}
+
+#[cfg(feature = "trace")]
+#[test]
+fn test_nested_context_after_unwind() {
+    use nom_tracable::{captured_trace, nested_context};
+
+    let ret = expr(LocatedSpan::new_extra(
+        "1",
+        TracableInfo::new().capture(true),
+    ));
+    assert!(ret.is_ok());
+    let outer = captured_trace();
+    assert!(!outer.events.is_empty());
+
+    abort_message(|| {
+        nested_context("included.txt", || {
+            expr(LocatedSpan::new_extra(
+                "1+1",
+                TracableInfo::new().invocation_budget("term_inner", 0),
+            ))
+        })
+    });
+
+    // The outer storage is resumed, so the trace of the outer parse is kept
+    assert_eq!(captured_trace(), outer);
+}
+
+#[cfg(feature = "trace")]
+#[test]
+fn test_cancellation() {
+    use nom_tracable::{cancellation, set_cancellation_token, CancellationToken};
+
+    let token = CancellationToken::new();
+    set_cancellation_token(Some(token.clone()));
+
+    let ret = expr(LocatedSpan::new_extra("1+1", TracableInfo::new()));
+    assert!(ret.is_ok());
+    assert_eq!(cancellation(), None);
+
+    token.cancel();
+    abort_message(|| expr(LocatedSpan::new_extra("1+1", TracableInfo::new())));
+    set_cancellation_token(None);
+
+    let x = cancellation().unwrap();
+    assert_eq!(x.parser, "expr");
+    assert_eq!(x.offset, 0);
+    assert_eq!(x.chain, vec!["expr"]);
+}
+
+#[test]
+fn test_max_depth_elision() {
+    use nom_tracable::with_captured_trace;
+
+    let (ret, output) = with_captured_trace(|| {
+        expr(LocatedSpan::new_extra(
+            "1+1+1",
+            TracableInfo::new().max_depth(1),
+        ))
+    });
+    assert_eq!("\"1+1+1\"", format!("{:?}", ret.unwrap().1));
+
+    #[cfg(feature = "trace")]
+    assert_eq!(
+        &trace_lines(&output)[1..],
+        &[
+            "1 : -> expr : 0 : 1+1+1",
+            "2 : -> expr_plus : 0 : 1+1+1",
+            ": … 30 nested events elided",
+            "16 : <- expr_plus : 5 :",
+            "17 : <- expr : 5 :",
+        ]
+    );
+    #[cfg(not(feature = "trace"))]
+    let _ = output;
+}
+
+#[cfg(feature = "trace")]
+#[test]
+fn test_invocation_budget() {
+    let ret = expr(LocatedSpan::new_extra(
+        "1+1",
+        TracableInfo::new().invocation_budget("term", 10),
+    ));
+    assert!(ret.is_ok());
+
+    let message = abort_message(|| {
+        expr(LocatedSpan::new_extra(
+            "1+1+1",
+            TracableInfo::new().invocation_budget("term", 3),
+        ))
+    });
+    assert_eq!(
+        message,
+        "invocation budget : term is called more than 3 times"
+    );
+}
+
+#[cfg(feature = "trace")]
+#[test]
+fn test_event_iterator() {
+    use nom_tracable::{captured_trace, take_captured_trace, with_captured_events, TraceEventKind};
+
+    let _ = expr(LocatedSpan::new_extra(
+        "1+1",
+        TracableInfo::new().capture(true),
+    ));
+
+    let forwards =
+        with_captured_events(|events| events.filter(|x| x.kind == TraceEventKind::Forward).count());
+
+    let trace = take_captured_trace();
+    assert_eq!(trace.iter().count(), forwards * 2);
+    assert_eq!(
+        (&trace).into_iter().next().map(|x| x.parser.as_str()),
+        Some("expr")
+    );
+    assert!(captured_trace().events.is_empty());
+}
+
+#[test]
+fn test_include_exclude() {
+    use nom_tracable::with_captured_trace;
+
+    let (ret, output) = with_captured_trace(|| {
+        expr(LocatedSpan::new_extra(
+            "1+1",
+            TracableInfo::new().include(&["expr", "term"]),
+        ))
+    });
+    assert_eq!("\"1+1\"", format!("{:?}", ret.unwrap().1));
+
+    #[cfg(feature = "trace")]
+    assert_eq!(
+        trace_arrows(&output),
+        vec![
+            "-> expr", "-> term", "<- term", "-> expr", "-> term", "<- term", "-> term", "<- term",
+            "-> term", "<- term", "<- expr", "<- expr",
+        ]
+    );
+    #[cfg(not(feature = "trace"))]
+    let _ = output;
+
+    let (ret, output) = with_captured_trace(|| {
+        expr(LocatedSpan::new_extra(
+            "1+1",
+            TracableInfo::new().exclude(&["term_inner"]),
+        ))
+    });
+    assert_eq!("\"1+1\"", format!("{:?}", ret.unwrap().1));
+
+    #[cfg(feature = "trace")]
+    {
+        let arrows = trace_arrows(&output);
+        assert_eq!(arrows.len(), 18);
+        assert!(arrows.iter().all(|x| !x.ends_with("term_inner")));
+        assert_eq!(arrows.iter().filter(|x| *x == "-> term").count(), 4);
+    }
+    #[cfg(not(feature = "trace"))]
+    let _ = output;
+}
+
+#[cfg(feature = "trace")]
+#[test]
+fn test_ndjson() {
+    use nom_tracable::{TraceEvent, TraceEventKind};
+
+    fn errors(x: &TraceEvent) -> bool {
+        x.kind == TraceEventKind::Err
+    }
+
+    let path = std::env::temp_dir().join("nom_tracable_test_ndjson.ndjson");
+    let ret = expr(LocatedSpan::new_extra(
+        "1+1",
+        TracableInfo::new().forward(false).ndjson(&path),
+    ));
+    assert!(ret.is_ok());
+
+    let lines = std::fs::read_to_string(&path).unwrap();
+    let first = lines.lines().next().unwrap();
+    assert!(first.starts_with(r#"{"kind":"forward","depth":0,"parser":"expr","offset":0,"#));
+    assert!(lines.lines().any(|x| x.starts_with(r#"{"kind":"ok""#)));
+
+    let ret = expr(LocatedSpan::new_extra(
+        "1+1",
+        TracableInfo::new().ndjson(&path).ndjson_filter(errors),
+    ));
+    assert!(ret.is_ok());
+
+    let lines = std::fs::read_to_string(&path).unwrap();
+    assert!(lines.lines().all(|x| x.starts_with(r#"{"kind":"err""#)));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_failures_only() {
+    use nom_tracable::with_captured_trace;
+
+    let (ret, output) = with_captured_trace(|| {
+        expr(LocatedSpan::new_extra(
+            "1+1",
+            TracableInfo::new().failures_only(true),
+        ))
+    });
+    assert_eq!("\"1+1\"", format!("{:?}", ret.unwrap().1));
+
+    // Only the failed parsers and the ancestors of them are shown
+    #[cfg(feature = "trace")]
+    {
+        assert_eq!(
+            trace_arrows(&output),
+            vec![
+                "-> expr",
+                "-> expr_plus",
+                "-> expr",
+                "-> expr_plus",
+                "<- expr_plus",
+                "-> expr_minus",
+                "<- expr_minus",
+            ]
+        );
+        let lines = trace_lines(&output);
+        assert!(lines
+            .iter()
+            .filter(|x| x.contains(" <- "))
+            .all(|x| x.ends_with(": Char at offset 3")));
+    }
+    #[cfg(not(feature = "trace"))]
+    let _ = output;
+}
+
+#[tracable_parser]
+pub fn number(s: Span) -> IResult<Span, String> {
+    let (s, x) = digit0(s)?;
+    Ok((s, x.to_string()))
+}
+
+#[tracable_parser]
+pub fn numbers(s: Span) -> IResult<Span, Vec<String>> {
+    nom::multi::separated_list0(char(','), number)(s)
+}
+
+#[cfg(feature = "trace")]
+#[test]
+fn test_anomalies() {
+    use nom_tracable::{captured_trace, AnomalyKind};
+
+    let ret = numbers(LocatedSpan::new_extra(
+        "1,2,3,,123456789,4",
+        TracableInfo::new().capture(true),
+    ));
+    assert!(ret.is_ok());
+
+    let anomalies = captured_trace().anomalies(4.0);
+    let anomalies: Vec<_> = anomalies
+        .iter()
+        .map(|x| (x.kind, x.offset, x.consumed, x.median))
+        .collect();
+    assert_eq!(
+        anomalies,
+        vec![(AnomalyKind::Zero, 6, 0, 1), (AnomalyKind::Large, 7, 9, 1)]
+    );
+}
+
+#[test]
+fn test_trace_range() {
+    use nom_tracable::with_captured_trace;
+
+    let (ret, output) = with_captured_trace(|| {
+        expr(LocatedSpan::new_extra(
+            "1+1+1+1",
+            TracableInfo::new().trace_range(2..4),
+        ))
+    });
+    assert_eq!("\"1+1+1+1\"", format!("{:?}", ret.unwrap().1));
+
+    #[cfg(feature = "trace")]
+    assert_eq!(
+        &trace_lines(&output)[1..],
+        &[
+            "5 : -> expr : 2 : 1+1+1",
+            "6 : -> expr_plus : 2 : 1+1+1",
+            "7 : -> term : 2 : 1+1+1",
+            "8 : -> term_inner : 2 : 1+1+1",
+            "3 : <- term_inner : 3 : +1+1",
+            "4 : <- term : 3 : +1+1",
+            "18 : <- expr_plus : 7 :",
+            "19 : <- expr : 7 :",
+        ]
+    );
+    #[cfg(not(feature = "trace"))]
+    let _ = output;
+}
+
+#[cfg(feature = "trace")]
+#[test]
+fn test_render_page() {
+    use nom_tracable::{captured_trace, render_page, RenderOptions};
+
+    let _ = expr(LocatedSpan::new_extra(
+        "1+1",
+        TracableInfo::new().capture(true),
+    ));
+    let trace = captured_trace();
+
+    let options = RenderOptions {
+        parser_width: 16,
+        ..Default::default()
+    };
+    let page = render_page(&trace, 1, 2, &options);
+    let lines: Vec<_> = page.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("2          :   -> term       "));
+    assert!(lines[1].starts_with("3          :    -> term_inner"));
+
+    assert_eq!(trace.page_count(2), trace.events.len().div_ceil(2));
+    assert_eq!(render_page(&trace, trace.page_count(2), 2, &options), "");
+    assert_eq!(trace.render(&options).lines().count(), trace.events.len());
+}
+
+#[cfg(feature = "trace")]
+#[test]
+fn test_record_replay() {
+    use nom_tracable::{captured_trace, render, RenderOptions, Trace, TraceLine};
+
+    let _ = include(LocatedSpan::new_extra(
+        "@",
+        TracableInfo::new().capture(true),
+    ));
+    let trace = captured_trace();
+    assert_eq!(trace.includes.len(), 1);
+
+    let path = std::env::temp_dir().join(format!("nom-tracable-{}.trace", std::process::id()));
+    trace.save(&path).unwrap();
+    let loaded = Trace::load(&path);
+    std::fs::write(&path, "forward\t0\t\"expr\"\n").unwrap();
+    let broken = Trace::load(&path);
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(loaded.unwrap(), trace);
+    assert_eq!(broken.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+
+    fn offset_first(x: &TraceLine) -> String {
+        format!("{} {}{}", x.offset, "  ".repeat(x.depth), x.parser)
+    }
+
+    let options = RenderOptions {
+        include: Some(vec![String::from("term")]),
+        line_format: Some(offset_first),
+        ..Default::default()
+    };
+    let included = &trace.includes[0].trace;
+    let rendered = render(&included.events, &options);
+    assert_eq!(rendered.lines().count(), 8);
+    assert!(rendered.lines().all(|x| x.ends_with("term")));
+    assert_eq!(rendered.lines().next(), Some("0       term"));
+}
+
+#[test]
+fn test_watch() {
+    use nom_tracable::with_captured_trace;
+
+    fn last_one(x: &[u8]) -> bool {
+        x == b"1"
+    }
+
+    let (ret, output) = with_captured_trace(|| {
+        expr(LocatedSpan::new_extra(
+            "1+1-1",
+            TracableInfo::new()
+                .forward(false)
+                .backward(false)
+                .watch("1-")
+                .watch_with(last_one),
+        ))
+    });
+    assert_eq!("\"1+1-1\"", format!("{:?}", ret.unwrap().1));
+
+    #[cfg(feature = "trace")]
+    {
+        let lines = trace_lines(&output);
+        let fragment: Vec<_> = lines
+            .iter()
+            .filter_map(|x| x.strip_prefix("*** watch : \"1-\" is entered by "))
+            .collect();
+        assert_eq!(
+            fragment,
+            vec![
+                "expr at offset 2",
+                "expr_plus at offset 2",
+                "term at offset 2",
+                "term_inner at offset 2",
+                "expr_minus at offset 2",
+                "term at offset 2",
+                "term_inner at offset 2",
+            ]
+        );
+        let predicate: Vec<_> = lines
+            .iter()
+            .filter_map(|x| x.strip_prefix("*** watch : predicate is entered by "))
+            .collect();
+        assert_eq!(predicate.len(), 9);
+        assert!(predicate.iter().all(|x| x.ends_with(" at offset 4")));
+
+        // Only the header and the watch lines are shown without forward and backward
+        assert_eq!(lines.len(), 1 + fragment.len() + predicate.len());
+    }
+    #[cfg(not(feature = "trace"))]
+    let _ = output;
+}
+
+#[test]
+fn test_fold_summary() {
+    use nom_tracable::{with_captured_trace, FoldSummary};
+
+    fn summary(x: &FoldSummary) -> String {
+        format!("skipped {} bytes in {} calls", x.consumed, x.calls)
+    }
+
+    let (ret, output) = with_captured_trace(|| {
+        expr(LocatedSpan::new_extra(
+            "1+1",
+            TracableInfo::new()
+                .fold("expr_plus")
+                .fold_summary("expr_plus", summary),
+        ))
+    });
+    assert_eq!("\"1+1\"", format!("{:?}", ret.unwrap().1));
+
+    #[cfg(feature = "trace")]
+    assert_eq!(
+        &trace_lines(&output)[1..],
+        &[
+            "1 : -> expr : 0 : 1+1",
+            "2 : -> expr_plus + : 0 : 1+1",
+            ": … skipped 3 bytes in 11 calls",
+            "1 : <- expr_plus + : 3 :",
+            "2 : <- expr : 3 :",
+        ]
+    );
+    #[cfg(not(feature = "trace"))]
+    let _ = output;
+}
+
+#[cfg(feature = "trace")]
+#[test]
+fn test_sampling() {
+    use nom_tracable::set_sampling;
+
+    let path = std::env::temp_dir().join("nom_tracable_test_sampling.ndjson");
+    let info = TracableInfo::new().sampling(2).ndjson(&path);
+
+    let mut sampled = Vec::new();
+    for _ in 0..4 {
+        let _ = std::fs::remove_file(&path);
+        let _ = expr(LocatedSpan::new_extra("1", info.clone()));
+        sampled.push(path.exists());
+    }
+    assert_eq!(sampled, vec![true, false, true, false]);
+
+    set_sampling(Some(3));
+    let info = TracableInfo::new().ndjson(&path);
+    let mut sampled = Vec::new();
+    for _ in 0..4 {
+        let _ = std::fs::remove_file(&path);
+        let _ = expr(LocatedSpan::new_extra("1", info.clone()));
+        sampled.push(path.exists());
+    }
+    set_sampling(None);
+    assert_eq!(sampled, vec![true, false, false, true]);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_ring_buffer() {
+    use nom_tracable::with_captured_trace;
+
+    // Nothing is shown if the root parser succeeds
+    let (ret, output) = with_captured_trace(|| {
+        expr(LocatedSpan::new_extra(
+            "1+1",
+            TracableInfo::new().ring_buffer(4),
+        ))
+    });
+    assert!(ret.is_ok());
+    assert_eq!(output, "");
+
+    let (ret, output) = with_captured_trace(|| {
+        expr_eof(LocatedSpan::new_extra(
+            "1+",
+            TracableInfo::new().ring_buffer(4),
+        ))
+    });
+    assert!(ret.is_err());
+
+    #[cfg(feature = "trace")]
+    assert_eq!(
+        trace_lines(&output),
+        vec![
+            "… 35 earlier lines dropped",
+            "16 : <- term_inner : 1 : +",
+            "17 : <- term : 1 : +",
+            "18 : <- expr : 1 : +",
+            "19 : <- expr_eof : Eof at offset 1",
+        ]
+    );
+    #[cfg(not(feature = "trace"))]
+    let _ = output;
+}
+
+#[cfg(feature = "trace")]
+#[test]
+fn test_warnings() {
+    use nom_tracable::{set_warning_subscriber, warnings, Warning, WarningKind};
+
+    thread_local!(static RECEIVED: std::cell::RefCell<Vec<String>> = Default::default());
+
+    fn subscriber(x: &Warning) {
+        RECEIVED.with(|r| r.borrow_mut().push(x.to_json()));
+    }
+
+    set_warning_subscriber(Some(subscriber));
+    let ret = expr(LocatedSpan::new_extra(
+        "1+1",
+        TracableInfo::new().invocation_budget("term_inner", 2),
+    ));
+    set_warning_subscriber(None);
+    assert!(ret.is_ok());
+
+    let warnings = warnings();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].kind, WarningKind::InvocationBudget);
+    assert_eq!(warnings[0].parser, "term_inner");
+    assert_eq!(warnings[0].offset, 2);
+
+    let received = RECEIVED.with(|r| r.borrow().clone());
+    assert_eq!(
+        received,
+        vec![concat!(
+            r#"{"kind":"invocation_budget","parser":"term_inner","offset":2,"#,
+            r#""chain":["expr","expr_plus","expr","expr_minus","term","term_inner"],"#,
+            r#""message":"invocation budget : term_inner is called more than 2 times"}"#
+        )]
+    );
+}
+
+#[test]
+fn test_from_env() {
+    std::env::set_var(
+        "NOM_TRACABLE",
+        "forward,width=64,include=expr,term,no-color,unknown",
+    );
+    let info = TracableInfo::from_env();
+    std::env::remove_var("NOM_TRACABLE");
+
+    #[cfg(feature = "trace")]
+    assert_eq!(
+        info,
+        TracableInfo::new()
+            .backward(false)
+            .custom(false)
+            .parser_width(64)
+            .include(&["expr", "term"])
+            .color(false)
+    );
+
+    let ret = expr(LocatedSpan::new_extra("1+1", info));
+    assert_eq!("\"1+1\"", format!("{:?}", ret.unwrap().1));
+}
+
+#[test]
+fn test_from_file() {
+    let dir = std::env::temp_dir();
+    let toml = dir.join(format!("nom-tracable-{}.toml", std::process::id()));
+    let json = dir.join(format!("nom-tracable-{}.json", std::process::id()));
+    std::fs::write(
+        &toml,
+        concat!(
+            "# shared setup\n",
+            "backward = false\n",
+            "custom = false\n",
+            "width = 64 # parser column\n",
+            "include = [\"expr\", \"term\"]\n",
+            "color = false\n",
+        ),
+    )
+    .unwrap();
+    std::fs::write(
+        &json,
+        r#"{"backward": false, "custom": false, "width": 64, "include": ["expr", "term"], "color": false}"#,
+    )
+    .unwrap();
+
+    let from_toml = TracableInfo::from_file(&toml).unwrap();
+    let from_json = TracableInfo::from_file(&json).unwrap();
+
+    std::fs::write(&toml, "include = ['expr', \"term\"]\n").unwrap();
+    let literal = TracableInfo::from_file(&toml);
+
+    // The unsupported syntax and the invalid items are rejected with the reasons
+    let rejected: Vec<_> = [
+        "include = [\"expr\"\n",
+        "[trace]\nwidth = 64\n",
+        "trace.width = 64\n",
+        "width = { value = 64 }\n",
+        "output = stderr\n",
+        "include = '''expr'''\n",
+        "width = \"wide\"\n",
+        "{'width': 64}",
+        "color_scheme = \"\\u001b[1;37m\"\n",
+    ]
+    .iter()
+    .map(|x| {
+        std::fs::write(&toml, x).unwrap();
+        TracableInfo::from_file(&toml)
+    })
+    .collect();
+    let _ = std::fs::remove_file(&toml);
+    let _ = std::fs::remove_file(&json);
+
+    #[cfg(feature = "trace")]
+    {
+        let expected = TracableInfo::new()
+            .backward(false)
+            .custom(false)
+            .parser_width(64)
+            .include(&["expr", "term"])
+            .color(false);
+        assert_eq!(from_toml, expected);
+        assert_eq!(from_json, expected);
+        assert_eq!(
+            literal.unwrap(),
+            TracableInfo::new().include(&["expr", "term"])
+        );
+
+        let reasons: Vec<_> = rejected
+            .into_iter()
+            .map(|x| {
+                let x = x.unwrap_err();
+                assert_eq!(x.kind(), std::io::ErrorKind::InvalidData);
+                String::from(x.to_string().split_once(" : ").unwrap().1)
+            })
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                "unexpected end of file",
+                "line 1 : tables are not supported",
+                "line 1 : dotted key `trace.width` is not supported",
+                "line 1 : inline tables are not supported",
+                "line 1 : unquoted string `stderr` is not supported",
+                "line 1 : multi-line strings are not supported",
+                "invalid value of `width` : wide",
+                "line 1 : single-quoted strings are not supported in JSON",
+                "unknown item `color_scheme`",
+            ]
+        );
+        assert!(TracableInfo::from_file(dir.join("nom-tracable-missing.toml")).is_err());
+    }
+    #[cfg(not(feature = "trace"))]
+    let _ = (from_toml, from_json, literal, rejected);
+}
+
+#[tracable_parser]
+pub fn include(s: Span) -> IResult<Span, String> {
+    let (s, _) = char('@')(s)?;
+    let included = LocatedSpan::new_extra("1+1", s.extra.clone());
+    let (_, x) = nom_tracable::nested_context("included.txt", || expr(included)).unwrap();
+    Ok((s, x))
+}
+
+#[test]
+fn test_nested_context() {
+    let ret = include(LocatedSpan::new_extra(
+        "@",
+        TracableInfo::new().capture(true),
+    ));
+    assert_eq!("\"1+1\"", format!("{:?}", ret.unwrap().1));
+
+    #[cfg(feature = "trace")]
+    {
+        let trace = nom_tracable::captured_trace();
+        assert_eq!(trace.events[0].parser, "include");
+        assert_eq!(trace.includes.len(), 1);
+        assert_eq!(trace.includes[0].source, "included.txt");
+        assert_eq!(trace.includes[0].position, 1);
+        assert_eq!(trace.includes[0].trace.events[0].parser, "expr");
+        assert!(trace.events.iter().all(|x| x.parser != "expr"));
+    }
+}
+
+#[tracable_parser]
+pub fn embedded(s: Span) -> IResult<Span, String> {
+    let (s, x) = nom::sequence::delimited(char('"'), digit1, char('"'))(s)?;
+    // The literal is re-parsed by a fresh span without `nested_context`
+    let literal = LocatedSpan::new_extra(*x.fragment(), TracableInfo::new().capture(true));
+    let (_, y) = expr(literal)?;
+    let (s, z) = term(s)?;
+    Ok((s, format!("{}{}", y, z)))
+}
+
+#[test]
+fn test_nested_session() {
+    use nom_tracable::with_captured_trace;
+
+    let (ret, output) = with_captured_trace(|| {
+        embedded(LocatedSpan::new_extra(
+            "\"1\"1",
+            TracableInfo::new().capture(true),
+        ))
+    });
+    assert_eq!(ret.unwrap().1, "11");
+
+    #[cfg(feature = "trace")]
+    {
+        let trace = nom_tracable::captured_trace();
+        let parsers: Vec<_> = trace.events.iter().map(|x| x.parser.as_str()).collect();
+        assert_eq!(
+            parsers,
+            vec![
+                "embedded",
+                "term",
+                "term_inner",
+                "term_inner",
+                "term",
+                "embedded"
+            ]
+        );
+        assert_eq!(trace.includes.len(), 1);
+        assert_eq!(trace.includes[0].source, "expr");
+        assert_eq!(trace.includes[0].position, 1);
+        assert_eq!(trace.includes[0].trace.events[0].parser, "expr");
+
+        let lines: Vec<_> = output.lines().filter(|x| !x.is_empty()).collect();
+        assert_eq!(
+            lines[2],
+            " === nested parse : expr from embedded at offset 0 ==="
+        );
+        assert_eq!(lines.iter().filter(|x| x.starts_with("forward")).count(), 2);
+        // The counters of the outer session are continued
+        let end = lines
+            .iter()
+            .position(|x| *x == " === end of nested parse : expr ===")
+            .unwrap();
+        assert!(lines[end + 1].starts_with("2 "));
+    }
+    #[cfg(not(feature = "trace"))]
+    let _ = output;
+}
+
+#[test]
+fn test_golden_corpus() {
+    use nom_tracable::GoldenCorpus;
+
+    let path = std::env::temp_dir().join("nom_tracable_test_golden.txt");
+    let inputs = ["1", "1+1", "1-1\t"];
+
+    let golden = GoldenCorpus::record(&inputs, |x| {
+        let _ = expr(LocatedSpan::new_extra(x, TracableInfo::new().capture(true)));
+    });
+    golden.save(&path).unwrap();
+    let golden = GoldenCorpus::load(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    let divergences = golden.verify(|x| {
+        let _ = expr(LocatedSpan::new_extra(x, TracableInfo::new().capture(true)));
+    });
+    assert!(divergences.is_empty());
+
+    // The root parser is changed
+    let divergences = golden.verify(|x| {
+        let _ = expr_eof(LocatedSpan::new_extra(x, TracableInfo::new().capture(true)));
+    });
+
+    #[cfg(feature = "trace")]
+    {
+        assert_eq!(divergences.len(), 3);
+        assert_eq!(divergences[0].0, "1");
+        assert_eq!(divergences[0].1.index, 0);
+        assert_eq!(divergences[0].1.expected.as_ref().unwrap().parser, "expr");
+        assert_eq!(divergences[0].1.actual.as_ref().unwrap().parser, "expr_eof");
+    }
+    #[cfg(not(feature = "trace"))]
+    assert!(divergences.is_empty());
+
+    // The parser names are escaped like the inputs
+    let record = |x: &str| {
+        let _ = nom_tracable::tracable("tab\tname", term)(LocatedSpan::new_extra(
+            x,
+            TracableInfo::new().capture(true),
+        ));
+    };
+    GoldenCorpus::record(&["1"], record).save(&path).unwrap();
+    let golden = GoldenCorpus::load(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+    assert!(golden.verify(record).is_empty());
+}
+
+#[test]
+fn test_color_scheme() {
+    use nom_tracable::{render_event, ColorScheme, RenderOptions, TraceEvent, TraceEventKind};
+
+    let scheme = ColorScheme {
+        forward: "\u{001b}[34m",
+        ..ColorScheme::DEFAULT
+    };
+    let ret = expr(LocatedSpan::new_extra(
+        "1+1",
+        TracableInfo::new().color(true).color_scheme(scheme),
+    ));
+    assert!(ret.is_ok());
+
+    let event = TraceEvent {
+        kind: TraceEventKind::Forward,
+        depth: 0,
+        parser: String::from("expr"),
+        offset: 0,
+        fragment: String::from("1+1"),
+    };
+    let options = RenderOptions {
+        color: true,
+        color_scheme: ColorScheme {
+            forward: "<f>",
+            reset: "</f>",
+            ..ColorScheme::NONE
+        },
+        parser_width: 8,
+        ..Default::default()
+    };
+    assert_eq!(
+        render_event(0, &event, &options),
+        "0          : <f>-> expr</f>  : 1+1"
+    );
+}
+
+#[tracable_parser]
+pub fn tagged_term(s: Span) -> IResult<Span, (String, Option<usize>)> {
+    let (s, x) = term(s)?;
+    Ok((s, (x, nom_tracable::current_event_id())))
+}
+
+#[tracable_parser]
+pub fn tagged_terms(s: Span) -> IResult<Span, Vec<(String, Option<usize>)>> {
+    nom::multi::separated_list1(char('+'), tagged_term)(s)
+}
+
+#[test]
+fn test_current_event_id() {
+    let ret = tagged_terms(LocatedSpan::new_extra(
+        "1+1",
+        TracableInfo::new().capture(true),
+    ));
+    let nodes = ret.unwrap().1;
+    assert_eq!(nom_tracable::current_event_id(), None);
+
+    #[cfg(feature = "trace")]
+    {
+        let trace = nom_tracable::captured_trace();
+        assert_eq!(nodes.len(), 2);
+        assert_ne!(nodes[0].1, nodes[1].1);
+        for (_, id) in nodes {
+            let event = &trace.events[id.unwrap()];
+            assert_eq!(event.kind, nom_tracable::TraceEventKind::Forward);
+            assert_eq!(event.parser, "tagged_term");
+        }
+    }
+    #[cfg(not(feature = "trace"))]
+    assert!(nodes.iter().all(|x| x.1.is_none()));
+}
+
+#[test]
+fn test_escalation() {
+    let ret = expr(LocatedSpan::new_extra(
+        "1+1-1-1",
+        TracableInfo::new().escalation(2, 2),
+    ));
+    assert_eq!("\"1+1-1-1\"", format!("{:?}", ret.unwrap().1));
+
+    let ret = expr_eof(LocatedSpan::new_extra(
+        "1+1-",
+        TracableInfo::new().escalation(0, 1).timing(true),
+    ));
+    assert!(ret.is_err());
+}
+
+#[cfg(feature = "trace")]
+#[test]
+fn test_failure_cooccurrence() {
+    let ret = expr(LocatedSpan::new_extra(
+        "1+1+1",
+        TracableInfo::new().capture(true),
+    ));
+    assert!(ret.is_ok());
+
+    let trace = nom_tracable::captured_trace();
+    assert!(trace.failure_cooccurrence(2).is_empty());
+    let pairs = trace.show_failure_cooccurrence(1);
+    assert_eq!(pairs.len(), 1);
+    assert_eq!(pairs[0].first, "expr_minus");
+    assert_eq!(pairs[0].second, "expr_plus");
+    assert_eq!(pairs[0].offsets, vec![4]);
+
+    let mut csv = Vec::new();
+    trace.write_failure_cooccurrence(&mut csv).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+    assert_eq!(
+        csv.lines().next().unwrap(),
+        r#"parser,"expr_minus","expr_plus""#
+    );
+}
+
+#[cfg(feature = "trace")]
+#[test]
+fn test_call_graph() {
+    use nom_tracable::call_graph;
+
+    let _ = expr(LocatedSpan::new_extra("1+1", TracableInfo::new()));
+    let graph = call_graph();
+    let edge = |caller: &str, callee: &str| {
+        graph
+            .edges
+            .iter()
+            .find(|x| x.caller == caller && x.callee == callee)
+            .map(|x| (x.calls, x.failures))
+    };
+    assert_eq!(graph.edges[0].caller, "term");
+    assert_eq!(graph.edges[0].callee, "term_inner");
+    assert_eq!(edge("term", "term_inner"), Some((4, 0)));
+    assert_eq!(edge("expr", "expr_plus"), Some((2, 1)));
+    assert_eq!(edge("expr", "expr_minus"), Some((1, 1)));
+    assert_eq!(edge("expr_plus", "expr"), Some((1, 0)));
+    assert_eq!(edge("term_inner", "term"), None);
+
+    let mut dot = Vec::new();
+    graph.write_dot(&mut dot).unwrap();
+    let dot = String::from_utf8(dot).unwrap();
+    assert!(dot.starts_with("digraph {"));
+    assert!(dot.contains(r#""term" -> "term_inner" [label="4 (0 failed)"];"#));
+
+    // reset at each parse
+    let _ = term(LocatedSpan::new_extra("1", TracableInfo::new()));
+    assert_eq!(call_graph().edges.len(), 1);
+}
+
+#[test]
+fn test_with_captured_trace() {
+    use nom_tracable::with_captured_trace;
+
+    let (ret, trace) =
+        with_captured_trace(|| term(LocatedSpan::new_extra("1", TracableInfo::new().color(true))));
+    assert!(ret.is_ok());
+
+    #[cfg(feature = "trace")]
+    {
+        assert!(!trace.contains('\u{001b}'));
+        let lines: Vec<_> = trace
+            .lines()
+            .filter_map(|x| x.split(" : ").nth(1))
+            .map(|x| x.trim())
+            .skip(1)
+            .collect();
+        assert_eq!(
+            lines,
+            vec!["-> term", "-> term_inner", "<- term_inner", "<- term"]
+        );
+
+        // nested captures are separated
+        let (_, outer) = with_captured_trace(|| {
+            let (_, inner) = with_captured_trace(|| {
+                term_inner(LocatedSpan::new_extra("1", TracableInfo::new()))
+            });
+            assert!(inner.contains("-> term_inner"));
+            term(LocatedSpan::new_extra("1", TracableInfo::new()))
+        });
+        assert!(outer.contains("-> term"));
+        assert_eq!(outer.matches("-> term_inner").count(), 1);
+    }
+    #[cfg(not(feature = "trace"))]
+    assert!(trace.is_empty());
+}
+
+#[test]
+fn test_snapshot() {
+    use nom_tracable::with_captured_trace;
+
+    let info = TracableInfo::new()
+        .color(true)
+        .timing(true)
+        .thread_tag(true)
+        .snapshot(true);
+    let (_, first) = with_captured_trace(|| expr(LocatedSpan::new_extra("1+1", info.clone())));
+    let (_, second) = with_captured_trace(|| expr(LocatedSpan::new_extra("1+1", info)));
+    assert_eq!(first, second);
+
+    #[cfg(feature = "trace")]
+    {
+        let lines: Vec<_> = first.lines().filter(|x| !x.is_empty()).collect();
+        assert!(lines[0].starts_with("forward    backward   : parser"));
+        assert!(lines[1].starts_with("1                     : -> expr"));
+        assert!(lines.iter().all(|x| !x.contains('[') && !x.contains("µs")));
+    }
+}
+
+#[cfg(feature = "trace")]
+#[test]
+fn test_diff() {
+    use nom_tracable::{captured_trace, diff};
+
+    let info = TracableInfo::new().capture(true);
+    let _ = expr(LocatedSpan::new_extra("1+1", info.clone()));
+    let plus = captured_trace();
+    let _ = expr(LocatedSpan::new_extra("1+1", info.clone()));
+    assert_eq!(plus.diff(&captured_trace()), None);
+
+    let _ = expr(LocatedSpan::new_extra("1-1", info));
+    let minus = captured_trace();
+    let x = diff(&plus.events, &minus.events).unwrap();
+    assert_eq!(x.left_index, x.right_index);
+    assert_eq!(x.stack, vec!["expr", "expr_plus"]);
+    let left = x.left.as_ref().unwrap();
+    let right = x.right.as_ref().unwrap();
+    assert_eq!((left.parser.as_str(), left.offset), ("expr", 2));
+    assert_eq!((right.parser.as_str(), right.offset), ("expr_plus", 0));
+    assert_eq!(
+        x.to_string(),
+        format!(
+            "event {}/{} in expr > expr_plus : -> expr at 2 vs <- expr_plus (err) at 0",
+            x.left_index, x.right_index
+        )
+    );
+
+    // the shorter trace diverges at its end
+    let x = diff(&plus.events[..4], &plus.events).unwrap();
+    assert_eq!(x.left_index, 4);
+    assert_eq!(x.left, None);
+}
+
+#[cfg(feature = "trace")]
+#[test]
+fn test_hotspots() {
+    use nom_tracable::{hotspots, show_hotspots};
+
+    let _ = expr(LocatedSpan::new_extra("1+1", TracableInfo::new()));
+    let x = show_hotspots(1);
+    assert_eq!(x.len(), 1);
+    assert_eq!(x[0].offset, 2);
+    assert_eq!(x[0].visits, 9);
+    assert_eq!(x[0].parsers[0], (String::from("term"), 3));
+    assert_eq!(x[0].parsers[1], (String::from("term_inner"), 3));
+
+    let x = hotspots(10);
+    assert_eq!(
+        x.iter().map(|x| (x.offset, x.visits)).collect::<Vec<_>>(),
+        vec![(2, 9), (0, 4)]
+    );
+}
+
+#[cfg(feature = "trace")]
+#[test]
+fn test_failure_stats() {
+    use nom_tracable::show_failure_stats;
+
+    let _ = expr(LocatedSpan::new_extra("1+1", TracableInfo::new()));
+    let stats = show_failure_stats();
+    let stat = |name: &str| {
+        stats
+            .iter()
+            .find(|x| x.parser == name)
+            .map(|x| (x.success, x.failure, x.wasted_calls))
+    };
+    assert_eq!(stat("expr_plus"), Some((1, 1, 3)));
+    assert_eq!(stat("expr_minus"), Some((0, 1, 3)));
+    assert_eq!(stat("term"), Some((4, 0, 0)));
+    assert_eq!(stats.last().unwrap().wasted_calls, 0);
+    assert!(stats[..2].iter().all(|x| x.parser.starts_with("expr_")));
+    assert_eq!(stats[0].failure_rate() + stats[1].failure_rate(), 1.5);
+}
+
+thread_local!(static MEMO_CALLS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) });
+
+#[tracable_parser(memoize)]
+pub fn memo_term(s: Span) -> IResult<Span, String> {
+    MEMO_CALLS.with(|x| x.set(x.get() + 1));
+    term(s)
+}
+
+#[tracable_parser]
+pub fn memo_binary(s: Span) -> IResult<Span, String> {
+    let (s, x) = memo_term(s)?;
+    let (s, y) = one_of("+-")(s)?;
+    let (s, z) = memo_term(s)?;
+    Ok((s, format!("{}{}{}", x, y, z)))
+}
+
+#[tracable_parser]
+pub fn memo_expr(s: Span) -> IResult<Span, String> {
+    alt((
+        nom::combinator::map(nom::sequence::pair(memo_term, char('*')), |x| x.0),
+        memo_binary,
+    ))(s)
+}
+
+#[test]
+fn test_memoize() {
+    use nom_tracable::with_captured_trace;
+
+    let (ret, trace) =
+        with_captured_trace(|| memo_expr(LocatedSpan::new_extra("1-1", TracableInfo::new())));
+    assert_eq!(ret.unwrap().1, "1-1");
+
+    #[cfg(feature = "trace")]
+    {
+        // memo_term at 0 is taken from the cache in memo_binary
+        assert_eq!(MEMO_CALLS.with(|x| x.get()), 2);
+        assert_eq!(trace.matches(" : memoized").count(), 1);
+        assert!(trace
+            .lines()
+            .any(|x| x.contains("<- memo_term") && x.ends_with(" : memoized")));
+
+        // the cache is reset at each root parse
+        let _ = memo_expr(LocatedSpan::new_extra("1-1", TracableInfo::new()));
+        assert_eq!(MEMO_CALLS.with(|x| x.get()), 4);
+    }
+    #[cfg(not(feature = "trace"))]
+    {
+        let _ = trace;
+        assert_eq!(MEMO_CALLS.with(|x| x.get()), 3);
+    }
+}
+
+#[tracable_parser]
+pub fn nested(s: Span) -> IResult<Span, usize> {
+    let (s, x) = nom::combinator::opt(nom::sequence::delimited(char('('), nested, char(')')))(s)?;
+    Ok((s, x.map_or(0, |x| x + 1)))
+}
+
+/// Error without `ParseError`, which is not stopped by `depth_limit`
+#[derive(Debug)]
+pub struct PlainError;
+
+#[tracable_parser]
+pub fn plain(s: Span) -> IResult<Span, (), PlainError> {
+    Ok((s, ()))
+}
+
+#[test]
+fn test_depth_limit() {
+    let input = "((((((()))))))";
+    let ret = nested(LocatedSpan::new_extra(
+        input,
+        TracableInfo::new().depth_limit(8),
+    ));
+    assert_eq!(ret.unwrap().1, 7);
+
+    let ret = nested(LocatedSpan::new_extra(
+        input,
+        TracableInfo::new().depth_limit(4),
+    ));
+    #[cfg(feature = "trace")]
+    {
+        use nom::error::ErrorKind;
+        use nom_tracable::{warnings, WarningKind};
+
+        match ret {
+            Err(nom::Err::Failure(x)) => {
+                assert_eq!(x.code, ErrorKind::TooLarge);
+                assert_eq!(x.input.location_offset(), 4);
+            }
+            x => panic!("unexpected result : {:?}", x),
+        }
+        let warnings = warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].kind, WarningKind::DepthLimit);
+        assert_eq!(warnings[0].chain.len(), 5);
+    }
+    #[cfg(not(feature = "trace"))]
+    assert_eq!(ret.unwrap().1, 7);
+
+    let ret = plain(LocatedSpan::new_extra(
+        "",
+        TracableInfo::new().depth_limit(0),
+    ));
+    assert!(ret.is_ok());
+}
+
+pub fn nested_combinator(s: Span) -> IResult<Span, usize> {
+    nom_tracable::tracable("nested_combinator", |s| {
+        let (s, x) = nom::combinator::opt(nom::sequence::delimited(
+            char('('),
+            nested_combinator,
+            char(')'),
+        ))(s)?;
+        Ok((s, x.map_or(0, |x| x + 1)))
+    })(s)
+}
+
+#[test]
+fn test_combinator_depth_limit() {
+    let ret = nested_combinator(LocatedSpan::new_extra(
+        "((((((()))))))",
+        TracableInfo::new().depth_limit(4),
+    ));
+    #[cfg(feature = "trace-core")]
+    match ret {
+        Err(nom::Err::Failure(x)) => {
+            assert_eq!(x.code, nom::error::ErrorKind::TooLarge);
+            assert_eq!(x.input.location_offset(), 4);
+        }
+        x => panic!("unexpected result : {:?}", x),
+    }
+    #[cfg(not(feature = "trace-core"))]
+    assert_eq!(ret.unwrap().1, 7);
+}
+
+#[test]
+fn test_session_tag() {
+    use nom_tracable::with_captured_trace;
+
+    let (_, output) = with_captured_trace(|| {
+        term(LocatedSpan::new_extra(
+            "1",
+            TracableInfo::new().session_id("a.txt"),
+        ))
+    });
+    let ids: Vec<_> = (0..2)
+        .map(|_| {
+            let (_, output) = with_captured_trace(|| {
+                term(LocatedSpan::new_extra(
+                    "1",
+                    TracableInfo::new().session_tag(true),
+                ))
+            });
+            output
+        })
+        .collect();
+
+    #[cfg(feature = "trace")]
+    {
+        let lines: Vec<_> = output.lines().filter(|x| !x.is_empty()).collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines.iter().all(|x| x.starts_with("[#a.txt] ")));
+
+        let ids: Vec<usize> = ids
+            .iter()
+            .map(|x| {
+                let line = x.lines().find(|x| !x.is_empty()).unwrap();
+                line[2..line.find(']').unwrap()].parse().unwrap()
+            })
+            .collect();
+        assert!(ids[0] < ids[1]);
+    }
+    #[cfg(not(feature = "trace"))]
+    let _ = (output, ids);
+}
+
+#[tracable_parser]
+pub fn lines_eof(s: Span) -> IResult<Span, Vec<String>> {
+    let (s, x) = nom::multi::separated_list1(char('\n'), expr)(s)?;
+    let (s, _) = nom::combinator::eof(s)?;
+    Ok((s, x))
+}
+
+#[test]
+fn test_failure_snippet() {
+    use nom_tracable::with_captured_trace;
+
+    let (ret, output) = with_captured_trace(|| {
+        lines_eof(LocatedSpan::new_extra(
+            "1+1\n1+\t1-2\n1",
+            TracableInfo::new().failure_snippet(1),
+        ))
+    });
+    assert!(ret.is_err());
+
+    #[cfg(feature = "trace")]
+    {
+        let lines: Vec<_> = output.lines().collect();
+        let start = lines
+            .iter()
+            .position(|x| x.starts_with("error : "))
+            .unwrap();
+        assert_eq!(
+            &lines[start..],
+            &[
+                "error : parse failed at line 2, column 3 (offset 6)",
+                "1 | 1+1",
+                "2 | 1+\t1-2",
+                "  |   ^ lines_eof -> expr -> expr_plus -> expr -> expr_plus -> term -> term_inner",
+                "3 | 1",
+            ]
+        );
+
+        // not displayed on success
+        let (_, output) = with_captured_trace(|| {
+            lines_eof(LocatedSpan::new_extra(
+                "1",
+                TracableInfo::new().failure_snippet(1),
+            ))
+        });
+        assert!(!output.contains("error : "));
+    }
+    #[cfg(not(feature = "trace"))]
+    let _ = output;
+}
+
+#[tracable_parser]
+pub fn quiet_terms(s: Span) -> IResult<Span, Vec<String>> {
+    let (s, x) = term(s)?;
+    let (s, mut y) = nom_tracable::without_trace! {
+        nom::multi::many0(nom::sequence::preceded(char('+'), term))(s)?
+    };
+    y.insert(0, x);
+    Ok((s, y))
+}
+
+#[test]
+fn test_without_trace() {
+    let ret = quiet_terms(LocatedSpan::new_extra(
+        "1+1+1",
+        TracableInfo::new().capture(true),
+    ));
+    assert_eq!(ret.unwrap().1.len(), 3);
+
+    #[cfg(feature = "trace")]
+    {
+        let trace = nom_tracable::captured_trace();
+        let parsers: Vec<_> = trace.iter().map(|x| x.parser.as_str()).collect();
+        assert_eq!(
+            parsers,
+            vec![
+                "quiet_terms",
+                "term",
+                "term_inner",
+                "term_inner",
+                "term",
+                "quiet_terms"
+            ]
+        );
+    }
+}
+
+#[test]
+fn test_output() {
+    use nom_tracable::TraceOutput;
+
+    let ret = expr(LocatedSpan::new_extra(
+        "1+1",
+        TracableInfo::new().output(TraceOutput::Stderr),
+    ));
+    assert!(ret.is_ok());
+    histogram();
+}
+
+#[test]
+fn test_output_file() {
+    let path = std::env::temp_dir().join("nom_tracable_test_output_file.log");
+    let _ = std::fs::remove_file(&path);
+
+    let info = TracableInfo::new().output_file(&path);
+    let ret = expr(LocatedSpan::new_extra("1", info.clone()));
+    assert!(ret.is_ok());
+    let ret = expr(LocatedSpan::new_extra("1+1", info));
+    assert!(ret.is_ok());
+    histogram();
+
+    #[cfg(feature = "trace")]
+    {
+        let log = std::fs::read_to_string(&path).unwrap();
+        #[cfg(not(feature = "log"))]
+        assert_eq!(log.matches("-> expr ").count(), 3);
+        assert!(log.contains("parser     |"));
+    }
+    let _ = std::fs::remove_file(&path);
+}
+
+#[cfg(feature = "trace")]
+#[test]
+fn test_profile_json() {
+    let _ = expr(LocatedSpan::new_extra("1", TracableInfo::new()));
+    let json = nom_tracable::profile().to_json();
+    assert_eq!(
+        json,
+        concat!(
+            r#"{"version":1,"parsers":["#,
+            r#"{"name":"expr","count":1,"total_ns":0,"mean_ns":0},"#,
+            r#"{"name":"expr_minus","count":1,"total_ns":0,"mean_ns":0},"#,
+            r#"{"name":"expr_plus","count":1,"total_ns":0,"mean_ns":0},"#,
+            r#"{"name":"term","count":3,"total_ns":0,"mean_ns":0},"#,
+            r#"{"name":"term_inner","count":3,"total_ns":0,"mean_ns":0}]}"#
+        )
+    );
+}
+
+#[test]
+fn test_flush() {
+    use nom_tracable::TraceFlush;
+
+    let path = std::env::temp_dir().join("nom_tracable_test_flush.log");
+    let _ = std::fs::remove_file(&path);
+
+    let ret = expr(LocatedSpan::new_extra(
+        "1+1",
+        TracableInfo::new()
+            .flush(TraceFlush::Parse)
+            .output_file(&path),
+    ));
+    assert!(ret.is_ok());
+
+    #[cfg(all(feature = "trace", not(feature = "log")))]
+    {
+        let log = std::fs::read_to_string(&path).unwrap();
+        assert!(log.starts_with("\nforward    backward   : parser"));
+        assert!(log.lines().last().unwrap().contains(" : <- expr "));
+    }
+    let _ = std::fs::remove_file(&path);
+}
+
+#[cfg(feature = "trace")]
+#[test]
+fn test_grammar_doc() {
+    use nom_tracable::GrammarDoc;
+
+    let mut doc = GrammarDoc::new();
+    for input in ["1+1", "1-1"] {
+        let _ = expr(LocatedSpan::new_extra(
+            input,
+            TracableInfo::new().capture(true),
+        ));
+        doc.add(&nom_tracable::captured_trace());
+    }
+
+    let expr_plus = &doc.rules["expr_plus"];
+    assert_eq!(expr_plus.callers.iter().collect::<Vec<_>>(), vec!["expr"]);
+    assert!(expr_plus.callees.contains("term"));
+    assert_eq!(expr_plus.examples, vec!["1+1"]);
+    assert_eq!(doc.rules["term"].consumption(), Some((1, 1, 1)));
+
+    let mut markdown = Vec::new();
+    doc.write_markdown(&mut markdown).unwrap();
+    let markdown = String::from_utf8(markdown).unwrap();
+    assert!(markdown.contains("\n## `term_inner`\n"));
+    assert!(markdown.contains("* called by : [`term`](#term)\n"));
+}
+
+/// Future which is pending at the first poll.
+struct YieldNow(bool);
+
+impl std::future::Future for YieldNow {
+    type Output = ();
+
+    fn poll(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<()> {
+        if self.0 {
+            std::task::Poll::Ready(())
+        } else {
+            self.0 = true;
+            cx.waker().wake_by_ref();
+            std::task::Poll::Pending
+        }
+    }
+}
+
+struct NoopWaker;
+
+impl std::task::Wake for NoopWaker {
+    fn wake(self: std::sync::Arc<Self>) {}
+}
+
+fn poll_once<F: std::future::Future + Unpin>(f: &mut F) -> std::task::Poll<F::Output> {
+    let waker = std::task::Waker::from(std::sync::Arc::new(NoopWaker));
+    let mut cx = std::task::Context::from_waker(&waker);
+    std::pin::Pin::new(f).poll(&mut cx)
+}
+
+#[tracable_parser]
+pub async fn async_term(s: Span<'static>) -> IResult<Span<'static>, String> {
+    YieldNow(false).await;
+    async_digit(s).await
+}
+
+#[tracable_parser]
+pub async fn async_digit(s: Span<'static>) -> IResult<Span<'static>, String> {
+    YieldNow(false).await;
+    let (s, x) = char('1')(s)?;
+    Ok((s, x.to_string()))
+}
+
+#[test]
+fn test_async_parser() {
+    let info = TracableInfo::new().capture(true);
+    let mut parse = Box::pin(async_term(LocatedSpan::new_extra("1", info)));
+    assert!(poll_once(&mut parse).is_pending());
+
+    // The parse is resumed on another thread
+    let ret = std::thread::spawn(move || loop {
+        if let std::task::Poll::Ready(x) = poll_once(&mut parse) {
+            #[cfg(feature = "trace")]
+            {
+                let trace = nom_tracable::captured_trace();
+                let events: Vec<_> = trace
+                    .events
+                    .iter()
+                    .map(|x| (x.kind, x.parser.as_str(), x.depth))
+                    .collect();
+                use nom_tracable::TraceEventKind::*;
+                assert_eq!(
+                    events,
+                    vec![
+                        (Forward, "async_term", 0),
+                        (Forward, "async_digit", 1),
+                        (Ok, "async_digit", 1),
+                        (Ok, "async_term", 0),
+                    ]
+                );
+            }
+            break x.map(|x| x.1);
+        }
+    })
+    .join()
+    .unwrap();
+    assert_eq!("\"1\"", format!("{:?}", ret.unwrap()));
+}
+
+type ByteSpan<'a> = LocatedSpan<&'a [u8], TracableInfo>;
+
+#[tracable_parser]
+pub fn byte_header(s: ByteSpan) -> IResult<ByteSpan, u8> {
+    let (s, _) = nom::bytes::complete::tag(&b"\x7fELF"[..])(s)?;
+    nom::number::complete::u8(s)
+}
+
+#[test]
+fn test_byte_fragment() {
+    use nom_tracable::FragmentDisplay;
+    assert_eq!((&b"1+\x00"[..]).display(14), "31 2B 00 |1+.|");
+    assert_eq!((&b"1+\x00"[..]).display(13), "31 2B |1+|…");
+    assert_eq!((&b"1+\x00"[..]).display(6), "…");
+    assert_eq!((&b""[..]).display(14), "");
+
+    assert_eq!("1+1".display(3), "1+1");
+    assert_eq!("1+1+1".display(3), "1+…");
+    assert_eq!("1+1\n+1".display(3), "1+1");
+    assert_eq!("1\t+1\n".display_escaped(8), "1\\t+1\\n");
+    assert_eq!("1\t+1\n".display_escaped(6), "1\\t+1…");
+
+    let input: &[u8] = b"\x7fELF\x02";
+    let ret = byte_header(LocatedSpan::new_extra(
+        input,
+        TracableInfo::new().capture(true),
+    ));
+    assert_eq!(ret.unwrap().1, 2);
+
+    #[cfg(feature = "trace")]
+    {
+        let trace = nom_tracable::captured_trace();
+        assert!(trace.events[0]
+            .fragment
+            .ends_with(": 7F 45 4C 46 02 |.ELF.|"));
+    }
+}
+
+#[test]
+fn test_fragment_format() {
+    fn redact(x: &[u8], width: usize) -> String {
+        "*".repeat(x.len().min(width))
+    }
+
+    let info = TracableInfo::new().capture(true).fragment_format(redact);
+    let ret = term(LocatedSpan::new_extra("1", info));
+    assert_eq!("\"1\"", format!("{:?}", ret.unwrap().1));
+
+    #[cfg(feature = "trace")]
+    {
+        let trace = nom_tracable::captured_trace();
+        assert_eq!(trace.events[0].fragment, "0        : *");
+        assert_eq!(trace.events[2].fragment, "1        : ");
+    }
+}
+
+#[tracable_parser]
+pub fn span_number(s: nom_tracable::TracableStr) -> IResult<nom_tracable::TracableStr, String> {
+    let (s, _) = nom::bytes::complete::tag("n=")(s)?;
+    let (s, x) = digit1(s)?;
+    Ok((s, x.to_string()))
+}
+
+#[tracable_parser]
+pub fn span_byte(s: nom_tracable::TracableBytes) -> IResult<nom_tracable::TracableBytes, u8> {
+    let (s, _) = char('#')(s)?;
+    nom::number::complete::u8(s)
+}
+
+#[test]
+fn test_tracable_span() {
+    use nom_tracable::{TracableBytes, TracableStr};
+
+    let info = TracableInfo::new().capture(true);
+    let (s, x) = span_number(TracableStr::new("n=42;", info.clone())).unwrap();
+    assert_eq!(x, "42");
+    assert_eq!(s.location_offset(), 4);
+    assert_eq!(*s.fragment(), ";");
+
+    #[cfg(feature = "trace")]
+    {
+        let trace = nom_tracable::captured_trace();
+        assert_eq!(trace.events[0].fragment, "0        : n=42;");
+        assert_eq!(trace.events[1].offset, 4);
+    }
+
+    let input: &[u8] = b"#\x07";
+    let (s, x) = span_byte(TracableBytes::new(input, info.clone())).unwrap();
+    assert_eq!(x, 7);
+    assert_eq!(s.location_offset(), 2);
+    let s = nom::Slice::slice(&TracableBytes::new(input, info), 1..);
+    assert_eq!(s.location_offset(), 1);
+    assert!(span_byte(s).is_err());
+}
+
+#[tracable_parser]
+pub fn verbose_term(s: Span) -> IResult<Span, String, nom::error::VerboseError<Span>> {
+    let (s, x) = nom::error::context("one", char('1'))(s)?;
+    Ok((s, x.to_string()))
+}
+
+#[test]
+fn test_verbose_error() {
+    let ret = verbose_term(LocatedSpan::new_extra("1", TracableInfo::new()));
+    assert_eq!("\"1\"", format!("{:?}", ret.unwrap().1));
+
+    let ret = verbose_term(LocatedSpan::new_extra("2", TracableInfo::new()));
+    match ret {
+        Err(nom::Err::Error(e)) => {
+            assert!(e
+                .errors
+                .iter()
+                .any(|x| x.1 == nom::error::VerboseErrorKind::Context("one")));
+        }
+        _ => panic!("unexpected result"),
+    }
+}
+
+#[test]
+fn test_error_detail() {
+    let path = std::env::temp_dir().join("nom_tracable_test_error_detail.log");
+    let _ = std::fs::remove_file(&path);
+
+    let info = TracableInfo::new().color(false).output_file(&path);
+    assert!(term(LocatedSpan::new_extra("2", info.clone())).is_err());
+    assert!(verbose_term(LocatedSpan::new_extra("2", info)).is_err());
+
+    #[cfg(all(feature = "trace", not(feature = "log")))]
+    {
+        let log = std::fs::read_to_string(&path).unwrap();
+        let line = |name: &str| {
+            log.lines()
+                .find(|x| x.contains(&format!("<- {} ", name)))
+                .unwrap()
+                .to_string()
+        };
+        assert!(line("term_inner").ends_with(" : Char at offset 0"));
+        assert!(line("verbose_term").ends_with(" : expected '1' at offset 0, in one at offset 0"));
+    }
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tracable_parser]
+pub fn cut_term(s: Span) -> IResult<Span, String> {
+    let (s, _) = char('(')(s)?;
+    let (s, x) = nom::combinator::cut(term)(s)?;
+    Ok((s, x))
+}
+
+#[tracable_parser]
+pub fn streaming_term(s: Span) -> IResult<Span, String> {
+    let (s, x) = nom::bytes::streaming::tag("11")(s)?;
+    Ok((s, x.to_string()))
+}
+
+#[test]
+fn test_error_kinds() {
+    let path = std::env::temp_dir().join("nom_tracable_test_error_kinds.log");
+    let _ = std::fs::remove_file(&path);
+
+    let info = TracableInfo::new().color(false).output_file(&path);
+    assert!(matches!(
+        cut_term(LocatedSpan::new_extra("(2", info.clone())),
+        Err(nom::Err::Failure(_))
+    ));
+    assert!(matches!(
+        streaming_term(LocatedSpan::new_extra("1", info)),
+        Err(nom::Err::Incomplete(_))
+    ));
+
+    #[cfg(all(feature = "trace", not(feature = "log")))]
+    {
+        let log = std::fs::read_to_string(&path).unwrap();
+        let line = |marker: &str| {
+            log.lines()
+                .find(|x| x.contains(marker))
+                .unwrap()
+                .to_string()
+        };
+        assert!(line("<- term ").ends_with(" : Char at offset 1"));
+        assert!(line("<! cut_term ").ends_with(" : failure : Char at offset 1"));
+        assert!(line("<? streaming_term ").ends_with(" : incomplete (needed 1)"));
+    }
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tracable_parser(show_output)]
+pub fn shown_expr(s: Span) -> IResult<Span, String> {
+    expr(s)
+}
+
+#[test]
+fn test_show_output() {
+    let path = std::env::temp_dir().join("nom_tracable_test_show_output.log");
+    let _ = std::fs::remove_file(&path);
+
+    let info = TracableInfo::new().color(false).output_file(&path);
+    let ret = shown_expr(LocatedSpan::new_extra("1-1+1", info.clone()));
+    assert_eq!(ret.unwrap().1, "1-1+1");
+    let info = info.output_width(4);
+    let ret = shown_expr(LocatedSpan::new_extra("1-1+1", info));
+    assert_eq!(ret.unwrap().1, "1-1+1");
+
+    #[cfg(all(feature = "trace", not(feature = "log")))]
+    {
+        let log = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<_> = log
+            .lines()
+            .filter(|x| x.contains("<- shown_expr "))
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with(" : \"1-1+1\""));
+        assert!(lines[1].ends_with(" : \"1-1"));
+        // The other parsers don't show the value
+        let line = log.lines().find(|x| x.contains("<- expr ")).unwrap();
+        assert!(!line.contains("\"1-1+1\""));
+    }
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tracable_parser(name = "expression")]
+pub fn __rule_17_expr_inner(s: Span) -> IResult<Span, String> {
+    expr(s)
+}
+
+#[test]
+fn test_name() {
+    let path = std::env::temp_dir().join("nom_tracable_test_name.log");
+    let _ = std::fs::remove_file(&path);
+
+    let info = TracableInfo::new().color(false).output_file(&path);
+    let ret = __rule_17_expr_inner(LocatedSpan::new_extra("1", info));
+    assert_eq!(ret.unwrap().1, "1");
+
+    #[cfg(all(feature = "trace", not(feature = "log")))]
+    {
+        let log = std::fs::read_to_string(&path).unwrap();
+        assert!(log.lines().any(|x| x.contains("-> expression ")));
+        assert!(log.lines().any(|x| x.contains("<- expression ")));
+        assert!(!log.contains("__rule_17_expr_inner"));
+    }
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tracable_parser(skip)]
+pub fn skipped_term(s: Span) -> IResult<Span, String> {
+    term(s)
+}
+
+thread_local!(static TRACE_CONDITIONAL: std::cell::Cell<bool> = const { std::cell::Cell::new(false) });
+
+// The condition is not referred without `trace`
+#[cfg_attr(not(feature = "trace"), allow(dead_code))]
+fn trace_conditional() -> bool {
+    TRACE_CONDITIONAL.with(|x| x.get())
+}
+
+#[tracable_parser(if = trace_conditional)]
+pub fn conditional_term(s: Span) -> IResult<Span, String> {
+    term(s)
+}
+
+#[test]
+fn test_skip_and_condition() {
+    let path = std::env::temp_dir().join("nom_tracable_test_skip_and_condition.log");
+    let _ = std::fs::remove_file(&path);
+
+    let info = TracableInfo::new().color(false).output_file(&path);
+    assert_eq!(
+        skipped_term(LocatedSpan::new_extra("1", info.clone()))
+            .unwrap()
+            .1,
+        "1"
+    );
+    assert_eq!(
+        conditional_term(LocatedSpan::new_extra("1", info.clone()))
+            .unwrap()
+            .1,
+        "1"
+    );
+    TRACE_CONDITIONAL.with(|x| x.set(true));
+    assert_eq!(
+        conditional_term(LocatedSpan::new_extra("1", info))
+            .unwrap()
+            .1,
+        "1"
+    );
+
+    #[cfg(all(feature = "trace", not(feature = "log")))]
+    {
+        let log = std::fs::read_to_string(&path).unwrap();
+        assert!(!log.contains("skipped_term"));
+        let lines = log
+            .lines()
+            .filter(|x| x.contains("-> conditional_term "))
+            .count();
+        assert_eq!(lines, 1);
+        // The inner parsers are traced as roots if the outer one is not
+        let lines = log.lines().filter(|x| x.contains("-> term ")).count();
+        assert_eq!(lines, 3);
+    }
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tracable_parser]
+pub fn keyword<'a>(s: Span<'a>, kw: &str) -> IResult<Span<'a>, Span<'a>> {
+    nom::bytes::complete::tag(kw)(s)
+}
+
+#[tracable_parser(show_args)]
+pub fn keyword_repeat<'a>(s: Span<'a>, kw: &str, n: usize) -> IResult<Span<'a>, Vec<Span<'a>>> {
+    nom::multi::count(|s| keyword(s, kw), n)(s)
+}
+
+#[test]
+fn test_extra_args() {
+    let path = std::env::temp_dir().join("nom_tracable_test_extra_args.log");
+    let _ = std::fs::remove_file(&path);
+
+    let info = TracableInfo::new().color(false).output_file(&path);
+    let ret = keyword_repeat(LocatedSpan::new_extra("ifif", info), "if", 2);
+    assert_eq!(ret.unwrap().1.len(), 2);
+
+    #[cfg(all(feature = "trace", not(feature = "log")))]
+    {
+        let log = std::fs::read_to_string(&path).unwrap();
+        let line = log
+            .lines()
+            .find(|x| x.contains("-> keyword_repeat "))
+            .unwrap();
+        assert!(line.ends_with(" : kw = \"if\", n = 2"));
+        let line = log.lines().find(|x| x.contains("-> keyword ")).unwrap();
+        assert!(!line.contains("kw = "));
+    }
+    let _ = std::fs::remove_file(&path);
+}
+
+pub struct Parser {
+    digit: char,
+}
+
+impl Parser {
+    #[tracable_parser]
+    pub fn digits<'a>(&self, s: Span<'a>) -> IResult<Span<'a>, String> {
+        let (s, x) = nom::multi::many1(|s| self.digit(s))(s)?;
+        Ok((s, x.concat()))
+    }
+
+    #[tracable_parser(show_args)]
+    fn digit<'a>(&self, s: Span<'a>) -> IResult<Span<'a>, String> {
+        let (s, x) = char(self.digit)(s)?;
+        Ok((s, x.to_string()))
+    }
+}
+
+pub trait DigitParser {
+    fn digit(&self) -> char;
+
+    #[tracable_parser]
+    fn digit_pair<'a>(&self, s: Span<'a>) -> IResult<Span<'a>, String> {
+        let (s, x) = char(self.digit())(s)?;
+        let (s, y) = char(self.digit())(s)?;
+        Ok((s, format!("{}{}", x, y)))
+    }
+}
+
+impl DigitParser for Parser {
+    fn digit(&self) -> char {
+        self.digit
+    }
+}
+
+#[test]
+fn test_method() {
+    let path = std::env::temp_dir().join("nom_tracable_test_method.log");
+    let _ = std::fs::remove_file(&path);
+
+    let parser = Parser { digit: '7' };
+    let info = TracableInfo::new().color(false).output_file(&path);
+    let ret = parser.digits(LocatedSpan::new_extra("778", info.clone()));
+    assert_eq!(ret.unwrap().1, "77");
+    let ret = parser.digit_pair(LocatedSpan::new_extra("77", info));
+    assert_eq!(ret.unwrap().1, "77");
+
+    #[cfg(all(feature = "trace", not(feature = "log")))]
+    {
+        let log = std::fs::read_to_string(&path).unwrap();
+        assert!(log.lines().any(|x| x.contains("-> digits ")));
+        assert_eq!(log.lines().filter(|x| x.contains("<- digit ")).count(), 3);
+        assert!(log.lines().any(|x| x.contains("-> digit_pair ")));
+        // The receiver is not an argument to show
+        assert!(!log.contains("self ="));
+    }
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tracable_parser]
+pub fn ident<'a, E: nom::error::ParseError<Span<'a>>>(
+    s: Span<'a>,
+) -> IResult<Span<'a>, &'a str, E> {
+    let (s, x) = alpha1(s)?;
+    Ok((s, *x.fragment()))
+}
+
+#[tracable_parser]
+pub fn ident_list<'a, E>(s: Span<'a>) -> IResult<Span<'a>, Vec<&'a str>, E>
+where
+    E: nom::error::ParseError<Span<'a>> + std::fmt::Debug,
+{
+    nom::multi::separated_list1(char(','), ident)(s)
+}
+
+#[test]
+fn test_generics() {
+    let ret =
+        ident_list::<nom::error::Error<_>>(LocatedSpan::new_extra("a,bc", TracableInfo::new()));
+    assert_eq!(ret.unwrap().1, vec!["a", "bc"]);
+    let ret =
+        ident_list::<nom::error::VerboseError<_>>(LocatedSpan::new_extra("1", TracableInfo::new()));
+    assert!(ret.is_err());
+}
+
+#[tracable_parser]
+pub fn idents<'a, E, const N: usize>(mut s: Span<'a>) -> IResult<Span<'a>, [&'a str; N], E>
+where
+    E: nom::error::ParseError<Span<'a>> + std::fmt::Debug,
+{
+    let mut ret = [""; N];
+    for x in ret.iter_mut() {
+        let (rest, y) = ident(s)?;
+        s = rest;
+        *x = y;
+    }
+    Ok((s, ret))
+}
+
+#[test]
+fn test_generic_mut_input() {
+    let ret = idents::<nom::error::Error<_>, 2>(LocatedSpan::new_extra("ab", TracableInfo::new()));
+    assert!(ret.is_err());
+    let ret = idents::<nom::error::Error<_>, 0>(LocatedSpan::new_extra("ab", TracableInfo::new()));
+    assert_eq!(ret.unwrap().1.len(), 0);
+}
+
+pub fn array(s: Span) -> IResult<Span, Vec<String>> {
+    nom_tracable::tracable(
+        "array_items",
+        nom::multi::separated_list0(char(','), nom_tracable::tracable("item", term)),
+    )(s)
+}
+
+#[test]
+fn test_tracable_combinator() {
+    let path = std::env::temp_dir().join("nom_tracable_test_tracable_combinator.log");
+    let _ = std::fs::remove_file(&path);
+
+    let info = TracableInfo::new().color(false).output_file(&path);
+    let ret = array(LocatedSpan::new_extra("1,1,1", info));
+    assert_eq!(ret.unwrap().1.len(), 3);
+
+    #[cfg(all(feature = "trace", not(feature = "log")))]
+    {
+        let log = std::fs::read_to_string(&path).unwrap();
+        assert!(log.lines().any(|x| x.contains("-> array_items ")));
+        assert_eq!(log.lines().filter(|x| x.contains("<- item ")).count(), 3);
+        // The wrapped parser is nested in the combinator
+        assert!(log.lines().any(|x| x.contains(":  -> item ")));
+    }
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tracable_parser(group = "lexer")]
+pub fn lex_one(s: Span) -> IResult<Span, String> {
+    let (s, x) = char('1')(s)?;
+    Ok((s, x.to_string()))
+}
+
+#[tracable_parser(group = "expression")]
+pub fn grouped_expr(s: Span) -> IResult<Span, String> {
+    let (s, x) = lex_one(s)?;
+    let (s, y) = char('+')(s)?;
+    let (s, z) = lex_one(s)?;
+    Ok((s, format!("{}{}{}", x, y, z)))
+}
+
+#[test]
+fn test_group() {
+    let path = std::env::temp_dir().join("nom_tracable_test_group.log");
+    let _ = std::fs::remove_file(&path);
+
+    let info = TracableInfo::new()
+        .color(false)
+        .output_file(&path)
+        .enable_group("lexer", false);
+    let ret = grouped_expr(LocatedSpan::new_extra("1+1", info.clone()));
+    assert_eq!(ret.unwrap().1, "1+1");
+
+    #[cfg(all(feature = "trace", not(feature = "log")))]
+    {
+        let log = std::fs::read_to_string(&path).unwrap();
+        assert!(log.lines().any(|x| x.contains("-> grouped_expr ")));
+        assert!(!log.contains("lex_one"));
+    }
+
+    let info = info.enable_group("lexer", true);
+    let ret = grouped_expr(LocatedSpan::new_extra("1+1", info));
+    assert_eq!(ret.unwrap().1, "1+1");
+
+    #[cfg(all(feature = "trace", not(feature = "log")))]
+    {
+        let log = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(log.lines().filter(|x| x.contains("-> lex_one ")).count(), 2);
+    }
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_parser_colors() {
+    use nom_tracable::ColorScheme;
+
+    let scheme = ColorScheme {
+        forward: "<f>",
+        ok: "<o>",
+        parsers: &["<p0>", "<p1>", "<p2>"],
+        reset: "</>",
+        ..ColorScheme::NONE
+    };
+    let color = scheme.parser("expr").unwrap();
+    assert_eq!(scheme.parser("expr"), Some(color));
+    assert_eq!(ColorScheme::NONE.parser("expr"), None);
+
+    let path = std::env::temp_dir().join("nom_tracable_test_parser_colors.log");
+    let _ = std::fs::remove_file(&path);
+
+    let info = TracableInfo::new()
+        .color(true)
+        .color_scheme(scheme)
+        .parser_colors(true)
+        .output_file(&path);
+    assert!(expr(LocatedSpan::new_extra("1", info)).is_ok());
+
+    #[cfg(all(feature = "trace", not(feature = "log")))]
+    {
+        let log = std::fs::read_to_string(&path).unwrap();
+        let expected = format!("<f>-> </>{}expr</><f>  </>", color);
+        assert!(log.lines().any(|x| x.contains(&expected)));
+        let expected = format!("<o><- </>{}expr</><o>  </>", color);
+        assert!(log.lines().any(|x| x.contains(&expected)));
+    }
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tracable_parser]
+pub fn lines(s: Span) -> IResult<Span, Vec<String>> {
+    nom::multi::separated_list1(char('\n'), expr)(s)
+}
+
+#[test]
+fn test_location_style() {
+    use nom_tracable::LocationStyle;
+
+    let path = std::env::temp_dir().join("nom_tracable_test_location_style.log");
+    let _ = std::fs::remove_file(&path);
+
+    let info = TracableInfo::new().color(false).output_file(&path);
+    let ret = lines(LocatedSpan::new_extra(
+        "1\n1+1",
+        info.clone().location_style(LocationStyle::LineCol),
+    ));
+    assert_eq!(ret.unwrap().1.len(), 2);
+    let ret = lines(LocatedSpan::new_extra(
+        "1\n1+1",
+        info.location_style(LocationStyle::Both),
+    ));
+    assert_eq!(ret.unwrap().1.len(), 2);
+
+    #[cfg(all(feature = "trace", not(feature = "log")))]
+    {
+        let log = std::fs::read_to_string(&path).unwrap();
+        let headers: Vec<_> = log.lines().filter(|x| x.contains(": parser")).collect();
+        assert!(headers[0].ends_with(": line:col   : fragment"));
+        assert!(headers[1].ends_with(": offset   line:col   : fragment"));
+        // `1+1` of the 2nd line is consumed to the 4th column
+        assert!(log
+            .lines()
+            .any(|x| x.contains("<- expr_plus ") && x.contains(": 2:4 ")));
+        assert!(log
+            .lines()
+            .any(|x| x.contains("<- expr_plus ") && x.contains(": 5        2:4 ")));
+    }
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_escape_control() {
+    let info = TracableInfo::new().capture(true).escape_control(true);
+    let ret = lines(LocatedSpan::new_extra("1\n1", info));
+    assert_eq!(ret.unwrap().1.len(), 2);
+
+    #[cfg(feature = "trace")]
+    {
+        let trace = nom_tracable::captured_trace();
+        assert!(trace.events[0].fragment.ends_with(": 1\\n1"));
+    }
+}
+
+#[tracable_parser]
+pub fn optional_term(s: Span) -> IResult<Span, Option<String>> {
+    nom::combinator::opt(term)(s)
+}
+
+#[test]
+fn test_show_consumed() {
+    let path = std::env::temp_dir().join("nom_tracable_test_show_consumed.log");
+    let _ = std::fs::remove_file(&path);
+
+    let info = TracableInfo::new()
+        .color(false)
+        .output_file(&path)
+        .show_consumed(true);
+    assert!(expr(LocatedSpan::new_extra("1+1", info.clone())).is_ok());
+    assert!(optional_term(LocatedSpan::new_extra("+", info)).is_ok());
+
+    #[cfg(all(feature = "trace", not(feature = "log")))]
+    {
+        let log = std::fs::read_to_string(&path).unwrap();
+        let line = |marker: &str| log.lines().find(|x| x.contains(marker)).unwrap();
+        assert!(line("<- term ").ends_with(" : consumed 1"));
+        // The outermost `expr_plus` returns last
+        let last = log.lines().rfind(|x| x.contains("<- expr_plus "));
+        assert!(last.unwrap().ends_with(" : consumed 3"));
+        assert!(line("<- optional_term ").ends_with(" : consumed 0"));
+    }
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_show_matched() {
+    let path = std::env::temp_dir().join("nom_tracable_test_show_matched.log");
+    let _ = std::fs::remove_file(&path);
+
+    let info = TracableInfo::new()
+        .color(false)
+        .output_file(&path)
+        .show_matched(true);
+    assert!(expr(LocatedSpan::new_extra("1+1-1", info)).is_ok());
+
+    #[cfg(all(feature = "trace", not(feature = "log")))]
+    {
+        let log = std::fs::read_to_string(&path).unwrap();
+        let line = log.lines().find(|x| x.contains("<- term ")).unwrap();
+        assert!(line.ends_with(" : 1        : 1"));
+        let line = log.lines().rfind(|x| x.contains("<- expr ")).unwrap();
+        assert!(line.ends_with(" : 5        : 1+1-1"));
+        // The forward trace shows the remaining input
+        let line = log.lines().rfind(|x| x.contains("-> expr ")).unwrap();
+        assert!(line.ends_with(" : 4        : 1"));
+    }
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_tree_style() {
+    let path = std::env::temp_dir().join("nom_tracable_test_tree_style.log");
+    let _ = std::fs::remove_file(&path);
+
+    let info = TracableInfo::new()
+        .color(false)
+        .output_file(&path)
+        .parser_width(24)
+        .tree_style(true);
+    assert!(term(LocatedSpan::new_extra("1", info)).is_ok());
+
+    #[cfg(all(feature = "trace", not(feature = "log")))]
+    {
+        let log = std::fs::read_to_string(&path).unwrap();
+        let parsers: Vec<_> = log
+            .lines()
+            .filter(|x| !x.is_empty())
+            .skip(1)
+            .map(|x| x.split(" : ").nth(1).unwrap().trim_end())
+            .collect();
+        assert_eq!(
+            parsers,
+            vec!["-> term", "├─-> term_inner", "└─<- term_inner", "<- term"]
+        );
+    }
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tracable_parser]
+pub fn repeated_terms(s: Span) -> IResult<Span, Vec<String>> {
+    let (s, x) = nom::multi::many0(term)(s)?;
+    let (s, _) = char('+')(s)?;
+    let (s, y) = nom::multi::many0(term)(s)?;
+    Ok((s, [x, y].concat()))
+}
+
+#[test]
+fn test_collapse_repeats() {
+    let path = std::env::temp_dir().join("nom_tracable_test_collapse_repeats.log");
+    let _ = std::fs::remove_file(&path);
+
+    let info = TracableInfo::new()
+        .color(false)
+        .output_file(&path)
+        .parser_width(24)
+        .collapse_repeats(true);
+    let ret = repeated_terms(LocatedSpan::new_extra("1111+11", info));
+    assert_eq!(ret.unwrap().1.len(), 6);
+
+    #[cfg(all(feature = "trace", not(feature = "log")))]
+    {
+        let log = std::fs::read_to_string(&path).unwrap();
+        let parsers: Vec<_> = log
+            .lines()
+            .filter(|x| !x.is_empty())
+            .skip(1)
+            .map(|x| x.split(" : ").nth(1).unwrap().trim_end())
+            .collect();
+        assert_eq!(
+            parsers,
+            vec![
+                "-> repeated_terms",
+                " -> term",
+                "  -> term_inner",
+                "  <- term_inner",
+                " <- term",
+                " … repeated 3×",
+                " -> term",
+                "  -> term_inner",
+                "  <- term_inner",
+                " <- term",
+                " -> term",
+                "  -> term_inner",
+                "  <- term_inner",
+                " <- term",
+                " … repeated 1×",
+                " -> term",
+                "  -> term_inner",
+                "  <- term_inner",
+                " <- term",
+                "<- repeated_terms",
+            ]
+        );
+    }
+    let _ = std::fs::remove_file(&path);
+}
+
+thread_local!(static DEBUGGER_COMMANDS: std::cell::RefCell<Vec<&'static str>> = Default::default());
+
+fn debugger_command() -> Option<String> {
+    DEBUGGER_COMMANDS.with(|x| x.borrow_mut().pop().map(String::from))
+}
+
+#[test]
+fn test_breakpoint() {
+    use nom_tracable::{set_debugger_input, with_captured_trace};
+
+    // The commands are popped from the end
+    DEBUGGER_COMMANDS.with(|x| *x.borrow_mut() = vec!["c", "p", "n", "s", "bt", "x"]);
+    set_debugger_input(Some(debugger_command));
+    let (ret, output) = with_captured_trace(|| {
+        expr(LocatedSpan::new_extra(
+            "1+1",
+            TracableInfo::new()
+                .forward(false)
+                .backward(false)
+                .breakpoint("term"),
+        ))
+    });
+    set_debugger_input(None);
+    assert_eq!(ret.unwrap().1, "1+1");
+
+    #[cfg(feature = "trace")]
+    {
+        let lines: Vec<_> = output.lines().filter(|x| !x.is_empty()).collect();
+        assert_eq!(
+            &lines[1..],
+            &[
+                "*** break : term at offset 0 (depth 2) : breakpoint",
+                "unknown command `x` : `help` shows the commands",
+                "#0 expr at offset 0",
+                "#1 expr_plus at offset 0",
+                "#2 term at offset 0",
+                "*** break : term_inner at offset 0 (depth 3) : step",
+                "*** break : expr at offset 2 (depth 2) : step",
+                "\"1\"",
+                "*** break : term at offset 2 (depth 4) : breakpoint",
+                "*** break : term at offset 2 (depth 4) : breakpoint",
+                "*** break : term at offset 2 (depth 3) : breakpoint",
+            ]
+        );
+    }
+    #[cfg(not(feature = "trace"))]
+    let _ = output;
+}
+
+fn offset_first(x: &nom_tracable::TraceLine) -> String {
+    let arrow = match x.kind {
+        nom_tracable::TraceEventKind::Forward => "->",
+        _ => "<-",
+    };
+    format!(
+        "{:>3} {}{} {}{}",
+        x.offset,
+        "  ".repeat(x.depth),
+        arrow,
+        x.parser,
+        x.suffix
+    )
+}
+
+#[test]
+fn test_line_format() {
+    use nom_tracable::with_captured_trace;
+
+    let (ret, output) = with_captured_trace(|| {
+        expr(LocatedSpan::new_extra(
+            "1",
+            TracableInfo::new()
+                .line_format(offset_first)
+                .show_consumed(true),
+        ))
+    });
+    assert!(ret.is_ok());
+
+    #[cfg(feature = "trace")]
+    {
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(
+            &lines[..7],
+            &[
+                "  0 -> expr",
+                "  0   -> expr_plus",
+                "  0     -> term",
+                "  0       -> term_inner",
+                "  1       <- term_inner : consumed 1",
+                "  1     <- term : consumed 1",
+                "  0   <- expr_plus : Char at offset 1",
+            ]
+        );
+        assert_eq!(lines.last(), Some(&"  1 <- expr : consumed 1"));
+    }
+    #[cfg(not(feature = "trace"))]
+    let _ = output;
+}
+
+#[test]
+fn test_cumulative_stats() {
+    use nom_tracable::{cumulative_stats, reset_cumulative, show_cumulative_stats};
+
+    reset_cumulative();
+    for _ in 0..2 {
+        let ret = expr(LocatedSpan::new_extra("1", TracableInfo::new()));
+        assert!(ret.is_ok());
+    }
+    let stats = show_cumulative_stats();
+
+    #[cfg(feature = "trace")]
+    {
+        assert_eq!(stats.parses, 2);
+        assert_eq!(stats.forward, 18);
+        assert_eq!(stats.backward, 18);
+        let parsers: Vec<_> = stats
+            .parsers
+            .iter()
+            .map(|x| (x.parser.as_str(), x.calls, x.success, x.failure))
+            .collect();
+        assert_eq!(
+            parsers,
+            &[
+                ("term", 6, 6, 0),
+                ("term_inner", 6, 6, 0),
+                ("expr", 2, 2, 0),
+                ("expr_minus", 2, 0, 2),
+                ("expr_plus", 2, 0, 2),
+            ]
+        );
+    }
+
+    reset_cumulative();
+    assert_eq!(cumulative_stats(), Default::default());
+    #[cfg(not(feature = "trace"))]
+    let _ = stats;
+}
+
+#[test]
+fn test_event_hooks() {
+    use nom_tracable::{with_captured_trace, TraceEvent, TraceEventKind};
+
+    thread_local!(static EVENTS: std::cell::RefCell<Vec<String>> = Default::default());
+
+    fn hook(x: &TraceEvent) {
+        let kind = match x.kind {
+            TraceEventKind::Forward => "->",
+            TraceEventKind::Ok => "ok",
+            _ => "err",
+        };
+        EVENTS.with(|e| {
+            e.borrow_mut()
+                .push(format!("{} {} {}", kind, x.parser, x.offset))
+        });
+    }
+
+    let (ret, output) = with_captured_trace(|| {
+        term(LocatedSpan::new_extra(
+            "1",
+            TracableInfo::new()
+                .forward(false)
+                .backward(false)
+                .on_forward(hook)
+                .on_backward(hook),
+        ))
+    });
+    assert!(ret.is_ok());
+
+    #[cfg(feature = "trace")]
+    {
+        // The events are hooked instead of being displayed
+        assert!(!output.contains("term_inner"));
+        assert_eq!(
+            EVENTS.with(|e| e.take()),
+            &[
+                "-> term 0",
+                "-> term_inner 0",
+                "ok term_inner 1",
+                "ok term 1"
+            ]
+        );
+    }
+    #[cfg(not(feature = "trace"))]
+    let _ = output;
+}
+
+#[test]
+fn test_features() {
+    use nom_tracable::{captured_trace, failure_stats, tracable};
+
+    // The input carries nothing if the trace is compiled out
+    #[cfg(not(feature = "trace-core"))]
+    assert_eq!(std::mem::size_of::<TracableInfo>(), 0);
+
+    fn one(s: Span) -> IResult<Span, char> {
+        tracable("one", char('1'))(s)
+    }
+
+    let ret = one(LocatedSpan::new_extra(
+        "1",
+        TracableInfo::new().capture(true).color(true),
+    ));
+    assert!(ret.is_ok());
+
+    // The parts of the trace are collected only by the features
+    assert_eq!(!failure_stats().is_empty(), cfg!(feature = "trace-stats"));
+    assert_eq!(
+        !captured_trace().events.is_empty(),
+        cfg!(feature = "trace-capture")
+    );
+}
+
+#[test]
+fn test_summary() {
+    use nom_tracable::with_captured_trace;
+
+    let (ret, output) = with_captured_trace(|| {
+        expr(LocatedSpan::new_extra(
+            "1",
+            TracableInfo::new()
+                .forward(false)
+                .backward(false)
+                .snapshot(true)
+                .summary(true),
+        ))
+    });
+    assert!(ret.is_ok());
+
+    #[cfg(feature = "trace")]
+    {
+        let lines: Vec<_> = output.lines().filter(|x| !x.is_empty()).collect();
+        assert_eq!(
+            &lines[1..],
+            &[
+                "=== summary : expr ===",
+                "forward 9 : backward 9 : failed 2",
+                "max depth 4 : expr -> expr_plus -> term -> term_inner",
+                "most called : term (3), term_inner (3), expr (1)",
+            ]
+        );
+    }
+    #[cfg(not(feature = "trace"))]
+    let _ = output;
+}
+
+// `tracable` depends on `trace-core` of nom-tracable itself, so this checks the summary without `trace-stats`
+#[cfg(feature = "trace-core")]
+#[test]
+fn test_summary_counts() {
+    use nom_tracable::{tracable, with_captured_trace};
+
+    let mut pair = tracable(
+        "pair",
+        nom::sequence::pair(tracable("one", char('1')), tracable("two", char('2'))),
+    );
+    let (ret, output): (IResult<Span, _>, _) = with_captured_trace(|| {
+        pair(LocatedSpan::new_extra(
+            "13",
+            TracableInfo::new()
+                .forward(false)
+                .backward(false)
+                .snapshot(true)
+                .summary(true),
+        ))
+    });
+    assert!(ret.is_err());
+
+    let lines: Vec<_> = output.lines().filter(|x| !x.is_empty()).collect();
+    assert_eq!(
+        &lines[1..],
+        &[
+            "=== summary : pair ===",
+            "forward 3 : backward 3 : failed 2",
+            "max depth 2 : pair -> one",
+            "most called : one (1), pair (1), two (1)",
+        ]
+    );
+}